@@ -7,6 +7,7 @@ extern crate terminus_spaceport;
 
 use rand::Rng;
 use std::ops::Deref;
+use terminus_spaceport::memory::allocator::Allocator;
 use terminus_spaceport::memory::region::*;
 use terminus_spaceport::space::Space;
 use std::ptr::{null_mut,null};
@@ -44,6 +45,77 @@ fn bench_model_access(b: &mut Bencher) {
     };
 }
 
+#[bench]
+fn bench_lazy_model_access(b: &mut Bencher) {
+    let region = GHEAP.lazy_alloc(0x1_0000_0000, 1).unwrap();
+    let mut rng = rand::thread_rng();
+    let mut addrs = vec![];
+    for _ in 0..MAX_RND {
+        addrs.push(((rng.gen::<u64>() % 0x1_0000_0000) >> 3) << 3)
+    }
+    let mut i = 0;
+    let mut get_addr = || {
+        let data = addrs.get(i).unwrap();
+        if i == MAX_RND - 1 {
+            i = 0
+        } else {
+            i = i + 1
+        }
+        data
+    };
+    b.iter(|| {
+        U64Access::write(region.deref(), get_addr(), 0xaa);
+        U64Access::read(region.deref(), get_addr());
+    });
+    #[cfg(feature = "memprof")]
+    unsafe {
+        jemalloc_sys::malloc_stats_print(None, null_mut(), null())
+    };
+}
+
+const IMAGE_SIZE: usize = 0x10_0000;
+
+fn bench_bytes_access(b: &mut Bencher, region: &Region) {
+    let image = (0..IMAGE_SIZE).map(|i| i as u8).collect::<Vec<u8>>();
+    let mut data = vec![0u8; IMAGE_SIZE];
+    b.iter(|| {
+        BytesAccess::write(region, &region.info.base, &image).unwrap();
+        BytesAccess::read(region, &region.info.base, &mut data).unwrap();
+    });
+    #[cfg(feature = "memprof")]
+    unsafe {
+        jemalloc_sys::malloc_stats_print(None, null_mut(), null())
+    };
+}
+
+#[bench]
+fn bench_model_bytes_access(b: &mut Bencher) {
+    let region = GHEAP.alloc(IMAGE_SIZE as u64, 1).unwrap();
+    bench_bytes_access(b, region.deref())
+}
+
+#[bench]
+fn bench_lazy_model_bytes_access(b: &mut Bencher) {
+    let region = GHEAP.lazy_alloc(IMAGE_SIZE as u64, 1).unwrap();
+    bench_bytes_access(b, region.deref())
+}
+
+//byte by byte as lazy models did before bytes were copied a page at a time, for comparison
+#[bench]
+fn bench_lazy_model_bytes_access_per_byte(b: &mut Bencher) {
+    let region = GHEAP.lazy_alloc(IMAGE_SIZE as u64, 1).unwrap();
+    let image = (0..IMAGE_SIZE).map(|i| i as u8).collect::<Vec<u8>>();
+    let mut data = vec![0u8; IMAGE_SIZE];
+    b.iter(|| {
+        for (i, v) in image.iter().enumerate() {
+            U8Access::write(region.deref(), &(region.info.base + i as u64), *v);
+        }
+        for (i, v) in data.iter_mut().enumerate() {
+            *v = U8Access::read(region.deref(), &(region.info.base + i as u64));
+        }
+    });
+}
+
 #[bench]
 fn bench_space_access(b: &mut Bencher) {
     let region = GHEAP.alloc(0x1_0000_0000, 1).unwrap();
@@ -88,3 +160,56 @@ fn bench_space_access(b: &mut Bencher) {
         jemalloc_sys::malloc_stats_print(None, null_mut(), null())
     };
 }
+
+const MAX_BLOCKS: usize = 10000;
+
+//alloc/free with MAX_BLOCKS live blocks in a fragmented heap, the free list used to be walked linearly
+#[bench]
+fn bench_allocator_fragmented(b: &mut Bencher) {
+    let mut allocator = Allocator::new(0, 0x1_0000_0000);
+    let mut rng = rand::thread_rng();
+    let mut blocks = vec![];
+    for _ in 0..MAX_BLOCKS * 2 {
+        let size = rng.gen::<u64>() % 0x1000 + 1;
+        blocks.push(allocator.alloc(size, 1 << (rng.gen::<u64>() % 6)).unwrap().base)
+    }
+    //free every other block to leave holes of random sizes
+    let mut live = vec![];
+    for (i, base) in blocks.into_iter().enumerate() {
+        if i & 1 == 0 {
+            allocator.free(base).unwrap()
+        } else {
+            live.push(base)
+        }
+    }
+    let mut sizes = vec![];
+    for _ in 0..MAX_RND {
+        sizes.push((rng.gen::<u64>() % 0x1000 + 1, 1 << (rng.gen::<u64>() % 6)))
+    }
+    let mut i = 0;
+    b.iter(|| {
+        let (size, align) = sizes[i];
+        i = if i == MAX_RND - 1 { 0 } else { i + 1 };
+        let base = allocator.alloc(size, align).unwrap().base;
+        let j = i % live.len();
+        allocator.free(live[j]).unwrap();
+        live[j] = base;
+    });
+}
+
+#[bench]
+fn bench_allocator_alloc_free(b: &mut Bencher) {
+    let mut allocator = Allocator::new(0, 0x1_0000_0000);
+    let mut rng = rand::thread_rng();
+    let mut sizes = vec![];
+    for _ in 0..MAX_RND {
+        sizes.push((rng.gen::<u64>() % 0x1000 + 1, 1 << (rng.gen::<u64>() % 6)))
+    }
+    let mut i = 0;
+    b.iter(|| {
+        let (size, align) = sizes[i];
+        i = if i == MAX_RND - 1 { 0 } else { i + 1 };
+        let base = allocator.alloc(size, align).unwrap().base;
+        allocator.free(base).unwrap();
+    });
+}
@@ -10,6 +10,14 @@ void* tsc_new_locked_allocator(const uint64_t base, const uint64_t size) {
     return __ts_new_locked_allocator(base, size);
 }
 
+void* tsc_new_allocator_with_policy(const uint64_t base, const uint64_t size, const uint32_t policy) {
+    return __ts_new_allocator_with_policy(base, size, policy);
+}
+
+void* tsc_new_locked_allocator_with_policy(const uint64_t base, const uint64_t size, const uint32_t policy) {
+    return __ts_new_locked_allocator_with_policy(base, size, policy);
+}
+
 uint64_t tsc_alloc_addr(const void* allocator, const uint64_t size, const uint64_t align) {
     return __ts_alloc_addr(allocator, size, align);
 }
@@ -18,6 +26,54 @@ void tsc_free_addr(const void* allocator, const uint64_t addr) {
     __ts_free_addr(allocator, addr);
 }
 
+uint64_t tsc_alloc_addr_labeled(const void* allocator, const uint64_t size, const uint64_t align, const char* label) {
+    return __ts_alloc_addr_labeled(allocator, size, align, label);
+}
+
+void tsc_allocator_stats(const void* allocator, ts_alloc_stats* stats) {
+    __ts_allocator_stats(allocator, stats);
+}
+
+void tsc_reserve_addr(const void* allocator, const uint64_t base, const uint64_t size) {
+    __ts_reserve_addr(allocator, base, size);
+}
+
+uint64_t tsc_realloc_addr(const void* allocator, const uint64_t addr, const uint64_t size, const uint64_t align) {
+    return __ts_realloc_addr(allocator, addr, size, align);
+}
+
+void tsc_free_addr_range(const void* allocator, const uint64_t base, const uint64_t size) {
+    __ts_free_addr_range(allocator, base, size);
+}
+
+uint32_t tsc_try_alloc_addr(const void* allocator, const uint64_t size, const uint64_t align, uint64_t* addr) {
+    return __ts_try_alloc_addr(allocator, size, align, addr);
+}
+
+uint32_t tsc_try_free_addr(const void* allocator, const uint64_t addr) {
+    return __ts_try_free_addr(allocator, addr);
+}
+
+uint32_t tsc_try_reserve_addr(const void* allocator, const uint64_t base, const uint64_t size) {
+    return __ts_try_reserve_addr(allocator, base, size);
+}
+
+uint32_t tsc_try_realloc_addr(const void* allocator, const uint64_t addr, const uint64_t size, const uint64_t align, uint64_t* new_addr) {
+    return __ts_try_realloc_addr(allocator, addr, size, align, new_addr);
+}
+
+uint32_t tsc_try_free_addr_range(const void* allocator, const uint64_t base, const uint64_t size) {
+    return __ts_try_free_addr_range(allocator, base, size);
+}
+
+void tsc_allocator_print_stats(const void* allocator) {
+    __ts_allocator_print_stats(allocator);
+}
+
+void tsc_allocator_dump(const void* allocator) {
+    __ts_allocator_dump(allocator);
+}
+
 void* tsc_space() {
     return __ts_space();
 }
@@ -39,6 +95,17 @@ void* tsc_add_region(const void* space, const char* name, void* region) {
     return ptr;
 }
 
+void* tsc_add_region_with_priority(const void* space, const char* name, void* region, const int32_t priority) {
+    void* ptr = __ts_add_region_with_priority(space,name, region, priority);
+    __ts_clean_region(space,name,ptr);
+    __ts_clean_region(space,name,region);
+    return ptr;
+}
+
+void tsc_set_region_enabled(const void* space, const char* name, const uint32_t enabled) {
+    __ts_set_region_enabled(space, name, enabled != 0);
+}
+
 void* tsc_alloc_region(void* heap, uint64_t size, uint64_t align) {
     assert(heap != NULL);
     return __ts_alloc_region(heap, size, align, false);
@@ -52,6 +119,19 @@ void* tsc_lazy_root_region(uint64_t size, uint64_t align) {
     return __ts_alloc_region(NULL, size, align, true);
 }
 
+uint32_t tsc_try_alloc_region(void* heap, uint64_t size, uint64_t align, void** region) {
+    assert(heap != NULL);
+    return __ts_try_alloc_region(heap, size, align, false, region);
+}
+
+uint32_t tsc_try_root_region(uint64_t size, uint64_t align, void** region) {
+    return __ts_try_alloc_region(NULL, size, align, false, region);
+}
+
+uint32_t tsc_try_lazy_root_region(uint64_t size, uint64_t align, void** region) {
+    return __ts_try_alloc_region(NULL, size, align, true, region);
+}
+
 void* tsc_map_region(const void* region, uint64_t base) {
     return __ts_map_region(region, base);
 }
@@ -64,6 +144,10 @@ void* tsc_heap(const void* region) {
     return __ts_heap(region);
 }
 
+void* tsc_heap_with_policy(const void* region, const uint32_t policy) {
+    return __ts_heap_with_policy(region, policy);
+}
+
 void tsc_free_region(const void* region) {
     __ts_free_region(region);
 }
@@ -72,6 +156,42 @@ void tsc_free_heap(const void* heap) {
     __ts_free_heap(heap);
 }
 
+void tsc_heap_stats(const void* heap, ts_alloc_stats* stats) {
+    __ts_heap_stats(heap, stats);
+}
+
+void* tsc_reserve_region(const void* heap, uint64_t base, uint64_t size) {
+    return __ts_reserve_region(heap, base, size);
+}
+
+void tsc_realloc_region(const void* heap, void* region, uint64_t size, uint64_t align) {
+    __ts_realloc_region(heap, region, size, align);
+}
+
+void* tsc_free_region_range(const void* heap, void* region, uint64_t base, uint64_t size) {
+    return __ts_free_region_range(heap, region, base, size);
+}
+
+uint32_t tsc_try_reserve_region(const void* heap, uint64_t base, uint64_t size, void** region) {
+    return __ts_try_reserve_region(heap, base, size, region);
+}
+
+uint32_t tsc_try_realloc_region(const void* heap, void* region, uint64_t size, uint64_t align) {
+    return __ts_try_realloc_region(heap, region, size, align);
+}
+
+uint32_t tsc_try_free_region_range(const void* heap, void* region, uint64_t base, uint64_t size, void** upper) {
+    return __ts_try_free_region_range(heap, region, base, size, upper);
+}
+
+void tsc_heap_print_stats(const void* heap) {
+    __ts_heap_print_stats(heap);
+}
+
+void tsc_heap_dump(const void* heap) {
+    __ts_heap_dump(heap);
+}
+
 void tsc_region_write_u8(const void* region, const uint64_t addr, const uint8_t data) {
     __ts_region_write_u8(region, addr, data);
 }
@@ -130,8 +250,92 @@ uint64_t tsc_space_read_u64(const void* space, const uint64_t addr){
     return __ts_space_read_u64(space, addr);
 }
 
+void tsc_space_set_unmapped_policy(const void* space, const uint32_t policy, const uint64_t value) {
+    __ts_space_set_unmapped_policy(space, policy, value, NULL);
+}
+
+void tsc_space_set_unmapped_callback(const void* space, ts_unmapped_callback callback) {
+    __ts_space_set_unmapped_policy(space, TS_UNMAPPED_CALLBACK, 0, callback);
+}
+
+bool tsc_space_last_fault(const void* space, uint32_t* kind, uint64_t* addr, uint32_t* size) {
+    return __ts_space_last_fault(space, kind, addr, size);
+}
+
+void tsc_space_clear_last_fault(const void* space) {
+    __ts_space_clear_last_fault(space);
+}
+
 ts_mem_info* tsc_region_info(const void* region){
     return (ts_mem_info*)__ts_region_info(region);
 }
 
+void* tsc_load_elf(const void* space, const char* file_name) {
+    return __ts_load_elf(space, file_name);
+}
+
+uint64_t tsc_elf_entry(const void* elf) {
+    return __ts_elf_entry(elf);
+}
+
+bool tsc_elf_symbol(const void* elf, const char* name, uint64_t* addr) {
+    return __ts_elf_symbol(elf, name, addr);
+}
+
+void tsc_free_elf(const void* elf) {
+    __ts_free_elf(elf);
+}
+
+void tsc_region_load_image(const void* region, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t offset) {
+    __ts_region_load_image(region, file_name, format, word_size, offset);
+}
+
+void tsc_space_load_image(const void* space, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t offset) {
+    __ts_space_load_image(space, file_name, format, word_size, offset);
+}
+
+void tsc_region_dump_image(const void* region, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t addr, const uint64_t size, const uint64_t offset) {
+    __ts_region_dump_image(region, file_name, format, word_size, addr, size, offset);
+}
+
+void tsc_space_dump_image(const void* space, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t addr, const uint64_t size, const uint64_t offset) {
+    __ts_space_dump_image(space, file_name, format, word_size, addr, size, offset);
+}
+
+void* tsc_map_region_read_only(const void* region, uint64_t base, const uint32_t policy) {
+    return __ts_map_region_read_only(region, base, policy);
+}
+
+void tsc_region_backdoor_write_u8(const void* region, const uint64_t addr, const uint8_t data) {
+    __ts_region_backdoor_write_u8(region, addr, data);
+}
+
+void tsc_region_backdoor_write_u16(const void* region, const uint64_t addr, const uint16_t data) {
+    __ts_region_backdoor_write_u16(region, addr, data);
+}
+
+void tsc_region_backdoor_write_u32(const void* region, const uint64_t addr, const uint32_t data) {
+    __ts_region_backdoor_write_u32(region, addr, data);
+}
+
+void tsc_region_backdoor_write_u64(const void* region, const uint64_t addr, const uint64_t data) {
+    __ts_region_backdoor_write_u64(region, addr, data);
+}
+
+void tsc_space_backdoor_write_u8(const void* space, const uint64_t addr, const uint8_t data) {
+    __ts_space_backdoor_write_u8(space, addr, data);
+}
+
+void tsc_space_backdoor_write_u16(const void* space, const uint64_t addr, const uint16_t data) {
+    __ts_space_backdoor_write_u16(space, addr, data);
+}
+
+void tsc_space_backdoor_write_u32(const void* space, const uint64_t addr, const uint32_t data) {
+    __ts_space_backdoor_write_u32(space, addr, data);
+}
+
+void tsc_space_backdoor_write_u64(const void* space, const uint64_t addr, const uint64_t data) {
+    __ts_space_backdoor_write_u64(space, addr, data);
+}
+
 #endif
\ No newline at end of file
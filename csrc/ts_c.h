@@ -4,23 +4,52 @@
 
 void* tsc_new_allocator(const uint64_t base, const uint64_t size);
 void* tsc_new_locked_allocator(const uint64_t base, const uint64_t size);
+void* tsc_new_allocator_with_policy(const uint64_t base, const uint64_t size, const uint32_t policy);
+void* tsc_new_locked_allocator_with_policy(const uint64_t base, const uint64_t size, const uint32_t policy);
 uint64_t tsc_alloc_addr(const void* allocator, const uint64_t size, const uint64_t align);
 void tsc_free_addr(const void* allocator, const uint64_t addr);
+uint64_t tsc_alloc_addr_labeled(const void* allocator, const uint64_t size, const uint64_t align, const char* label);
+void tsc_allocator_stats(const void* allocator, ts_alloc_stats* stats);
+void tsc_reserve_addr(const void* allocator, const uint64_t base, const uint64_t size);
+uint64_t tsc_realloc_addr(const void* allocator, const uint64_t addr, const uint64_t size, const uint64_t align);
+void tsc_free_addr_range(const void* allocator, const uint64_t base, const uint64_t size);
+uint32_t tsc_try_alloc_addr(const void* allocator, const uint64_t size, const uint64_t align, uint64_t* addr);
+uint32_t tsc_try_free_addr(const void* allocator, const uint64_t addr);
+uint32_t tsc_try_reserve_addr(const void* allocator, const uint64_t base, const uint64_t size);
+uint32_t tsc_try_realloc_addr(const void* allocator, const uint64_t addr, const uint64_t size, const uint64_t align, uint64_t* new_addr);
+uint32_t tsc_try_free_addr_range(const void* allocator, const uint64_t base, const uint64_t size);
+void tsc_allocator_print_stats(const void* allocator);
+void tsc_allocator_dump(const void* allocator);
 
 void* tsc_space();
 void tsc_delete_region(const void* space, const char* name);
 void* tsc_get_region(const void* space, const char* name);
 void* tsc_add_region(const void* space, const char* name, void* region);
+void* tsc_add_region_with_priority(const void* space, const char* name, void* region, const int32_t priority);
+void tsc_set_region_enabled(const void* space, const char* name, const uint32_t enabled);
 
 void* tsc_alloc_region(void* heap, uint64_t size, uint64_t align);
 void* tsc_root_region(uint64_t size, uint64_t align);
 void* tsc_lazy_root_region(uint64_t size, uint64_t align);
+uint32_t tsc_try_alloc_region(void* heap, uint64_t size, uint64_t align, void** region);
+uint32_t tsc_try_root_region(uint64_t size, uint64_t align, void** region);
+uint32_t tsc_try_lazy_root_region(uint64_t size, uint64_t align, void** region);
 
 void* tsc_map_region(const void* region, uint64_t base);
 void* tsc_map_region_partial(const void* region, uint64_t base, uint64_t offset, uint64_t size);
 void* tsc_heap(const void* region);
+void* tsc_heap_with_policy(const void* region, const uint32_t policy);
 void tsc_free_region(const void* region);
 void tsc_free_heap(const void* heap);
+void tsc_heap_stats(const void* heap, ts_alloc_stats* stats);
+void* tsc_reserve_region(const void* heap, uint64_t base, uint64_t size);
+void tsc_realloc_region(const void* heap, void* region, uint64_t size, uint64_t align);
+void* tsc_free_region_range(const void* heap, void* region, uint64_t base, uint64_t size);
+uint32_t tsc_try_reserve_region(const void* heap, uint64_t base, uint64_t size, void** region);
+uint32_t tsc_try_realloc_region(const void* heap, void* region, uint64_t size, uint64_t align);
+uint32_t tsc_try_free_region_range(const void* heap, void* region, uint64_t base, uint64_t size, void** upper);
+void tsc_heap_print_stats(const void* heap);
+void tsc_heap_dump(const void* heap);
 
 void tsc_region_write_u8(const void* region, const uint64_t addr, const uint8_t data);
 void tsc_region_write_u16(const void* region, const uint64_t addr, const uint16_t data);
@@ -39,8 +68,32 @@ uint8_t tsc_space_read_u8(const void* space, const uint64_t addr);
 uint16_t tsc_space_read_u16(const void* space, const uint64_t addr);
 uint32_t tsc_space_read_u32(const void* space, const uint64_t addr);
 uint64_t tsc_space_read_u64(const void* space, const uint64_t addr);
+void tsc_space_set_unmapped_policy(const void* space, const uint32_t policy, const uint64_t value);
+void tsc_space_set_unmapped_callback(const void* space, ts_unmapped_callback callback);
+bool tsc_space_last_fault(const void* space, uint32_t* kind, uint64_t* addr, uint32_t* size);
+void tsc_space_clear_last_fault(const void* space);
 
 
 ts_mem_info* tsc_region_info(const void* region);
 
+void* tsc_load_elf(const void* space, const char* file_name);
+uint64_t tsc_elf_entry(const void* elf);
+bool tsc_elf_symbol(const void* elf, const char* name, uint64_t* addr);
+void tsc_free_elf(const void* elf);
+
+void tsc_region_load_image(const void* region, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t offset);
+void tsc_space_load_image(const void* space, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t offset);
+void tsc_region_dump_image(const void* region, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t addr, const uint64_t size, const uint64_t offset);
+void tsc_space_dump_image(const void* space, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t addr, const uint64_t size, const uint64_t offset);
+
+void* tsc_map_region_read_only(const void* region, uint64_t base, const uint32_t policy);
+void tsc_region_backdoor_write_u8(const void* region, const uint64_t addr, const uint8_t data);
+void tsc_region_backdoor_write_u16(const void* region, const uint64_t addr, const uint16_t data);
+void tsc_region_backdoor_write_u32(const void* region, const uint64_t addr, const uint32_t data);
+void tsc_region_backdoor_write_u64(const void* region, const uint64_t addr, const uint64_t data);
+void tsc_space_backdoor_write_u8(const void* space, const uint64_t addr, const uint8_t data);
+void tsc_space_backdoor_write_u16(const void* space, const uint64_t addr, const uint16_t data);
+void tsc_space_backdoor_write_u32(const void* space, const uint64_t addr, const uint32_t data);
+void tsc_space_backdoor_write_u64(const void* space, const uint64_t addr, const uint64_t data);
+
 #endif
\ No newline at end of file
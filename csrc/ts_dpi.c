@@ -10,6 +10,14 @@ void* tsv_new_locked_allocator(const uint64_t base, const uint64_t size) {
     return __ts_new_locked_allocator(base, size);
 }
 
+void* tsv_new_allocator_with_policy(const uint64_t base, const uint64_t size, const uint32_t policy) {
+    return __ts_new_allocator_with_policy(base, size, policy);
+}
+
+void* tsv_new_locked_allocator_with_policy(const uint64_t base, const uint64_t size, const uint32_t policy) {
+    return __ts_new_locked_allocator_with_policy(base, size, policy);
+}
+
 uint64_t tsv_alloc_addr(const void* allocator, const uint64_t size, const uint64_t align) {
     return __ts_alloc_addr(allocator, size, align);
 }
@@ -18,6 +26,50 @@ void tsv_free_addr(const void* allocator, const uint64_t addr) {
     __ts_free_addr(allocator, addr);
 }
 
+uint64_t tsv_alloc_addr_labeled(const void* allocator, const uint64_t size, const uint64_t align, const char* label) {
+    return __ts_alloc_addr_labeled(allocator, size, align, label);
+}
+
+void tsv_reserve_addr(const void* allocator, const uint64_t base, const uint64_t size) {
+    __ts_reserve_addr(allocator, base, size);
+}
+
+uint64_t tsv_realloc_addr(const void* allocator, const uint64_t addr, const uint64_t size, const uint64_t align) {
+    return __ts_realloc_addr(allocator, addr, size, align);
+}
+
+void tsv_free_addr_range(const void* allocator, const uint64_t base, const uint64_t size) {
+    __ts_free_addr_range(allocator, base, size);
+}
+
+uint32_t tsv_try_alloc_addr(const void* allocator, const uint64_t size, const uint64_t align, uint64_t* addr) {
+    return __ts_try_alloc_addr(allocator, size, align, addr);
+}
+
+uint32_t tsv_try_free_addr(const void* allocator, const uint64_t addr) {
+    return __ts_try_free_addr(allocator, addr);
+}
+
+uint32_t tsv_try_reserve_addr(const void* allocator, const uint64_t base, const uint64_t size) {
+    return __ts_try_reserve_addr(allocator, base, size);
+}
+
+uint32_t tsv_try_realloc_addr(const void* allocator, const uint64_t addr, const uint64_t size, const uint64_t align, uint64_t* new_addr) {
+    return __ts_try_realloc_addr(allocator, addr, size, align, new_addr);
+}
+
+uint32_t tsv_try_free_addr_range(const void* allocator, const uint64_t base, const uint64_t size) {
+    return __ts_try_free_addr_range(allocator, base, size);
+}
+
+void tsv_allocator_print_stats(const void* allocator) {
+    __ts_allocator_print_stats(allocator);
+}
+
+void tsv_allocator_dump(const void* allocator) {
+    __ts_allocator_dump(allocator);
+}
+
 void* tsv_space() {
     return __ts_space();
 }
@@ -39,6 +91,17 @@ void* tsv_add_region(const void* space, const char* name, void* region) {
     return ptr;
 }
 
+void* tsv_add_region_with_priority(const void* space, const char* name, void* region, const int32_t priority) {
+    void* ptr = __ts_add_region_with_priority(space,name, region, priority);
+    __ts_clean_region(space,name,ptr);
+    __ts_clean_region(space,name,region);
+    return ptr;
+}
+
+void tsv_set_region_enabled(const void* space, const char* name, const uint32_t enabled) {
+    __ts_set_region_enabled(space, name, enabled != 0);
+}
+
 void* tsv_alloc_region(void* heap, uint64_t size, uint64_t align) {
     assert(heap != NULL);
     return __ts_alloc_region(heap, size, align, false);
@@ -52,6 +115,19 @@ void* tsv_lazy_root_region(uint64_t size, uint64_t align) {
     return __ts_alloc_region(NULL, size, align, true);
 }
 
+uint32_t tsv_try_alloc_region(void* heap, uint64_t size, uint64_t align, void** region) {
+    assert(heap != NULL);
+    return __ts_try_alloc_region(heap, size, align, false, region);
+}
+
+uint32_t tsv_try_root_region(uint64_t size, uint64_t align, void** region) {
+    return __ts_try_alloc_region(NULL, size, align, false, region);
+}
+
+uint32_t tsv_try_lazy_root_region(uint64_t size, uint64_t align, void** region) {
+    return __ts_try_alloc_region(NULL, size, align, true, region);
+}
+
 void* tsv_map_region(const void* region, uint64_t base) {
     return __ts_map_region(region, base);
 }
@@ -64,6 +140,10 @@ void* tsv_heap(const void* region) {
     return __ts_heap(region);
 }
 
+void* tsv_heap_with_policy(const void* region, const uint32_t policy) {
+    return __ts_heap_with_policy(region, policy);
+}
+
 void tsv_free_region(const void* region) {
     __ts_free_region(region);
 }
@@ -72,6 +152,38 @@ void tsv_free_heap(const void* heap) {
     __ts_free_heap(heap);
 }
 
+void* tsv_reserve_region(const void* heap, uint64_t base, uint64_t size) {
+    return __ts_reserve_region(heap, base, size);
+}
+
+void tsv_realloc_region(const void* heap, void* region, uint64_t size, uint64_t align) {
+    __ts_realloc_region(heap, region, size, align);
+}
+
+void* tsv_free_region_range(const void* heap, void* region, uint64_t base, uint64_t size) {
+    return __ts_free_region_range(heap, region, base, size);
+}
+
+uint32_t tsv_try_reserve_region(const void* heap, uint64_t base, uint64_t size, void** region) {
+    return __ts_try_reserve_region(heap, base, size, region);
+}
+
+uint32_t tsv_try_realloc_region(const void* heap, void* region, uint64_t size, uint64_t align) {
+    return __ts_try_realloc_region(heap, region, size, align);
+}
+
+uint32_t tsv_try_free_region_range(const void* heap, void* region, uint64_t base, uint64_t size, void** upper) {
+    return __ts_try_free_region_range(heap, region, base, size, upper);
+}
+
+void tsv_heap_print_stats(const void* heap) {
+    __ts_heap_print_stats(heap);
+}
+
+void tsv_heap_dump(const void* heap) {
+    __ts_heap_dump(heap);
+}
+
 void tsv_region_write_u8(const void* region, const uint64_t addr, const uint8_t data) {
     __ts_region_write_u8(region, addr, data);
 }
@@ -130,6 +242,30 @@ void tsv_space_read_u64(const void* space, const uint64_t addr, uint64_t* data){
     *data =__ts_space_read_u64(space, addr);
 }
 
+//exported by the testbench, see ts_dpi.vh
+extern int tsv_unmapped_fault(const uint32_t kind, const uint64_t addr, const uint32_t size, uint64_t* value) __attribute__((weak));
+
+static bool tsv_unmapped_callback(const uint32_t kind, const uint64_t addr, const uint32_t size, uint64_t* value) {
+    return tsv_unmapped_fault(kind, addr, size, value) != 0;
+}
+
+void tsv_space_set_unmapped_policy(const void* space, const uint32_t policy, const uint64_t value) {
+    if (policy == TS_UNMAPPED_CALLBACK) {
+        assert(tsv_unmapped_fault && "tsv_unmapped_fault is not exported!");
+        __ts_space_set_unmapped_policy(space, policy, value, tsv_unmapped_callback);
+    } else {
+        __ts_space_set_unmapped_policy(space, policy, value, NULL);
+    }
+}
+
+int tsv_space_last_fault(const void* space, uint32_t* kind, uint64_t* addr, uint32_t* size) {
+    return __ts_space_last_fault(space, kind, addr, size);
+}
+
+void tsv_space_clear_last_fault(const void* space) {
+    __ts_space_clear_last_fault(space);
+}
+
 uint64_t tsv_region_base(const void* region){
     return ((ts_mem_info*)__ts_region_info(region))->base;
 }
@@ -137,4 +273,72 @@ uint64_t tsv_region_size(const void* region){
     return ((ts_mem_info*)__ts_region_info(region))->size;
 }
 
+void* tsv_load_elf(const void* space, const char* file_name) {
+    return __ts_load_elf(space, file_name);
+}
+
+uint64_t tsv_elf_entry(const void* elf) {
+    return __ts_elf_entry(elf);
+}
+
+int tsv_elf_symbol(const void* elf, const char* name, uint64_t* addr) {
+    return __ts_elf_symbol(elf, name, addr);
+}
+
+void tsv_free_elf(const void* elf) {
+    __ts_free_elf(elf);
+}
+
+void tsv_region_load_image(const void* region, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t offset) {
+    __ts_region_load_image(region, file_name, format, word_size, offset);
+}
+
+void tsv_space_load_image(const void* space, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t offset) {
+    __ts_space_load_image(space, file_name, format, word_size, offset);
+}
+
+void tsv_region_dump_image(const void* region, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t addr, const uint64_t size, const uint64_t offset) {
+    __ts_region_dump_image(region, file_name, format, word_size, addr, size, offset);
+}
+
+void tsv_space_dump_image(const void* space, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t addr, const uint64_t size, const uint64_t offset) {
+    __ts_space_dump_image(space, file_name, format, word_size, addr, size, offset);
+}
+
+void* tsv_map_region_read_only(const void* region, uint64_t base, const uint32_t policy) {
+    return __ts_map_region_read_only(region, base, policy);
+}
+
+void tsv_region_backdoor_write_u8(const void* region, const uint64_t addr, const uint8_t data) {
+    __ts_region_backdoor_write_u8(region, addr, data);
+}
+
+void tsv_region_backdoor_write_u16(const void* region, const uint64_t addr, const uint16_t data) {
+    __ts_region_backdoor_write_u16(region, addr, data);
+}
+
+void tsv_region_backdoor_write_u32(const void* region, const uint64_t addr, const uint32_t data) {
+    __ts_region_backdoor_write_u32(region, addr, data);
+}
+
+void tsv_region_backdoor_write_u64(const void* region, const uint64_t addr, const uint64_t data) {
+    __ts_region_backdoor_write_u64(region, addr, data);
+}
+
+void tsv_space_backdoor_write_u8(const void* space, const uint64_t addr, const uint8_t data) {
+    __ts_space_backdoor_write_u8(space, addr, data);
+}
+
+void tsv_space_backdoor_write_u16(const void* space, const uint64_t addr, const uint16_t data) {
+    __ts_space_backdoor_write_u16(space, addr, data);
+}
+
+void tsv_space_backdoor_write_u32(const void* space, const uint64_t addr, const uint32_t data) {
+    __ts_space_backdoor_write_u32(space, addr, data);
+}
+
+void tsv_space_backdoor_write_u64(const void* space, const uint64_t addr, const uint64_t data) {
+    __ts_space_backdoor_write_u64(space, addr, data);
+}
+
 #endif
\ No newline at end of file
@@ -4,23 +4,50 @@
 
 void* tsv_new_allocator(const uint64_t base, const uint64_t size);
 void* tsv_new_locked_allocator(const uint64_t base, const uint64_t size);
+void* tsv_new_allocator_with_policy(const uint64_t base, const uint64_t size, const uint32_t policy);
+void* tsv_new_locked_allocator_with_policy(const uint64_t base, const uint64_t size, const uint32_t policy);
 uint64_t tsv_alloc_addr(const void* allocator, const uint64_t size, const uint64_t align);
 void tsv_free_addr(const void* allocator, const uint64_t addr);
+uint64_t tsv_alloc_addr_labeled(const void* allocator, const uint64_t size, const uint64_t align, const char* label);
+void tsv_reserve_addr(const void* allocator, const uint64_t base, const uint64_t size);
+uint64_t tsv_realloc_addr(const void* allocator, const uint64_t addr, const uint64_t size, const uint64_t align);
+void tsv_free_addr_range(const void* allocator, const uint64_t base, const uint64_t size);
+uint32_t tsv_try_alloc_addr(const void* allocator, const uint64_t size, const uint64_t align, uint64_t* addr);
+uint32_t tsv_try_free_addr(const void* allocator, const uint64_t addr);
+uint32_t tsv_try_reserve_addr(const void* allocator, const uint64_t base, const uint64_t size);
+uint32_t tsv_try_realloc_addr(const void* allocator, const uint64_t addr, const uint64_t size, const uint64_t align, uint64_t* new_addr);
+uint32_t tsv_try_free_addr_range(const void* allocator, const uint64_t base, const uint64_t size);
+void tsv_allocator_print_stats(const void* allocator);
+void tsv_allocator_dump(const void* allocator);
 
 void* tsv_space();
 void tsv_delete_region(const void* space, const char* name);
 void* tsv_get_region(const void* space, const char* name);
 void* tsv_add_region(const void* space, const char* name, void* region);
+void* tsv_add_region_with_priority(const void* space, const char* name, void* region, const int32_t priority);
+void tsv_set_region_enabled(const void* space, const char* name, const uint32_t enabled);
 
 void* tsv_alloc_region(void* heap, uint64_t size, uint64_t align);
 void* tsv_root_region(uint64_t size, uint64_t align);
 void* tsv_lazy_root_region(uint64_t size, uint64_t align);
+uint32_t tsv_try_alloc_region(void* heap, uint64_t size, uint64_t align, void** region);
+uint32_t tsv_try_root_region(uint64_t size, uint64_t align, void** region);
+uint32_t tsv_try_lazy_root_region(uint64_t size, uint64_t align, void** region);
 
 void* tsv_map_region(const void* region, uint64_t base);
 void* tsv_map_region_partial(const void* region, uint64_t base, uint64_t offset, uint64_t size);
 void* tsv_heap(const void* region);
+void* tsv_heap_with_policy(const void* region, const uint32_t policy);
 void tsv_free_region(const void* region);
 void tsv_free_heap(const void* heap);
+void* tsv_reserve_region(const void* heap, uint64_t base, uint64_t size);
+void tsv_realloc_region(const void* heap, void* region, uint64_t size, uint64_t align);
+void* tsv_free_region_range(const void* heap, void* region, uint64_t base, uint64_t size);
+uint32_t tsv_try_reserve_region(const void* heap, uint64_t base, uint64_t size, void** region);
+uint32_t tsv_try_realloc_region(const void* heap, void* region, uint64_t size, uint64_t align);
+uint32_t tsv_try_free_region_range(const void* heap, void* region, uint64_t base, uint64_t size, void** upper);
+void tsv_heap_print_stats(const void* heap);
+void tsv_heap_dump(const void* heap);
 
 void tsv_region_write_u8(const void* region, const uint64_t addr, const uint8_t data);
 void tsv_region_write_u16(const void* region, const uint64_t addr, const uint16_t data);
@@ -39,8 +66,31 @@ void tsv_space_read_u8(const void* heap, const uint64_t addr, uint8_t* data);
 void tsv_space_read_u16(const void* heap, const uint64_t addr, uint16_t* data);
 void tsv_space_read_u32(const void* heap, const uint64_t addr, uint32_t* data);
 void tsv_space_read_u64(const void* heap, const uint64_t addr, uint64_t* data);
+void tsv_space_set_unmapped_policy(const void* space, const uint32_t policy, const uint64_t value);
+int tsv_space_last_fault(const void* space, uint32_t* kind, uint64_t* addr, uint32_t* size);
+void tsv_space_clear_last_fault(const void* space);
 
 uint64_t tsv_region_base(const void* region);
 uint64_t tsv_region_size(const void* region);
 
+void* tsv_load_elf(const void* space, const char* file_name);
+uint64_t tsv_elf_entry(const void* elf);
+int tsv_elf_symbol(const void* elf, const char* name, uint64_t* addr);
+void tsv_free_elf(const void* elf);
+
+void tsv_region_load_image(const void* region, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t offset);
+void tsv_space_load_image(const void* space, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t offset);
+void tsv_region_dump_image(const void* region, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t addr, const uint64_t size, const uint64_t offset);
+void tsv_space_dump_image(const void* space, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t addr, const uint64_t size, const uint64_t offset);
+
+void* tsv_map_region_read_only(const void* region, uint64_t base, const uint32_t policy);
+void tsv_region_backdoor_write_u8(const void* region, const uint64_t addr, const uint8_t data);
+void tsv_region_backdoor_write_u16(const void* region, const uint64_t addr, const uint16_t data);
+void tsv_region_backdoor_write_u32(const void* region, const uint64_t addr, const uint32_t data);
+void tsv_region_backdoor_write_u64(const void* region, const uint64_t addr, const uint64_t data);
+void tsv_space_backdoor_write_u8(const void* space, const uint64_t addr, const uint8_t data);
+void tsv_space_backdoor_write_u16(const void* space, const uint64_t addr, const uint16_t data);
+void tsv_space_backdoor_write_u32(const void* space, const uint64_t addr, const uint32_t data);
+void tsv_space_backdoor_write_u64(const void* space, const uint64_t addr, const uint64_t data);
+
 #endif
\ No newline at end of file
@@ -9,23 +9,98 @@ typedef struct{
     uint64_t size;
 } ts_mem_info ;
 
+typedef struct{
+    uint64_t alloced_bytes;
+    uint64_t free_bytes;
+    uint64_t largest_free;
+    uint64_t high_water;
+    uint64_t live_blocks;
+    uint64_t alloc_count;
+} ts_alloc_stats ;
+
+//kind is TS_FAULT_*, true completes the access with *value, false raises a bus error
+typedef bool (*ts_unmapped_callback)(const uint32_t kind, const uint64_t addr, const uint32_t size, uint64_t* value);
+
+#define TS_IMAGE_BIN 0
+#define TS_IMAGE_READMEMH 1
+#define TS_IMAGE_IHEX 2
+#define TS_IMAGE_SREC 3
+
+#define TS_READONLY_FAULT 0
+#define TS_READONLY_IGNORE 1
+
+#define TS_ALLOC_FIRST_FIT 0
+#define TS_ALLOC_BEST_FIT 1
+#define TS_ALLOC_NEXT_FIT 2
+#define TS_ALLOC_BUDDY 3
+#define TS_ALLOC_SLAB 4
+#define TS_ALLOC_BUMP 5
+
+#define TS_ALLOC_OK 0
+#define TS_ALLOC_OUT_OF_MEMORY 1
+#define TS_ALLOC_NOT_FREE 2
+#define TS_ALLOC_INVALID_FREE 3
+#define TS_ALLOC_DOUBLE_FREE 4
+#define TS_ALLOC_INVALID_REALLOC 5
+#define TS_ALLOC_PARTIAL_FREE 6
+#define TS_ALLOC_SHARED 7
+#define TS_ALLOC_BACKING 8
+
+//space accesses failed by a TS_UNMAPPED_CALLBACK bus error complete with 0, see *_space_last_fault
+#define TS_UNMAPPED_PANIC 0
+#define TS_UNMAPPED_FILL 1
+#define TS_UNMAPPED_CALLBACK 2
+
+#define TS_ROM_FAULT 0
+#define TS_ROM_IGNORE 1
+
+#define TS_FAULT_READ 0
+#define TS_FAULT_WRITE 1
+
 extern void* __ts_new_allocator(const uint64_t base, const uint64_t size);
 extern void* __ts_new_locked_allocator(const uint64_t base, const uint64_t size);
+extern void* __ts_new_allocator_with_policy(const uint64_t base, const uint64_t size, const uint32_t policy);
+extern void* __ts_new_locked_allocator_with_policy(const uint64_t base, const uint64_t size, const uint32_t policy);
 extern uint64_t __ts_alloc_addr(const void* allocator, const uint64_t size, const uint64_t align);
 extern void __ts_free_addr(const void* allocator, const uint64_t addr);
+extern uint64_t __ts_alloc_addr_labeled(const void* allocator, const uint64_t size, const uint64_t align, const char* label);
+extern void __ts_reserve_addr(const void* allocator, const uint64_t base, const uint64_t size);
+extern uint64_t __ts_realloc_addr(const void* allocator, const uint64_t addr, const uint64_t size, const uint64_t align);
+extern void __ts_free_addr_range(const void* allocator, const uint64_t base, const uint64_t size);
+extern uint32_t __ts_try_alloc_addr(const void* allocator, const uint64_t size, const uint64_t align, uint64_t* addr);
+extern uint32_t __ts_try_free_addr(const void* allocator, const uint64_t addr);
+extern uint32_t __ts_try_reserve_addr(const void* allocator, const uint64_t base, const uint64_t size);
+extern uint32_t __ts_try_realloc_addr(const void* allocator, const uint64_t addr, const uint64_t size, const uint64_t align, uint64_t* new_addr);
+extern uint32_t __ts_try_free_addr_range(const void* allocator, const uint64_t base, const uint64_t size);
+extern void __ts_allocator_stats(const void* allocator, ts_alloc_stats* stats);
+extern void __ts_allocator_print_stats(const void* allocator);
+extern void __ts_allocator_dump(const void* allocator);
 
 extern void* __ts_space();
 extern void* __ts_add_region(const void* space, const char* name, void* region);
 extern void __ts_clean_region(const void* space, const char* name, void* ptr);
 extern void* __ts_get_region(const void* space, const char* name);
 extern void __ts_delete_region(const void* space, const char* name);
+extern void* __ts_add_region_with_priority(const void* space, const char* name, void* region, const int32_t priority);
+extern void __ts_set_region_enabled(const void* space, const char* name, bool enabled);
 
 extern void* __ts_alloc_region(void* heap, uint64_t size, uint64_t align, bool lazy);
+extern uint32_t __ts_try_alloc_region(void* heap, uint64_t size, uint64_t align, bool lazy, void** region);
 extern void* __ts_map_region(const void* region, uint64_t base);
 extern void* __ts_map_region_partial(const void* region, uint64_t base, uint64_t offset, uint64_t size);
 extern void* __ts_heap(const void* region);
+extern void* __ts_heap_with_policy(const void* region, const uint32_t policy);
 extern void __ts_free_region(const void* region);
 extern void __ts_free_heap(const void* heap);
+extern void* __ts_reserve_region(const void* heap, uint64_t base, uint64_t size);
+extern void __ts_realloc_region(const void* heap, void* region, uint64_t size, uint64_t align);
+extern void* __ts_free_region_range(const void* heap, void* region, uint64_t base, uint64_t size);
+extern uint32_t __ts_try_reserve_region(const void* heap, uint64_t base, uint64_t size, void** region);
+extern uint32_t __ts_try_realloc_region(const void* heap, void* region, uint64_t size, uint64_t align);
+extern uint32_t __ts_try_free_region_range(const void* heap, void* region, uint64_t base, uint64_t size, void** upper);
+extern void __ts_heap_stats(const void* heap, ts_alloc_stats* stats);
+extern void __ts_heap_print_stats(const void* heap);
+extern void __ts_heap_dump(const void* heap);
 extern void* __ts_region_info(const void* region);
 
 extern void __ts_region_write_u8(const void* region, const uint64_t addr, const uint8_t data);
@@ -45,5 +120,29 @@ extern uint8_t __ts_space_read_u8(const void* space, const uint64_t addr);
 extern uint16_t __ts_space_read_u16(const void* space, const uint64_t addr);
 extern uint32_t __ts_space_read_u32(const void* space, const uint64_t addr);
 extern uint64_t __ts_space_read_u64(const void* space, const uint64_t addr);
+extern void __ts_space_set_unmapped_policy(const void* space, const uint32_t policy, const uint64_t value, ts_unmapped_callback callback);
+extern bool __ts_space_last_fault(const void* space, uint32_t* kind, uint64_t* addr, uint32_t* size);
+extern void __ts_space_clear_last_fault(const void* space);
+
+//NULL if the file can not be loaded
+extern void* __ts_load_elf(const void* space, const char* file_name);
+extern uint64_t __ts_elf_entry(const void* elf);
+extern bool __ts_elf_symbol(const void* elf, const char* name, uint64_t* addr);
+extern void __ts_free_elf(const void* elf);
+
+extern void __ts_region_load_image(const void* region, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t offset);
+extern void __ts_space_load_image(const void* space, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t offset);
+extern void __ts_region_dump_image(const void* region, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t addr, const uint64_t size, const uint64_t offset);
+extern void __ts_space_dump_image(const void* space, const char* file_name, const uint32_t format, const uint32_t word_size, const uint64_t addr, const uint64_t size, const uint64_t offset);
+
+extern void* __ts_map_region_read_only(const void* region, uint64_t base, const uint32_t policy);
+extern void __ts_region_backdoor_write_u8(const void* region, const uint64_t addr, const uint8_t data);
+extern void __ts_region_backdoor_write_u16(const void* region, const uint64_t addr, const uint16_t data);
+extern void __ts_region_backdoor_write_u32(const void* region, const uint64_t addr, const uint32_t data);
+extern void __ts_region_backdoor_write_u64(const void* region, const uint64_t addr, const uint64_t data);
+extern void __ts_space_backdoor_write_u8(const void* space, const uint64_t addr, const uint8_t data);
+extern void __ts_space_backdoor_write_u16(const void* space, const uint64_t addr, const uint16_t data);
+extern void __ts_space_backdoor_write_u32(const void* space, const uint64_t addr, const uint32_t data);
+extern void __ts_space_backdoor_write_u64(const void* space, const uint64_t addr, const uint64_t data);
 
 #endif
\ No newline at end of file
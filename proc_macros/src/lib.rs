@@ -38,10 +38,34 @@ pub fn derive_io(args: TokenStream, input: TokenStream) -> TokenStream {
             #q
         }
     });
+    let snapshot = if args.snapshot() {
+        quote! {
+            fn snapshot(&self) -> Option<&dyn IOSnapshot> {
+                Some(self)
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let atomic = if args.atomic() {
+        quote! {
+            fn atomic(&self) -> Option<&dyn AtomicAccess> {
+                Some(self)
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let io_access = quote! {
+        impl IOAccess for #name {
+            #snapshot
+            #atomic
+        }
+    };
     (quote! {
         #data
         #defaults
-        impl IOAccess for #name {}
+        #io_access
     })
     .into()
 }
@@ -52,6 +76,8 @@ mod args_kw {
     syn::custom_keyword!(U32);
     syn::custom_keyword!(U64);
     syn::custom_keyword!(Bytes);
+    syn::custom_keyword!(Snapshot);
+    syn::custom_keyword!(Atomic);
 }
 
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -166,9 +192,47 @@ impl Parse for AccessTrait {
     }
 }
 
-struct Args(Punctuated<AccessTrait, Token![,]>);
+enum Arg {
+    Access(AccessTrait),
+    Snapshot,
+    Atomic,
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(args_kw::Snapshot) {
+            input.parse::<args_kw::Snapshot>()?;
+            Ok(Arg::Snapshot)
+        } else if input.peek(args_kw::Atomic) {
+            input.parse::<args_kw::Atomic>()?;
+            Ok(Arg::Atomic)
+        } else {
+            Ok(Arg::Access(input.parse()?))
+        }
+    }
+}
+
+struct Args(Punctuated<Arg, Token![,]>);
 
 impl Args {
+    fn access_traits(&self) -> Vec<AccessTrait> {
+        self.0
+            .iter()
+            .filter_map(|a| match a {
+                Arg::Access(t) => Some(*t),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn snapshot(&self) -> bool {
+        self.0.iter().any(|a| matches!(a, Arg::Snapshot))
+    }
+
+    fn atomic(&self) -> bool {
+        self.0.iter().any(|a| matches!(a, Arg::Atomic))
+    }
+
     fn defaults(&self) -> Result<Vec<AccessTrait>> {
         let all_traits = vec![
             AccessTrait::U8,
@@ -177,7 +241,8 @@ impl Args {
             AccessTrait::U64,
             AccessTrait::Bytes,
         ];
-        if self.0.is_empty() {
+        let traits = self.access_traits();
+        if traits.is_empty() {
             Err(Error::new(
                 Span::call_site(),
                 "At least one in [U8|U16|U32|U64|Bytes]!",
@@ -185,7 +250,7 @@ impl Args {
         } else {
             Ok(all_traits
                 .iter()
-                .filter(|&t| !self.0.iter().any(|&a| a == *t))
+                .filter(|&t| !traits.contains(t))
                 .map(|t| t.clone())
                 .collect::<Vec<_>>())
         }
@@ -194,6 +259,6 @@ impl Args {
 
 impl Parse for Args {
     fn parse(input: ParseStream) -> Result<Self> {
-        Ok(Args(input.parse_terminated(AccessTrait::parse)?))
+        Ok(Args(input.parse_terminated(Arg::parse)?))
     }
 }
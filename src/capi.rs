@@ -1,10 +1,12 @@
+use crate::loader::*;
 use crate::memory::allocator::*;
+use crate::memory::attr::Attr;
 use crate::memory::region::*;
 use crate::memory::MemInfo;
-use crate::space::Space;
+use crate::space::{Space, UnmappedPolicy};
 use std::any::Any;
 use std::ffi::{c_void, CStr};
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
 use std::os::raw::c_char;
 use std::rc::Rc;
 
@@ -22,6 +24,50 @@ extern "C" fn __ts_new_locked_allocator(base: u64, size: u64) -> *const c_void {
     )) as *const c_void
 }
 
+fn alloc_policy(policy: u32) -> Policy {
+    match policy {
+        0 => Policy::FirstFit,
+        1 => Policy::BestFit,
+        2 => Policy::NextFit,
+        3 => Policy::Buddy,
+        4 => Policy::Slab,
+        5 => Policy::Bump,
+        _ => panic!("unknown allocation policy {}!", policy),
+    }
+}
+
+#[no_mangle]
+extern "C" fn __ts_new_allocator_with_policy(base: u64, size: u64, policy: u32) -> *const c_void {
+    Box::into_raw(Box::new(
+        Box::new(Allocator::with_policy(base, size, alloc_policy(policy))) as Box<dyn Any>,
+    )) as *const c_void
+}
+
+#[no_mangle]
+extern "C" fn __ts_new_locked_allocator_with_policy(
+    base: u64,
+    size: u64,
+    policy: u32,
+) -> *const c_void {
+    Box::into_raw(Box::new(Box::new(LockedAllocator::with_policy(
+        base,
+        size,
+        alloc_policy(policy),
+    )) as Box<dyn Any>)) as *const c_void
+}
+
+fn with_allocator<T, F: FnOnce(&mut Allocator) -> T>(a: &mut Box<dyn Any>, f: F) -> T {
+    if let Some(allocator) = a.downcast_mut::<Allocator>() {
+        f(allocator)
+    } else if let Some(allocator) = a.downcast_mut::<LockedAllocator>() {
+        f(allocator.lock().unwrap().deref_mut())
+    } else {
+        panic!(
+            "wrong type!allocator should be create by ts_new_allocator or ts_new_locked_allocator!"
+        )
+    }
+}
+
 #[no_mangle]
 //safe pointer style
 extern "C" fn __ts_alloc_addr(a: &mut Box<dyn Any>, size: u64, align: u64) -> u64 {
@@ -35,19 +81,152 @@ extern "C" fn __ts_alloc_addr(a: &mut Box<dyn Any>, size: u64, align: u64) -> u6
         )
     };
 
-    if let Some(i) = info {
-        i.base
-    } else {
-        panic!("oom!")
+    match info {
+        Ok(i) => i.base,
+        Err(e) => panic!(
+            "{}, {}",
+            e,
+            with_allocator(a, |allocator| allocator.stats())
+        ),
     }
 }
 
+#[no_mangle]
+extern "C" fn __ts_alloc_addr_labeled(
+    a: &mut Box<dyn Any>,
+    size: u64,
+    align: u64,
+    label: *const c_char,
+) -> u64 {
+    let label = unsafe { CStr::from_ptr(label).to_str().unwrap() };
+    with_allocator(a, |allocator| {
+        match allocator.alloc_labeled(size, align, label) {
+            Ok(i) => i.base,
+            Err(e) => panic!("{} for {}, {}", e, label, allocator.stats()),
+        }
+    })
+}
+
+#[no_mangle]
+extern "C" fn __ts_reserve_addr(a: &mut Box<dyn Any>, base: u64, size: u64) {
+    with_allocator(a, |allocator| {
+        if let Err(e) = allocator.reserve(base, size) {
+            panic!("{}", e)
+        }
+    })
+}
+
+#[no_mangle]
+extern "C" fn __ts_realloc_addr(a: &mut Box<dyn Any>, addr: u64, size: u64, align: u64) -> u64 {
+    with_allocator(a, |allocator| match allocator.realloc(addr, size, align) {
+        Ok(i) => i.base,
+        Err(e) => panic!("{} realloc @{:#x}, {}", e, addr, allocator.stats()),
+    })
+}
+
+#[no_mangle]
+extern "C" fn __ts_free_addr_range(a: &mut Box<dyn Any>, base: u64, size: u64) {
+    with_allocator(a, |allocator| {
+        if let Err(e) = allocator.free_range(base, size) {
+            panic!("{}", e)
+        }
+    })
+}
+
+//status codes of __ts_try_*, the result is only written on TS_ALLOC_OK
+fn alloc_status<T, F: FnOnce(T)>(r: Result<T, AllocError>, f: F) -> u32 {
+    match r {
+        Ok(v) => {
+            f(v);
+            0
+        }
+        Err(AllocError::OutOfMemory(..)) => 1,
+        Err(AllocError::NotFree(..)) => 2,
+        Err(AllocError::InvalidFree(_)) => 3,
+        Err(AllocError::DoubleFree(_)) => 4,
+        Err(AllocError::InvalidRealloc(_)) => 5,
+        Err(AllocError::PartialFree(_)) => 6,
+        Err(AllocError::Shared(_)) => 7,
+        Err(AllocError::Backing(_)) => 8,
+    }
+}
+
+#[no_mangle]
+//status code style, errors are returned instead of panic
+extern "C" fn __ts_try_alloc_addr(
+    a: &mut Box<dyn Any>,
+    size: u64,
+    align: u64,
+    addr: &mut u64,
+) -> u32 {
+    alloc_status(
+        with_allocator(a, |allocator| allocator.alloc(size, align)),
+        |i| *addr = i.base,
+    )
+}
+
+#[no_mangle]
+extern "C" fn __ts_try_free_addr(a: &mut Box<dyn Any>, addr: u64) -> u32 {
+    alloc_status(with_allocator(a, |allocator| allocator.free(addr)), |_| {})
+}
+
+#[no_mangle]
+extern "C" fn __ts_try_reserve_addr(a: &mut Box<dyn Any>, base: u64, size: u64) -> u32 {
+    alloc_status(
+        with_allocator(a, |allocator| allocator.reserve(base, size)),
+        |_| {},
+    )
+}
+
+#[no_mangle]
+extern "C" fn __ts_try_realloc_addr(
+    a: &mut Box<dyn Any>,
+    addr: u64,
+    size: u64,
+    align: u64,
+    new_addr: &mut u64,
+) -> u32 {
+    alloc_status(
+        with_allocator(a, |allocator| allocator.realloc(addr, size, align)),
+        |i| *new_addr = i.base,
+    )
+}
+
+#[no_mangle]
+extern "C" fn __ts_try_free_addr_range(a: &mut Box<dyn Any>, base: u64, size: u64) -> u32 {
+    alloc_status(
+        with_allocator(a, |allocator| allocator.free_range(base, size)),
+        |_| {},
+    )
+}
+
+#[no_mangle]
+extern "C" fn __ts_allocator_stats(a: &mut Box<dyn Any>, stats: &mut AllocStats) {
+    *stats = with_allocator(a, |allocator| allocator.stats())
+}
+
+#[no_mangle]
+extern "C" fn __ts_allocator_print_stats(a: &mut Box<dyn Any>) {
+    println!("{}", with_allocator(a, |allocator| allocator.stats()))
+}
+
+#[no_mangle]
+//live blocks, the leaks at the end of test
+extern "C" fn __ts_allocator_dump(a: &mut Box<dyn Any>) {
+    with_allocator(a, |allocator| {
+        allocator
+            .live_blocks()
+            .iter()
+            .for_each(|block| println!("{}", block))
+    })
+}
+
 #[no_mangle]
 //unsafe raw pointer style
 extern "C" fn __ts_free_addr(a: *mut c_void, addr: u64) {
     let abox = unsafe { &mut *(a as *mut Box<dyn Any>) };
 
-    if let Some(allocator) = abox.downcast_mut::<Allocator>() {
+    let r = if let Some(allocator) = abox.downcast_mut::<Allocator>() {
         allocator.free(addr)
     } else if let Some(allocator) = abox.downcast_mut::<LockedAllocator>() {
         allocator.free(addr)
@@ -55,6 +234,9 @@ extern "C" fn __ts_free_addr(a: *mut c_void, addr: u64) {
         panic!(
             "wrong type!allocator should be create by ts_new_allocator or ts_new_locked_allocator!"
         )
+    };
+    if let Err(e) = r {
+        panic!("{}", e)
     }
 }
 
@@ -97,17 +279,41 @@ extern "C" fn __ts_get_region(space: &Space, name: *const c_char) -> *const Box<
 
 #[no_mangle]
 extern "C" fn __ts_delete_region(space: &mut Space, name: *const c_char) {
-    space.delete_region(unsafe { CStr::from_ptr(name).to_str().unwrap() })
+    if let Err(e) = space.delete_region(unsafe { CStr::from_ptr(name).to_str().unwrap() }) {
+        panic!("{:?}", e)
+    }
 }
 
 #[no_mangle]
-extern "C" fn __ts_alloc_region(
+extern "C" fn __ts_add_region_with_priority(
+    space: &mut Space,
+    name: *const c_char,
+    region: &Box<Rc<Region>>,
+    priority: i32,
+) -> *const Box<Rc<Region>> {
+    let name = unsafe { CStr::from_ptr(name).to_str().unwrap() };
+    match space.add_region_with_priority(name, region.deref(), Attr::default(), priority) {
+        Ok(r) => to_c_ptr(r),
+        Err(e) => panic!("{:?}", e),
+    }
+}
+
+#[no_mangle]
+extern "C" fn __ts_set_region_enabled(space: &Space, name: *const c_char, enabled: bool) {
+    if let Err(e) =
+        space.set_region_enabled(unsafe { CStr::from_ptr(name).to_str().unwrap() }, enabled)
+    {
+        panic!("{:?}", e)
+    }
+}
+
+fn alloc_region(
     heap: *const Box<Rc<Heap>>,
     size: u64,
     align: u64,
     lazy: bool,
-) -> *const Box<Rc<Region>> {
-    match unsafe {
+) -> Result<Rc<Region>, AllocError> {
+    unsafe {
         if heap.is_null() {
             if lazy {
                 GHEAP.lazy_alloc(size, align)
@@ -118,12 +324,40 @@ extern "C" fn __ts_alloc_region(
             let p = heap.as_ref().unwrap();
             p.alloc(size, align)
         }
-    } {
+    }
+}
+
+#[no_mangle]
+extern "C" fn __ts_alloc_region(
+    heap: *const Box<Rc<Heap>>,
+    size: u64,
+    align: u64,
+    lazy: bool,
+) -> *const Box<Rc<Region>> {
+    match alloc_region(heap, size, align, lazy) {
         Ok(region) => to_c_ptr(region),
-        Err(msg) => panic!("{}", msg),
+        Err(e) => panic!(
+            "{}, {}",
+            e,
+            unsafe { heap.as_ref() }.map_or_else(|| GHEAP.stats(), |h| h.stats())
+        ),
     }
 }
 
+#[no_mangle]
+//status code style as __ts_try_alloc_addr, region is untouched on failure
+extern "C" fn __ts_try_alloc_region(
+    heap: *const Box<Rc<Heap>>,
+    size: u64,
+    align: u64,
+    lazy: bool,
+    region: &mut *const Box<Rc<Region>>,
+) -> u32 {
+    alloc_status(alloc_region(heap, size, align, lazy), |r| {
+        *region = to_c_ptr(r)
+    })
+}
+
 #[no_mangle]
 extern "C" fn __ts_free_region(region: *const Box<Rc<Region>>) {
     std::mem::drop(unsafe { region.read() })
@@ -139,6 +373,106 @@ extern "C" fn __ts_heap(region: &Box<Rc<Region>>) -> *const Box<Rc<Heap>> {
     Box::into_raw(Box::new(Box::new(Heap::new(region.deref()))))
 }
 
+#[no_mangle]
+extern "C" fn __ts_heap_with_policy(region: &Box<Rc<Region>>, policy: u32) -> *const Box<Rc<Heap>> {
+    Box::into_raw(Box::new(Box::new(Heap::with_policy(
+        region.deref(),
+        alloc_policy(policy),
+    ))))
+}
+
+#[no_mangle]
+extern "C" fn __ts_reserve_region(
+    heap: &Box<Rc<Heap>>,
+    base: u64,
+    size: u64,
+) -> *const Box<Rc<Region>> {
+    match heap.reserve(base, size) {
+        Ok(r) => to_c_ptr(r),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[no_mangle]
+extern "C" fn __ts_try_reserve_region(
+    heap: &Box<Rc<Heap>>,
+    base: u64,
+    size: u64,
+    region: &mut *const Box<Rc<Region>>,
+) -> u32 {
+    alloc_status(heap.reserve(base, size), |r| *region = to_c_ptr(r))
+}
+
+#[no_mangle]
+//the region is changed in place, it must be the only handle of the block
+extern "C" fn __ts_realloc_region(
+    heap: &Box<Rc<Heap>>,
+    region: &mut Box<Rc<Region>>,
+    size: u64,
+    align: u64,
+) {
+    if let Err(e) = heap.realloc(region, size, align) {
+        panic!("{}", e)
+    }
+}
+
+#[no_mangle]
+//the block is kept unchanged on failure
+extern "C" fn __ts_try_realloc_region(
+    heap: &Box<Rc<Heap>>,
+    region: &mut Box<Rc<Region>>,
+    size: u64,
+    align: u64,
+) -> u32 {
+    alloc_status(heap.realloc(region, size, align), |_| {})
+}
+
+#[no_mangle]
+//the upper part left is returned if any, the region keeps the other part
+extern "C" fn __ts_free_region_range(
+    heap: &Box<Rc<Heap>>,
+    region: &mut Box<Rc<Region>>,
+    base: u64,
+    size: u64,
+) -> *const Box<Rc<Region>> {
+    match heap.free_range(region, base, size) {
+        Ok(Some(r)) => to_c_ptr(r),
+        Ok(None) => std::ptr::null(),
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[no_mangle]
+//upper is the upper part left or NULL
+extern "C" fn __ts_try_free_region_range(
+    heap: &Box<Rc<Heap>>,
+    region: &mut Box<Rc<Region>>,
+    base: u64,
+    size: u64,
+    upper: &mut *const Box<Rc<Region>>,
+) -> u32 {
+    alloc_status(heap.free_range(region, base, size), |r| {
+        *upper = r.map_or(std::ptr::null(), to_c_ptr)
+    })
+}
+
+#[no_mangle]
+extern "C" fn __ts_heap_stats(heap: &Box<Rc<Heap>>, stats: &mut AllocStats) {
+    *stats = heap.stats()
+}
+
+#[no_mangle]
+extern "C" fn __ts_heap_print_stats(heap: &Box<Rc<Heap>>) {
+    println!("{}", heap.stats())
+}
+
+#[no_mangle]
+extern "C" fn __ts_heap_dump(heap: &Box<Rc<Heap>>) {
+    heap.live_blocks()
+        .iter()
+        .for_each(|block| println!("{}", block))
+}
+
 #[no_mangle]
 extern "C" fn __ts_free_heap(heap: *const Box<Rc<Heap>>) {
     std::mem::drop(unsafe { heap.read() })
@@ -199,44 +533,327 @@ extern "C" fn __ts_region_read_u64(region: &Box<Rc<Region>>, addr: u64) -> u64 {
     U64Access::read(region.deref().deref(), &addr)
 }
 
+//bus errors raised by UnmappedPolicy::Callback complete the access with 0, the fault is left in
+//last_fault. only UnmappedPolicy::Panic panics
+fn space_fault<T: Default>(space: &Space, addr: u64) -> T {
+    if let UnmappedPolicy::Panic = space.unmapped_policy() {
+        panic!("unmapped access @{:#x}!", addr)
+    }
+    T::default()
+}
+
+#[no_mangle]
+extern "C" fn __ts_space_write_u8(space: &Space, addr: u64, data: u8) {
+    space
+        .write_u8(&addr, data)
+        .unwrap_or_else(|addr| space_fault(space, addr))
+}
+
+#[no_mangle]
+extern "C" fn __ts_space_write_u16(space: &Space, addr: u64, data: u16) {
+    space
+        .write_u16(&addr, data)
+        .unwrap_or_else(|addr| space_fault(space, addr))
+}
+
+#[no_mangle]
+extern "C" fn __ts_space_write_u32(space: &Space, addr: u64, data: u32) {
+    space
+        .write_u32(&addr, data)
+        .unwrap_or_else(|addr| space_fault(space, addr))
+}
+
+#[no_mangle]
+extern "C" fn __ts_space_write_u64(space: &Space, addr: u64, data: u64) {
+    space
+        .write_u64(&addr, data)
+        .unwrap_or_else(|addr| space_fault(space, addr))
+}
+
+#[no_mangle]
+extern "C" fn __ts_space_read_u8(space: &Space, addr: u64) -> u8 {
+    space
+        .read_u8(&addr)
+        .unwrap_or_else(|addr| space_fault(space, addr))
+}
+
+#[no_mangle]
+extern "C" fn __ts_space_read_u16(space: &Space, addr: u64) -> u16 {
+    space
+        .read_u16(&addr)
+        .unwrap_or_else(|addr| space_fault(space, addr))
+}
+
+#[no_mangle]
+extern "C" fn __ts_space_read_u32(space: &Space, addr: u64) -> u32 {
+    space
+        .read_u32(&addr)
+        .unwrap_or_else(|addr| space_fault(space, addr))
+}
+
+#[no_mangle]
+extern "C" fn __ts_space_read_u64(space: &Space, addr: u64) -> u64 {
+    space
+        .read_u64(&addr)
+        .unwrap_or_else(|addr| space_fault(space, addr))
+}
+
+fn fault_kind(kind: AccessKind) -> u32 {
+    match kind {
+        AccessKind::Read => 0,
+        AccessKind::Write => 1,
+    }
+}
+
+type UnmappedCallback = extern "C" fn(u32, u64, u32, &mut u64) -> bool;
+
+//policy codes are TS_UNMAPPED_* in ts_ffi.h, value is only used by TS_UNMAPPED_FILL and callback
+//only by TS_UNMAPPED_CALLBACK
+#[no_mangle]
+extern "C" fn __ts_space_set_unmapped_policy(
+    space: &mut Space,
+    policy: u32,
+    value: u64,
+    callback: Option<UnmappedCallback>,
+) {
+    space.set_unmapped_policy(match policy {
+        0 => UnmappedPolicy::Panic,
+        1 => UnmappedPolicy::Fill(value),
+        2 => {
+            let callback = callback.expect("TS_UNMAPPED_CALLBACK without callback!");
+            UnmappedPolicy::Callback(Rc::new(move |fault| {
+                let mut value = 0;
+                if callback(
+                    fault_kind(fault.kind),
+                    fault.addr,
+                    fault.size as u32,
+                    &mut value,
+                ) {
+                    Ok(value)
+                } else {
+                    Err(())
+                }
+            }))
+        }
+        _ => panic!("unknown unmapped policy {}!", policy),
+    })
+}
+
+//kind is TS_FAULT_READ or TS_FAULT_WRITE, false if there is no fault since the last clear
+#[no_mangle]
+extern "C" fn __ts_space_last_fault(
+    space: &Space,
+    kind: &mut u32,
+    addr: &mut u64,
+    size: &mut u32,
+) -> bool {
+    if let Some(fault) = space.last_fault() {
+        *kind = fault_kind(fault.kind);
+        *addr = fault.addr;
+        *size = fault.size as u32;
+        true
+    } else {
+        false
+    }
+}
+
+#[no_mangle]
+extern "C" fn __ts_space_clear_last_fault(space: &Space) {
+    space.clear_last_fault()
+}
+
+#[no_mangle]
+//NULL if the file can not be loaded, nothing is written then
+extern "C" fn __ts_load_elf(space: &Space, file_name: *const c_char) -> *const Elf {
+    let file_name = unsafe { CStr::from_ptr(file_name).to_str().unwrap() };
+    match load_elf(space, file_name) {
+        Ok(elf) => Box::into_raw(Box::new(elf)),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::ptr::null()
+        }
+    }
+}
+
+#[no_mangle]
+extern "C" fn __ts_elf_entry(elf: &Elf) -> u64 {
+    elf.entry
+}
+
+#[no_mangle]
+extern "C" fn __ts_elf_symbol(elf: &Elf, name: *const c_char, addr: &mut u64) -> bool {
+    let name = unsafe { CStr::from_ptr(name).to_str().unwrap() };
+    if let Some(s) = elf.symbol(name) {
+        *addr = s.addr;
+        true
+    } else {
+        false
+    }
+}
+
+#[no_mangle]
+extern "C" fn __ts_free_elf(elf: *mut Elf) {
+    std::mem::drop(unsafe { Box::from_raw(elf) })
+}
+
+//format codes are TS_IMAGE_* in ts_ffi.h, word_size is only used by readmemh
+fn image_format(format: u32, word_size: u32) -> ImageFormat {
+    match format {
+        0 => ImageFormat::Binary,
+        1 => ImageFormat::ReadMemH(word_size as usize),
+        2 => ImageFormat::IntelHex,
+        3 => ImageFormat::Srec,
+        _ => panic!("unknown image format {}!", format),
+    }
+}
+
+#[no_mangle]
+extern "C" fn __ts_region_load_image(
+    region: &Box<Rc<Region>>,
+    file_name: *const c_char,
+    format: u32,
+    word_size: u32,
+    offset: u64,
+) {
+    let file_name = unsafe { CStr::from_ptr(file_name).to_str().unwrap() };
+    load_image(
+        region.deref().deref(),
+        file_name,
+        image_format(format, word_size),
+        offset,
+    )
+    .unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[no_mangle]
+extern "C" fn __ts_space_load_image(
+    space: &Space,
+    file_name: *const c_char,
+    format: u32,
+    word_size: u32,
+    offset: u64,
+) {
+    let file_name = unsafe { CStr::from_ptr(file_name).to_str().unwrap() };
+    load_image(space, file_name, image_format(format, word_size), offset)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[no_mangle]
+extern "C" fn __ts_region_dump_image(
+    region: &Box<Rc<Region>>,
+    file_name: *const c_char,
+    format: u32,
+    word_size: u32,
+    addr: u64,
+    size: u64,
+    offset: u64,
+) {
+    let file_name = unsafe { CStr::from_ptr(file_name).to_str().unwrap() };
+    dump_image(
+        region.deref().deref(),
+        file_name,
+        image_format(format, word_size),
+        addr,
+        size,
+        offset,
+    )
+    .unwrap_or_else(|e| panic!("{}", e))
+}
+
+#[no_mangle]
+extern "C" fn __ts_space_dump_image(
+    space: &Space,
+    file_name: *const c_char,
+    format: u32,
+    word_size: u32,
+    addr: u64,
+    size: u64,
+    offset: u64,
+) {
+    let file_name = unsafe { CStr::from_ptr(file_name).to_str().unwrap() };
+    dump_image(
+        space,
+        file_name,
+        image_format(format, word_size),
+        addr,
+        size,
+        offset,
+    )
+    .unwrap_or_else(|e| panic!("{}", e))
+}
+
+//policy codes are TS_ROM_* in ts_ffi.h, NULL if the code is unknown
+#[no_mangle]
+extern "C" fn __ts_map_region_read_only(
+    region: &Box<Rc<Region>>,
+    base: u64,
+    policy: u32,
+) -> *const Box<Rc<Region>> {
+    let policy = match policy {
+        0 => RomPolicy::Fault,
+        1 => RomPolicy::Ignore,
+        _ => {
+            eprintln!("unknown read only policy {}!", policy);
+            return std::ptr::null();
+        }
+    };
+    to_c_ptr(Region::read_only(base, region.deref(), policy))
+}
+
 #[no_mangle]
-extern "C" fn __ts_space_write_u8(space: &Rc<Space>, addr: u64, data: u8) {
-    space.write_u8(&addr, data).unwrap()
+extern "C" fn __ts_region_backdoor_write_u8(region: &Box<Rc<Region>>, addr: u64, data: u8) {
+    region
+        .backdoor_write_bytes(&addr, &data.to_le_bytes())
+        .unwrap_or_else(|e| panic!("{}", e));
 }
 
 #[no_mangle]
-extern "C" fn __ts_space_write_u16(space: &Rc<Space>, addr: u64, data: u16) {
-    space.write_u16(&addr, data).unwrap()
+extern "C" fn __ts_region_backdoor_write_u16(region: &Box<Rc<Region>>, addr: u64, data: u16) {
+    region
+        .backdoor_write_bytes(&addr, &data.to_le_bytes())
+        .unwrap_or_else(|e| panic!("{}", e));
 }
 
 #[no_mangle]
-extern "C" fn __ts_space_write_u32(space: &Rc<Space>, addr: u64, data: u32) {
-    space.write_u32(&addr, data).unwrap()
+extern "C" fn __ts_region_backdoor_write_u32(region: &Box<Rc<Region>>, addr: u64, data: u32) {
+    region
+        .backdoor_write_bytes(&addr, &data.to_le_bytes())
+        .unwrap_or_else(|e| panic!("{}", e));
 }
 
 #[no_mangle]
-extern "C" fn __ts_space_write_u64(space: &Rc<Space>, addr: u64, data: u64) {
-    space.write_u64(&addr, data).unwrap()
+extern "C" fn __ts_region_backdoor_write_u64(region: &Box<Rc<Region>>, addr: u64, data: u64) {
+    region
+        .backdoor_write_bytes(&addr, &data.to_le_bytes())
+        .unwrap_or_else(|e| panic!("{}", e));
 }
 
 #[no_mangle]
-extern "C" fn __ts_space_read_u8(space: &Rc<Space>, addr: u64) -> u8 {
-    space.read_u8(&addr).unwrap()
+extern "C" fn __ts_space_backdoor_write_u8(space: &Space, addr: u64, data: u8) {
+    space
+        .backdoor_write_bytes(&addr, &data.to_le_bytes())
+        .unwrap_or_else(|e| panic!("{}", e));
 }
 
 #[no_mangle]
-extern "C" fn __ts_space_read_u16(space: &Rc<Space>, addr: u64) -> u16 {
-    space.read_u16(&addr).unwrap()
+extern "C" fn __ts_space_backdoor_write_u16(space: &Space, addr: u64, data: u16) {
+    space
+        .backdoor_write_bytes(&addr, &data.to_le_bytes())
+        .unwrap_or_else(|e| panic!("{}", e));
 }
 
 #[no_mangle]
-extern "C" fn __ts_space_read_u32(space: &Rc<Space>, addr: u64) -> u32 {
-    space.read_u32(&addr).unwrap()
+extern "C" fn __ts_space_backdoor_write_u32(space: &Space, addr: u64, data: u32) {
+    space
+        .backdoor_write_bytes(&addr, &data.to_le_bytes())
+        .unwrap_or_else(|e| panic!("{}", e));
 }
 
 #[no_mangle]
-extern "C" fn __ts_space_read_u64(space: &Rc<Space>, addr: u64) -> u64 {
-    space.read_u64(&addr).unwrap()
+extern "C" fn __ts_space_backdoor_write_u64(space: &Space, addr: u64, data: u64) {
+    space
+        .backdoor_write_bytes(&addr, &data.to_le_bytes())
+        .unwrap_or_else(|e| panic!("{}", e));
 }
 
 fn to_c_ptr(obj: Rc<Region>) -> *const Box<Rc<Region>> {
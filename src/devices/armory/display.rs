@@ -1,7 +1,10 @@
 use crate::devices::{FrameBuffer, PixelFormat};
 use crate::memory::prelude::*;
+use crate::memory::region::snapshot::{get_bytes, invalid, put_bytes};
 use std::cell::{RefCell, RefMut};
 use std::cmp::min;
+use std::io;
+use std::io::{Read, Write};
 use std::rc::Rc;
 
 const SIMPLE_FB_PAGE_SIZE: u32 = 4096;
@@ -42,6 +45,11 @@ impl Fb {
         let pos = page >> SIMPLE_FB_REFRESH_BATCH_SHIFT;
         self.dirties.borrow_mut()[pos as usize] |= 1 << bits
     }
+    fn set_all_dirty(&self) {
+        for page in 0..self.pages {
+            self.set_dirty(&((page as u64) << SIMPLE_FB_PAGE_SIZE_SHIFT))
+        }
+    }
 }
 
 impl FrameBuffer for Fb {
@@ -108,7 +116,7 @@ impl FrameBuffer for Fb {
     }
 }
 
-#[derive_io(Bytes, U8)]
+#[derive_io(Bytes, U8, Snapshot)]
 pub struct SimpleFb(Rc<Fb>);
 
 impl SimpleFb {
@@ -142,3 +150,20 @@ impl U8Access for SimpleFb {
         (*self.0.fb.borrow())[*addr as usize]
     }
 }
+
+//the whole frame is redrawn after load
+impl IOSnapshot for SimpleFb {
+    fn save(&self, w: &mut dyn Write) -> io::Result<()> {
+        put_bytes(w, &self.0.fb.borrow())
+    }
+
+    fn load(&self, r: &mut dyn Read) -> io::Result<()> {
+        let content = get_bytes(r)?;
+        if content.len() != self.0.fb.borrow().len() {
+            return Err(invalid("frame buffer size mismatch!"));
+        }
+        self.0.fb.borrow_mut().copy_from_slice(&content);
+        self.0.set_all_dirty();
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
 use crate::irq::IrqVecSender;
 use crate::memory::prelude::*;
+use crate::memory::region::snapshot::{get_bytes, get_u8, invalid, put_bytes, put_u8};
 use crate::memory::region::{Region, GHEAP};
 use crate::virtio::{
     DescMeta, Device, DeviceAccess, Error, MMIODevice, Queue, QueueClient, QueueSetting, Result,
 };
 use std::fs;
 use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Write};
 use std::ops::Deref;
 use std::os::unix::prelude::FileExt;
 use std::rc::Rc;
@@ -198,10 +201,12 @@ impl<T: BytesAccess> QueueClient for VirtIOBlkQueue<T> {
     }
 }
 
-#[derive_io(Bytes)]
+#[derive_io(Bytes, Snapshot)]
 pub struct VirtIOBlk {
     virtio_device: Device,
     num_sectors: u64,
+    //in-memory disk of VirtIOBlkConfig::SNAPSHOT
+    snapshot_disk: Option<Rc<Region>>,
 }
 
 impl VirtIOBlk {
@@ -215,6 +220,7 @@ impl VirtIOBlk {
         assert!(num_queues > 0);
         let mut virtio_device = Device::new(memory, irq_sender, 1, 2, 0, 0);
         virtio_device.get_irq_vec().set_enable_uncheck(0, true);
+        let mut snapshot_disk = None;
         let len = match config {
             VirtIOBlkConfig::RO => {
                 let file = Rc::new(
@@ -275,12 +281,14 @@ impl VirtIOBlk {
                         ),
                     ));
                 }
+                snapshot_disk = Some(snapshot);
                 content.len() as u64
             }
         };
         VirtIOBlk {
             virtio_device,
             num_sectors: len >> VIRTIO_BLK_SECTOR_SHIFT,
+            snapshot_disk,
         }
     }
 }
@@ -300,6 +308,38 @@ impl DeviceAccess for VirtIOBlk {
 
 impl MMIODevice for VirtIOBlk {}
 
+//ro and rw disks are files, only the in-memory disk of VirtIOBlkConfig::SNAPSHOT is saved
+impl IOSnapshot for VirtIOBlk {
+    fn save(&self, w: &mut dyn Write) -> io::Result<()> {
+        self.virtio_device.save(w)?;
+        if let Some(disk) = &self.snapshot_disk {
+            let mut content = vec![0u8; disk.info.size as usize];
+            BytesAccess::read(disk.deref(), &0, &mut content).map_err(invalid)?;
+            put_u8(w, 1)?;
+            put_bytes(w, &content)
+        } else {
+            put_u8(w, 0)
+        }
+    }
+
+    fn load(&self, r: &mut dyn Read) -> io::Result<()> {
+        self.virtio_device.load(r)?;
+        let has_disk = get_u8(r)? != 0;
+        match &self.snapshot_disk {
+            Some(disk) if has_disk => {
+                let content = get_bytes(r)?;
+                if content.len() as u64 != disk.info.size {
+                    return Err(invalid("virtio blk disk size mismatch!"));
+                }
+                BytesAccess::write(disk.deref(), &0, &content).map_err(invalid)?;
+                Ok(())
+            }
+            None if !has_disk => Ok(()),
+            _ => Err(invalid("virtio blk config mismatch!")),
+        }
+    }
+}
+
 impl BytesAccess for VirtIOBlk {
     fn write(&self, addr: &u64, data: &[u8]) -> std::result::Result<usize, String> {
         self.write_bytes(addr, data);
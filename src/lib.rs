@@ -6,6 +6,11 @@ pub mod memory;
 
 pub mod space;
 
+pub mod loader;
+
+#[cfg(feature = "sync")]
+pub mod sync;
+
 pub mod irq;
 
 pub mod virtio;
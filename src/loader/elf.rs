@@ -0,0 +1,282 @@
+use super::*;
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+const ELFCLASS32: u8 = 1;
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ELFDATA2MSB: u8 = 2;
+const PT_LOAD: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub addr: u64,
+    pub size: u64,
+}
+
+pub struct Elf {
+    pub entry: u64,
+    pub symbols: Vec<Symbol>,
+    index: HashMap<String, usize>,
+}
+
+impl Elf {
+    //if there are several symbols with the same name, the first one is returned
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.index.get(name).map(|&i| &self.symbols[i])
+    }
+}
+
+//bounds-checked field reader of both classes and both byte orders
+struct Reader<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    is_le: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&self, offset: u64, len: u64) -> Result<&'a [u8], Error> {
+        let end = offset.saturating_add(len);
+        if end > self.data.len() as u64 {
+            return Err(Error::Format(format!(
+                "[{:#x} -> {:#x}) beyond end of file {:#x}!",
+                offset,
+                end,
+                self.data.len()
+            )));
+        }
+        Ok(&self.data[offset as usize..end as usize])
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16, Error> {
+        let b = self.bytes(offset, 2)?.try_into().unwrap();
+        Ok(if self.is_le {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32, Error> {
+        let b = self.bytes(offset, 4)?.try_into().unwrap();
+        Ok(if self.is_le {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64, Error> {
+        let b = self.bytes(offset, 8)?.try_into().unwrap();
+        Ok(if self.is_le {
+            u64::from_le_bytes(b)
+        } else {
+            u64::from_be_bytes(b)
+        })
+    }
+
+    //offset of entry index of a table at base, entries starting past the end of file are reported
+    fn entry(&self, base: u64, index: u64, entsize: u64) -> Result<u64, Error> {
+        match index.checked_mul(entsize).and_then(|o| o.checked_add(base)) {
+            Some(offset) if offset <= self.data.len() as u64 => Ok(offset),
+            _ => Err(Error::Format(format!(
+                "entry {} of table @{:#x} beyond end of file!",
+                index, base
+            ))),
+        }
+    }
+
+    //address sized field
+    fn addr(&self, offset: u64) -> Result<u64, Error> {
+        if self.is_64 {
+            self.u64(offset)
+        } else {
+            self.u32(offset).map(|v| v as u64)
+        }
+    }
+
+    fn str(&self, offset: u64) -> Result<String, Error> {
+        let tail = self
+            .data
+            .get(offset as usize..)
+            .ok_or_else(|| Error::Format(format!("string @{:#x} beyond end of file!", offset)))?;
+        let len = tail
+            .iter()
+            .position(|&c| c == 0)
+            .ok_or_else(|| Error::Format(format!("unterminated string @{:#x}!", offset)))?;
+        Ok(String::from_utf8_lossy(&tail[..len]).to_string())
+    }
+}
+
+struct Header {
+    entry: u64,
+    phoff: u64,
+    shoff: u64,
+    phentsize: u64,
+    phnum: u64,
+    shentsize: u64,
+    shnum: u64,
+}
+
+fn header<'a>(data: &'a [u8]) -> Result<(Reader<'a>, Header), Error> {
+    if data.len() < 16 || &data[..4] != ELF_MAGIC {
+        return Err(Error::Format("not an ELF file!".to_string()));
+    }
+    let is_64 = match data[4] {
+        ELFCLASS32 => false,
+        ELFCLASS64 => true,
+        c => return Err(Error::Format(format!("invalid ELF class {}!", c))),
+    };
+    let is_le = match data[5] {
+        ELFDATA2LSB => true,
+        ELFDATA2MSB => false,
+        d => return Err(Error::Format(format!("invalid ELF data encoding {}!", d))),
+    };
+    let r = Reader { data, is_64, is_le };
+    //offsets of e_entry, e_phoff, e_shoff and e_phentsize
+    let (entry, phoff, shoff, phentsize) = if is_64 {
+        (0x18, 0x20, 0x28, 0x36)
+    } else {
+        (0x18, 0x1c, 0x20, 0x2a)
+    };
+    let header = Header {
+        entry: r.addr(entry)?,
+        phoff: r.addr(phoff)?,
+        shoff: r.addr(shoff)?,
+        phentsize: r.u16(phentsize)? as u64,
+        phnum: r.u16(phentsize + 2)? as u64,
+        shentsize: r.u16(phentsize + 4)? as u64,
+        shnum: r.u16(phentsize + 6)? as u64,
+    };
+    Ok((r, header))
+}
+
+//PT_LOAD segments with non-zero memsz, placed at p_paddr
+fn segments<'a>(r: &Reader<'a>, h: &Header) -> Result<Vec<(Segment, &'a [u8])>, Error> {
+    let mut segments = vec![];
+    for index in 0..h.phnum as usize {
+        let ph = r.entry(h.phoff, index as u64, h.phentsize)?;
+        if r.u32(ph)? != PT_LOAD {
+            continue;
+        }
+        let (offset, addr, file_size, mem_size) = if r.is_64 {
+            (
+                r.u64(ph + 0x8)?,
+                r.u64(ph + 0x18)?,
+                r.u64(ph + 0x20)?,
+                r.u64(ph + 0x28)?,
+            )
+        } else {
+            (
+                r.u32(ph + 0x4)? as u64,
+                r.u32(ph + 0xc)? as u64,
+                r.u32(ph + 0x10)? as u64,
+                r.u32(ph + 0x14)? as u64,
+            )
+        };
+        if mem_size == 0 {
+            continue;
+        }
+        if file_size > mem_size {
+            return Err(Error::Format(format!(
+                "segment {} filesz {:#x} > memsz {:#x}!",
+                index, file_size, mem_size
+            )));
+        }
+        if addr.checked_add(mem_size).is_none() {
+            return Err(Error::Format(format!(
+                "segment {} @{:#x} memsz {:#x} overflows!",
+                index, addr, mem_size
+            )));
+        }
+        segments.push((
+            Segment {
+                index,
+                addr,
+                file_size,
+                mem_size,
+            },
+            r.bytes(offset, file_size)?,
+        ))
+    }
+    Ok(segments)
+}
+
+//named symbols of all SHT_SYMTAB sections
+fn symbols(r: &Reader, h: &Header) -> Result<Vec<Symbol>, Error> {
+    let mut symbols = vec![];
+    for i in 0..h.shnum {
+        let sh = r.entry(h.shoff, i, h.shentsize)?;
+        if r.u32(sh + 0x4)? != SHT_SYMTAB {
+            continue;
+        }
+        //sh_offset, sh_size, sh_link, sh_entsize
+        let (offset, size, link, entsize) = if r.is_64 {
+            (
+                r.u64(sh + 0x18)?,
+                r.u64(sh + 0x20)?,
+                r.u32(sh + 0x28)?,
+                r.u64(sh + 0x38)?,
+            )
+        } else {
+            (
+                r.u32(sh + 0x10)? as u64,
+                r.u32(sh + 0x14)? as u64,
+                r.u32(sh + 0x18)?,
+                r.u32(sh + 0x24)? as u64,
+            )
+        };
+        if entsize == 0 {
+            return Err(Error::Format(format!("symtab section {} entsize is 0!", i)));
+        }
+        let strtab_sh = r.entry(h.shoff, link as u64, h.shentsize)?;
+        let strtab = if r.is_64 {
+            r.u64(strtab_sh + 0x18)?
+        } else {
+            r.u32(strtab_sh + 0x10)? as u64
+        };
+        for j in 0..size / entsize {
+            let sym = r.entry(offset, j, entsize)?;
+            let (name, addr, size) = if r.is_64 {
+                (r.u32(sym)?, r.u64(sym + 0x8)?, r.u64(sym + 0x10)?)
+            } else {
+                (
+                    r.u32(sym)?,
+                    r.u32(sym + 0x4)? as u64,
+                    r.u32(sym + 0x8)? as u64,
+                )
+            };
+            if name == 0 {
+                continue;
+            }
+            symbols.push(Symbol {
+                name: r.str(r.entry(strtab, name as u64, 1)?)?,
+                addr,
+                size,
+            })
+        }
+    }
+    Ok(symbols)
+}
+
+pub fn load_elf_bytes(space: &Space, data: &[u8]) -> Result<Elf, Error> {
+    let (r, h) = header(data)?;
+    let symbols = symbols(&r, &h)?;
+    write_segments(space, &segments(&r, &h)?)?;
+    let mut index = HashMap::new();
+    for (i, s) in symbols.iter().enumerate() {
+        index.entry(s.name.clone()).or_insert(i);
+    }
+    Ok(Elf {
+        entry: h.entry,
+        symbols,
+        index,
+    })
+}
+
+pub fn load_elf(space: &Space, file_name: &str) -> Result<Elf, Error> {
+    load_elf_bytes(space, &read_file(file_name)?)
+}
@@ -0,0 +1,361 @@
+use super::*;
+use crate::memory::region::{AccessError, Region};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ImageFormat {
+    //raw bytes
+    Binary,
+    //$readmemh style words of word size bytes, @addr is in words, words are little-endian in memory
+    ReadMemH(usize),
+    IntelHex,
+    Srec,
+}
+
+//Region and Space, an image can be loaded into or dumped from, loading bypasses write policies
+pub trait ImageTarget {
+    fn write_image(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError>;
+    fn read_image(&self, addr: &u64, data: &mut [u8]) -> Result<usize, AccessError>;
+}
+
+impl ImageTarget for Region {
+    fn write_image(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        self.backdoor_write_bytes(addr, data)
+    }
+
+    fn read_image(&self, addr: &u64, data: &mut [u8]) -> Result<usize, AccessError> {
+        self.try_read_bytes(addr, data)
+    }
+}
+
+impl ImageTarget for Space {
+    fn write_image(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        self.backdoor_write_bytes(addr, data)
+    }
+
+    fn read_image(&self, addr: &u64, data: &mut [u8]) -> Result<usize, AccessError> {
+        self.try_read_bytes(addr, data)
+    }
+}
+
+//contiguous records are merged
+#[derive(Default)]
+struct Chunks(Vec<(u64, Vec<u8>)>);
+
+impl Chunks {
+    fn push(&mut self, addr: u64, data: &[u8]) {
+        if let Some((a, d)) = self.0.last_mut() {
+            if *a + d.len() as u64 == addr {
+                d.extend_from_slice(data);
+                return;
+            }
+        }
+        self.0.push((addr, data.to_vec()))
+    }
+}
+
+fn format_err(line: usize, msg: &str) -> Error {
+    Error::Format(format!("line {}: {}", line + 1, msg))
+}
+
+fn hex_bytes(s: &str, line: usize) -> Result<Vec<u8>, Error> {
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return Err(format_err(line, "invalid hex bytes!"));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| format_err(line, "invalid hex bytes!"))
+        })
+        .collect()
+}
+
+fn sum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |acc, b| acc.wrapping_add(*b))
+}
+
+//replace comments by a space, line breaks are kept for error reporting
+fn strip_comments(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while chars.peek().map_or(false, |&n| n != '\n') {
+                    chars.next();
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for n in chars.by_ref() {
+                    if n == '\n' {
+                        out.push(n)
+                    }
+                    if prev == '*' && n == '/' {
+                        break;
+                    }
+                    prev = n;
+                }
+                out.push(' ')
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn parse_readmemh(content: &str, word_size: usize) -> Result<Chunks, Error> {
+    if word_size == 0 || word_size > 8 {
+        return Err(Error::Format(format!("invalid word size {}!", word_size)));
+    }
+    let mut chunks = Chunks::default();
+    let mut addr: u64 = 0;
+    for (i, code) in strip_comments(content).lines().enumerate() {
+        for token in code.split_whitespace() {
+            if let Some(a) = token.strip_prefix('@') {
+                let word = u64::from_str_radix(&a.replace('_', ""), 16)
+                    .map_err(|_| format_err(i, &format!("invalid address {}!", token)))?;
+                addr = word * word_size as u64;
+                continue;
+            }
+            let digits = token.replace('_', "");
+            if digits.len() > word_size * 2 {
+                return Err(format_err(i, &format!("word {} is too wide!", token)));
+            }
+            let word = u64::from_str_radix(&digits, 16)
+                .map_err(|_| format_err(i, &format!("invalid word {}!", token)))?;
+            chunks.push(addr, &word.to_le_bytes()[..word_size]);
+            addr += word_size as u64;
+        }
+    }
+    Ok(chunks)
+}
+
+fn parse_ihex(content: &str) -> Result<Chunks, Error> {
+    let mut chunks = Chunks::default();
+    let mut base: u64 = 0;
+    for (i, l) in content.lines().enumerate() {
+        let l = l.trim();
+        if l.is_empty() {
+            continue;
+        }
+        let record = l
+            .strip_prefix(':')
+            .ok_or_else(|| format_err(i, "record should start with ':'!"))?;
+        let bytes = hex_bytes(record, i)?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(format_err(i, "invalid record length!"));
+        }
+        if sum(&bytes) != 0 {
+            return Err(format_err(i, "checksum error!"));
+        }
+        let offset = ((bytes[1] as u64) << 8) | bytes[2] as u64;
+        let data = &bytes[4..bytes.len() - 1];
+        let data_u16 = || {
+            if data.len() != 2 {
+                Err(format_err(i, "invalid address record!"))
+            } else {
+                Ok(((data[0] as u64) << 8) | data[1] as u64)
+            }
+        };
+        match bytes[3] {
+            0x00 => chunks.push(base + offset, data),
+            0x01 => break,
+            0x02 => base = data_u16()? << 4,
+            0x04 => base = data_u16()? << 16,
+            //start address
+            0x03 | 0x05 => {}
+            t => return Err(format_err(i, &format!("unknown record type {:#x}!", t))),
+        }
+    }
+    Ok(chunks)
+}
+
+fn parse_srec(content: &str) -> Result<Chunks, Error> {
+    let mut chunks = Chunks::default();
+    for (i, l) in content.lines().enumerate() {
+        let l = l.trim();
+        if l.is_empty() {
+            continue;
+        }
+        if !l.starts_with('S') || l.len() < 2 || !l.is_ascii() {
+            return Err(format_err(i, "record should start with 'S'!"));
+        }
+        let bytes = hex_bytes(&l[2..], i)?;
+        if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+            return Err(format_err(i, "invalid record length!"));
+        }
+        if sum(&bytes) != 0xff {
+            return Err(format_err(i, "checksum error!"));
+        }
+        let addr_len = match &l[1..2] {
+            "0" | "1" | "5" | "9" => 2,
+            "2" | "6" | "8" => 3,
+            "3" | "7" => 4,
+            t => return Err(format_err(i, &format!("unknown record type S{}!", t))),
+        };
+        if bytes.len() < addr_len + 2 {
+            return Err(format_err(i, "invalid record length!"));
+        }
+        let addr = bytes[1..1 + addr_len]
+            .iter()
+            .fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        match &l[1..2] {
+            "1" | "2" | "3" => chunks.push(addr, &bytes[1 + addr_len..bytes.len() - 1]),
+            "7" | "8" | "9" => break,
+            //header and record count
+            _ => {}
+        }
+    }
+    Ok(chunks)
+}
+
+//image address + offset = memory address, nothing is written if the image is malformed
+pub fn load_image<T: ImageTarget + ?Sized>(
+    target: &T,
+    file_name: &str,
+    format: ImageFormat,
+    offset: u64,
+) -> Result<(), Error> {
+    let data = read_file(file_name)?;
+    let chunks = if format == ImageFormat::Binary {
+        Chunks(vec![(0, data)])
+    } else {
+        let content = String::from_utf8(data)
+            .map_err(|_| Error::Format(format!("{} is not a text file!", file_name)))?;
+        match format {
+            ImageFormat::ReadMemH(word_size) => parse_readmemh(&content, word_size)?,
+            ImageFormat::IntelHex => parse_ihex(&content)?,
+            ImageFormat::Srec => parse_srec(&content)?,
+            ImageFormat::Binary => unreachable!(),
+        }
+    };
+    for (addr, data) in chunks.0.iter() {
+        target
+            .write_image(&(addr + offset), data)
+            .map_err(Error::Access)?;
+    }
+    Ok(())
+}
+
+const DUMP_RECORD_SIZE: u64 = 16;
+
+//split at DUMP_RECORD_SIZE aligned image addresses, so records never cross a 64K boundary
+fn records(image_addr: u64, data: &[u8]) -> impl Iterator<Item = (u64, &[u8])> {
+    let mut done: usize = 0;
+    std::iter::from_fn(move || {
+        if done >= data.len() {
+            return None;
+        }
+        let addr = image_addr + done as u64;
+        let len = std::cmp::min(
+            (DUMP_RECORD_SIZE - addr % DUMP_RECORD_SIZE) as usize,
+            data.len() - done,
+        );
+        let record = (addr, &data[done..done + len]);
+        done += len;
+        Some(record)
+    })
+}
+
+fn write_record(
+    w: &mut dyn Write,
+    prefix: &str,
+    bytes: &[u8],
+    checksum: u8,
+) -> std::io::Result<()> {
+    write!(w, "{}", prefix)?;
+    for b in bytes {
+        write!(w, "{:02X}", b)?;
+    }
+    writeln!(w, "{:02X}", checksum)
+}
+
+fn dump_records(
+    w: &mut dyn Write,
+    format: ImageFormat,
+    image_addr: u64,
+    data: &[u8],
+) -> std::io::Result<()> {
+    match format {
+        ImageFormat::Binary => w.write_all(data),
+        ImageFormat::ReadMemH(word_size) => {
+            writeln!(w, "@{:x}", image_addr / word_size as u64)?;
+            for word in data.chunks(word_size) {
+                let mut bytes = [0u8; 8];
+                bytes[..word_size].copy_from_slice(word);
+                writeln!(w, "{:01$x}", u64::from_le_bytes(bytes), word_size * 2)?;
+            }
+            Ok(())
+        }
+        ImageFormat::IntelHex => {
+            let mut upper: Option<u64> = None;
+            for (addr, record) in records(image_addr, data) {
+                if upper != Some(addr >> 16) {
+                    upper = Some(addr >> 16);
+                    let bytes = [2, 0, 0, 4, (addr >> 24) as u8, (addr >> 16) as u8];
+                    write_record(w, ":", &bytes, sum(&bytes).wrapping_neg())?;
+                }
+                let mut bytes = vec![record.len() as u8, (addr >> 8) as u8, addr as u8, 0];
+                bytes.extend_from_slice(record);
+                write_record(w, ":", &bytes, sum(&bytes).wrapping_neg())?;
+            }
+            writeln!(w, ":00000001FF")
+        }
+        ImageFormat::Srec => {
+            writeln!(w, "S0030000FC")?;
+            for (addr, record) in records(image_addr, data) {
+                let mut bytes = vec![record.len() as u8 + 5];
+                bytes.extend_from_slice(&(addr as u32).to_be_bytes());
+                bytes.extend_from_slice(record);
+                write_record(w, "S3", &bytes, !sum(&bytes))?;
+            }
+            writeln!(w, "S70500000000FA")
+        }
+    }
+}
+
+//memory address - offset = image address
+pub fn dump_image<T: ImageTarget + ?Sized>(
+    target: &T,
+    file_name: &str,
+    format: ImageFormat,
+    addr: u64,
+    size: u64,
+    offset: u64,
+) -> Result<(), Error> {
+    let image_addr = addr
+        .checked_sub(offset)
+        .ok_or_else(|| Error::Format(format!("addr {:#x} < offset {:#x}!", addr, offset)))?;
+    match format {
+        ImageFormat::ReadMemH(word_size) => {
+            if word_size == 0
+                || word_size > 8
+                || image_addr % word_size as u64 != 0
+                || size % word_size as u64 != 0
+            {
+                return Err(Error::Format(format!(
+                    "addr {:#x} size {:#x} should be aligned to word size {}!",
+                    image_addr, size, word_size
+                )));
+            }
+        }
+        ImageFormat::IntelHex | ImageFormat::Srec => {
+            if image_addr + size > 0x1_0000_0000 {
+                return Err(Error::Format(format!(
+                    "addr {:#x} size {:#x} exceeds 4G!",
+                    image_addr, size
+                )));
+            }
+        }
+        ImageFormat::Binary => {}
+    }
+    let mut data = vec![0u8; size as usize];
+    target.read_image(&addr, &mut data).map_err(Error::Access)?;
+    let io_err = |e: std::io::Error| Error::Io(format!("can not write {}! {}", file_name, e));
+    let mut w = BufWriter::new(File::create(file_name).map_err(io_err)?);
+    dump_records(&mut w, format, image_addr, &data).map_err(io_err)?;
+    w.flush().map_err(io_err)
+}
@@ -0,0 +1,85 @@
+#[cfg(test)]
+mod test;
+
+mod elf;
+
+pub use elf::{load_elf, load_elf_bytes, Elf, Symbol};
+
+mod image;
+
+pub use image::{dump_image, load_image, ImageFormat, ImageTarget};
+
+use crate::memory::region::AccessError;
+use crate::space::{unmapped_addr, Space};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+//a chunk of image placed at addr, bytes after file_size up to mem_size are zero-filled
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Segment {
+    pub index: usize,
+    pub addr: u64,
+    pub file_size: u64,
+    pub mem_size: u64,
+}
+
+impl Display for Segment {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "segment {} [{:#x} -> {:#x}) filesz {:#x}",
+            self.index,
+            self.addr,
+            self.addr + self.mem_size,
+            self.file_size
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(String),
+    Format(String),
+    //the offending segment and the first address not in any region
+    Unmapped(Segment, u64),
+    Access(AccessError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Io(s) => write!(f, "Io!{}", s),
+            Error::Format(s) => write!(f, "Format!{}", s),
+            Error::Unmapped(seg, addr) => write!(f, "Unmapped!{} @{:#x}", seg, addr),
+            Error::Access(e) => write!(f, "Access!{}", e),
+        }
+    }
+}
+
+fn read_file(file_name: &str) -> Result<Vec<u8>, Error> {
+    std::fs::read(file_name).map_err(|e| Error::Io(format!("can not read {}! {}", file_name, e)))
+}
+
+//all segments are checked before any byte is written
+fn write_segments(space: &Space, segments: &[(Segment, &[u8])]) -> Result<(), Error> {
+    for (seg, _) in segments {
+        space
+            .check_mapped(&seg.addr, seg.mem_size as usize)
+            .map_err(|addr| Error::Unmapped(*seg, addr))?;
+    }
+    const ZEROS: [u8; 0x1000] = [0; 0x1000];
+    for (seg, data) in segments {
+        space
+            .backdoor_write_bytes(&seg.addr, data)
+            .map_err(|e| Error::Unmapped(*seg, unmapped_addr(e)))?;
+        let mut addr = seg.addr + seg.file_size;
+        while addr < seg.addr + seg.mem_size {
+            let len = std::cmp::min(ZEROS.len() as u64, seg.addr + seg.mem_size - addr);
+            space
+                .backdoor_write_bytes(&addr, &ZEROS[..len as usize])
+                .map_err(|e| Error::Unmapped(*seg, unmapped_addr(e)))?;
+            addr += len;
+        }
+    }
+    Ok(())
+}
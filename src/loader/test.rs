@@ -0,0 +1,397 @@
+use super::*;
+use crate::memory::region::{Region, RomPolicy, GHEAP};
+use std::ops::Deref;
+
+struct ElfBuilder {
+    is_64: bool,
+    is_le: bool,
+    data: Vec<u8>,
+}
+
+impl ElfBuilder {
+    fn put(&mut self, v: u64, size: usize) {
+        let bytes = v.to_le_bytes();
+        if self.is_le {
+            self.data.extend_from_slice(&bytes[..size])
+        } else {
+            self.data.extend(bytes[..size].iter().rev())
+        }
+    }
+
+    fn addr(&mut self, v: u64) {
+        self.put(v, if self.is_64 { 8 } else { 4 })
+    }
+
+    //segments: (paddr, data, memsz), symbols: (name, value, size)
+    fn build(
+        is_64: bool,
+        is_le: bool,
+        entry: u64,
+        segments: &[(u64, &[u8], u64)],
+        symbols: &[(&str, u64, u64)],
+    ) -> Vec<u8> {
+        let mut b = ElfBuilder {
+            is_64,
+            is_le,
+            data: vec![],
+        };
+        let (ehsize, phentsize, shentsize, symentsize) = if is_64 {
+            (64, 56, 64, 24)
+        } else {
+            (52, 32, 40, 16)
+        };
+        let phoff = ehsize;
+        let mut offset = phoff + phentsize * segments.len() as u64;
+        let seg_offsets = segments
+            .iter()
+            .map(|(_, d, _)| {
+                let o = offset;
+                offset += d.len() as u64;
+                o
+            })
+            .collect::<Vec<_>>();
+        let mut strtab = vec![0u8];
+        let names = symbols
+            .iter()
+            .map(|(n, _, _)| {
+                let o = strtab.len() as u64;
+                strtab.extend_from_slice(n.as_bytes());
+                strtab.push(0);
+                o
+            })
+            .collect::<Vec<_>>();
+        let symtab_off = offset;
+        let symtab_size = symentsize * (symbols.len() as u64 + 1);
+        let strtab_off = symtab_off + symtab_size;
+        let shoff = strtab_off + strtab.len() as u64;
+
+        //header
+        b.data.extend_from_slice(b"\x7fELF");
+        b.data.push(if is_64 { 2 } else { 1 });
+        b.data.push(if is_le { 1 } else { 2 });
+        b.data.push(1);
+        b.data.resize(16, 0);
+        b.put(2, 2);
+        b.put(0xf3, 2);
+        b.put(1, 4);
+        b.addr(entry);
+        b.addr(phoff);
+        b.addr(shoff);
+        b.put(0, 4);
+        b.put(ehsize, 2);
+        b.put(phentsize, 2);
+        b.put(segments.len() as u64, 2);
+        b.put(shentsize, 2);
+        b.put(3, 2);
+        b.put(0, 2);
+
+        for ((paddr, data, memsz), off) in segments.iter().zip(seg_offsets.iter()) {
+            b.put(1, 4);
+            if is_64 {
+                b.put(7, 4);
+            }
+            b.addr(*off);
+            //vaddr differs from paddr, loader should use paddr
+            b.addr(*paddr + 0x1000_0000);
+            b.addr(*paddr);
+            b.addr(data.len() as u64);
+            b.addr(*memsz);
+            if !is_64 {
+                b.put(7, 4);
+            }
+            b.addr(8);
+        }
+        for (_, data, _) in segments {
+            b.data.extend_from_slice(data)
+        }
+
+        b.data.resize(b.data.len() + symentsize as usize, 0);
+        for ((_, value, size), name) in symbols.iter().zip(names.iter()) {
+            b.put(*name, 4);
+            if is_64 {
+                b.put(0x11, 1);
+                b.put(0, 1);
+                b.put(1, 2);
+                b.put(*value, 8);
+                b.put(*size, 8);
+            } else {
+                b.put(*value, 4);
+                b.put(*size, 4);
+                b.put(0x11, 1);
+                b.put(0, 1);
+                b.put(1, 2);
+            }
+        }
+        b.data.extend_from_slice(&strtab);
+
+        //null, symtab, strtab
+        b.data.resize(b.data.len() + shentsize as usize, 0);
+        for (ty, off, size, link, entsize) in [
+            (2, symtab_off, symtab_size, 2, symentsize),
+            (3, strtab_off, strtab.len() as u64, 0, 0),
+        ]
+        .iter()
+        {
+            b.put(0, 4);
+            b.put(*ty, 4);
+            b.addr(0);
+            b.addr(0);
+            b.addr(*off);
+            b.addr(*size);
+            b.put(*link, 4);
+            b.put(0, 4);
+            b.addr(1);
+            b.addr(*entsize);
+        }
+        b.data
+    }
+}
+
+fn elf_space() -> Space {
+    let mut space = Space::new();
+    space
+        .add_region(
+            "rom",
+            &Region::read_only(0x1000, &GHEAP.alloc(0x100, 8).unwrap(), RomPolicy::Fault),
+        )
+        .unwrap();
+    space
+        .add_region(
+            "ram",
+            &Region::remap(0x80000000, &GHEAP.alloc(0x1000, 8).unwrap()),
+        )
+        .unwrap();
+    space
+}
+
+#[test]
+fn load_elf_segments() {
+    for &(is_64, is_le) in [(true, true), (false, true), (false, false)].iter() {
+        let space = elf_space();
+        space.write_u64(&0x80000010, 0xdeadbeef).unwrap();
+        let image = ElfBuilder::build(
+            is_64,
+            is_le,
+            0x1000,
+            &[
+                (0x1000, &[0x13, 0, 0, 0, 0x73, 0, 0x10, 0], 8),
+                (0x80000000, &[1, 2, 3, 4], 0x20),
+            ],
+            &[("_start", 0x1000, 8), ("tohost", 0x80000008, 8)],
+        );
+        let elf = load_elf_bytes(&space, &image).unwrap();
+        assert_eq!(elf.entry, 0x1000);
+        assert_eq!(space.read_u32(&0x1004), Ok(0x100073));
+        assert_eq!(
+            space.try_write_u32(&0x1004, 0),
+            Err(AccessError::ReadOnly(0x1004))
+        );
+        assert_eq!(space.read_u32(&0x80000000), Ok(0x04030201));
+        //bss is zero-filled
+        assert_eq!(space.read_u64(&0x80000010), Ok(0));
+        assert_eq!(elf.symbols.len(), 2);
+        assert_eq!(
+            elf.symbol("tohost"),
+            Some(&Symbol {
+                name: "tohost".to_string(),
+                addr: 0x80000008,
+                size: 8
+            })
+        );
+        assert_eq!(elf.symbol("fromhost"), None);
+    }
+}
+
+#[test]
+fn load_elf_unmapped() {
+    let space = elf_space();
+    let image = ElfBuilder::build(
+        true,
+        true,
+        0x1000,
+        &[(0x1000, &[1; 8], 8), (0x80000ff0, &[2; 8], 0x20)],
+        &[],
+    );
+    match load_elf_bytes(&space, &image) {
+        Err(Error::Unmapped(seg, addr)) => {
+            assert_eq!(
+                seg,
+                Segment {
+                    index: 1,
+                    addr: 0x80000ff0,
+                    file_size: 8,
+                    mem_size: 0x20
+                }
+            );
+            assert_eq!(addr, 0x80001000);
+        }
+        _ => panic!("segment out of regions should fail!"),
+    }
+    //nothing is written
+    assert_eq!(space.read_u8(&0x1000), Ok(0));
+    assert!(matches!(
+        load_elf_bytes(&space, &image[..40]),
+        Err(Error::Format(_))
+    ));
+    let overflow = ElfBuilder::build(
+        true,
+        true,
+        0x1000,
+        &[(0xffff_ffff_e000_0000, &[3; 8], 0x4000_0000)],
+        &[],
+    );
+    assert!(matches!(
+        load_elf_bytes(&space, &overflow),
+        Err(Error::Format(_))
+    ));
+    //table offsets wrapping around are format errors
+    let image = ElfBuilder::build(true, true, 0x1000, &[(0x1000, &[1; 8], 8)], &[("a", 0, 0)]);
+    let mut bad = image.clone();
+    bad[0x20..0x28].copy_from_slice(&(u64::MAX - 1).to_le_bytes());
+    assert!(matches!(
+        load_elf_bytes(&space, &bad),
+        Err(Error::Format(_))
+    ));
+    let shoff = u64::from_le_bytes(image[0x28..0x30].try_into().unwrap()) as usize;
+    let strtab_offset = shoff + 2 * 64 + 0x18;
+    let mut bad = image.clone();
+    bad[strtab_offset..strtab_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(matches!(
+        load_elf_bytes(&space, &bad),
+        Err(Error::Format(_))
+    ));
+    let symtab_offset = shoff + 64 + 0x18;
+    let mut bad = image;
+    bad[symtab_offset..symtab_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(matches!(
+        load_elf_bytes(&space, &bad),
+        Err(Error::Format(_))
+    ));
+    assert!(matches!(
+        load_elf(&space, "not_exist.elf"),
+        Err(Error::Io(_))
+    ));
+}
+
+fn image_file(name: &str, content: &[u8]) -> String {
+    let path = std::env::temp_dir().join(format!("ts_image_{}_{}", std::process::id(), name));
+    std::fs::write(&path, content).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn image_round_trip() {
+    let space = elf_space();
+    let data = (0..0x50).map(|i| i as u8 ^ 0xa5).collect::<Vec<_>>();
+    space.write_bytes(&0x80000104, &data).unwrap();
+    for (i, &format) in [
+        ImageFormat::Binary,
+        ImageFormat::ReadMemH(1),
+        ImageFormat::ReadMemH(4),
+        ImageFormat::IntelHex,
+        ImageFormat::Srec,
+    ]
+    .iter()
+    .enumerate()
+    {
+        let file = image_file(&format!("round_trip_{}", i), b"");
+        dump_image(&space, &file, format, 0x80000104, 0x50, 0x80000000).unwrap();
+        //load into rom at another offset
+        let target = elf_space();
+        let offset = if format == ImageFormat::Binary {
+            0x1004
+        } else {
+            0x1000 - 0x100
+        };
+        load_image(&target, &file, format, offset).unwrap();
+        let mut buf = vec![0u8; 0x50];
+        target.read_bytes(&0x1004, &mut buf).unwrap();
+        assert_eq!(buf, data);
+        assert_eq!(target.read_u32(&0x1000), Ok(0));
+        assert_eq!(target.read_u32(&0x1054), Ok(0));
+        std::fs::remove_file(&file).unwrap();
+    }
+    let file = image_file("unaligned", b"");
+    assert!(matches!(
+        dump_image(&space, &file, ImageFormat::ReadMemH(4), 0x80000102, 8, 0),
+        Err(Error::Format(_))
+    ));
+    assert!(matches!(
+        dump_image(&space, &file, ImageFormat::IntelHex, 0xfffffff8, 0x10, 0),
+        Err(Error::Format(_))
+    ));
+    assert!(matches!(
+        dump_image(&space, &file, ImageFormat::Binary, 0x80000ff8, 0x10, 0),
+        Err(Error::Access(_))
+    ));
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn image_text_formats() {
+    let space = elf_space();
+    let file = image_file(
+        "ihex",
+        b":020000040000FA\n:10010000214601360121470136007EFE09D2190140\n:00000001FF\n",
+    );
+    load_image(&space, &file, ImageFormat::IntelHex, 0x80000000).unwrap();
+    assert_eq!(space.read_u32(&0x80000100), Ok(0x36014621));
+    assert_eq!(space.read_u8(&0x8000010f), Ok(0x01));
+    std::fs::remove_file(&file).unwrap();
+
+    let file = image_file(
+        "srec",
+        b"S00F000068656C6C6F202020202000003C\nS1137AF00A0A0D0000000000000000000000000061\nS9030000FC\n",
+    );
+    load_image(&space, &file, ImageFormat::Srec, 0x80000000 - 0x7000).unwrap();
+    assert_eq!(space.read_u32(&0x80000af0), Ok(0x000d0a0a));
+    std::fs::remove_file(&file).unwrap();
+
+    let file = image_file(
+        "readmemh",
+        b"// header\n@4 01020304 /* inline\n comment */ 05060708\n@0\naabbccdd\n",
+    );
+    load_image(&space, &file, ImageFormat::ReadMemH(4), 0x80000000).unwrap();
+    assert_eq!(space.read_u32(&0x80000010), Ok(0x01020304));
+    assert_eq!(space.read_u32(&0x80000014), Ok(0x05060708));
+    assert_eq!(space.read_u32(&0x80000000), Ok(0xaabbccdd));
+    std::fs::remove_file(&file).unwrap();
+
+    //bad checksum, nothing is written
+    let file = image_file(
+        "bad_ihex",
+        b":04000000DEADBEEFC4\n:10010000214601360121470136007EFE09D2190141\n:00000001FF\n",
+    );
+    assert!(matches!(
+        load_image(&space, &file, ImageFormat::IntelHex, 0x1000),
+        Err(Error::Format(_))
+    ));
+    assert_eq!(space.read_u32(&0x1000), Ok(0));
+    std::fs::remove_file(&file).unwrap();
+    assert!(matches!(
+        load_image(&space, "not_exist.hex", ImageFormat::IntelHex, 0),
+        Err(Error::Io(_))
+    ));
+}
+
+#[test]
+fn image_region() {
+    let region = Region::read_only(0x2000, &GHEAP.alloc(0x100, 8).unwrap(), RomPolicy::Ignore);
+    let file = image_file("region_bin", &[1, 2, 3, 4, 5, 6, 7, 8]);
+    load_image(region.deref(), &file, ImageFormat::Binary, 0x2010).unwrap();
+    assert_eq!(region.try_read_u64(&0x2010), Ok(0x0807060504030201));
+    dump_image(
+        region.deref(),
+        &file,
+        ImageFormat::ReadMemH(8),
+        0x2010,
+        8,
+        0x2000,
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "@2\n0807060504030201\n"
+    );
+    std::fs::remove_file(&file).unwrap();
+}
@@ -0,0 +1,119 @@
+use super::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+fn order_of(size: u64) -> u32 {
+    size.max(1).next_power_of_two().trailing_zeros()
+}
+
+//power of 2 blocks at offsets aligned to their size, a freed block merges with its buddy.
+//offsets are relative to the base, so alignments above the one of the base are not satisfied
+pub(super) struct BuddyAllocator {
+    info: MemInfo,
+    //free offsets by order
+    free: Vec<BTreeSet<u64>>,
+    //offset -> order
+    alloced: BTreeMap<u64, u32>,
+}
+
+impl BuddyAllocator {
+    pub(super) fn new(base: u64, size: u64) -> BuddyAllocator {
+        let mut allocator = BuddyAllocator {
+            info: MemInfo { base, size },
+            free: vec![BTreeSet::new(); 64],
+            alloced: BTreeMap::new(),
+        };
+        allocator.restore(vec![MemInfo { base, size }], vec![]);
+        allocator
+    }
+
+    //cut a range into the largest blocks its offsets allow
+    fn insert_range(&mut self, info: &MemInfo) {
+        let mut offset = info.base - self.info.base;
+        let end = offset + info.size;
+        while offset < end {
+            let order = std::cmp::min(
+                if offset == 0 {
+                    63
+                } else {
+                    offset.trailing_zeros()
+                },
+                63 - (end - offset).leading_zeros(),
+            );
+            self.free[order as usize].insert(offset);
+            offset += 1 << order;
+        }
+    }
+}
+
+impl AllocPolicy for BuddyAllocator {
+    fn alloc(&mut self, size: u64, align: u64) -> Option<MemInfo> {
+        if size > 1 << 63 || align > 1 << 63 || self.info.base & (align.max(1) - 1) != 0 {
+            return None;
+        }
+        let need = std::cmp::max(order_of(size), order_of(align));
+        let mut order = (need..64).find(|o| !self.free[*o as usize].is_empty())?;
+        let offset = self.free[order as usize].pop_first().unwrap();
+        while order > need {
+            order -= 1;
+            self.free[order as usize].insert(offset + (1 << order));
+        }
+        self.alloced.insert(offset, need);
+        Some(MemInfo {
+            base: self.info.base + offset,
+            size,
+        })
+    }
+
+    fn free(&mut self, addr: u64) {
+        let mut offset = addr.wrapping_sub(self.info.base);
+        let mut order = if let Some(order) = self.alloced.remove(&offset) {
+            order
+        } else {
+            panic!("invalid free @{}", addr);
+        };
+        while order < 63 && self.free[order as usize].remove(&(offset ^ (1 << order))) {
+            offset &= !(1 << order);
+            order += 1;
+        }
+        self.free[order as usize].insert(offset);
+    }
+
+    fn free_list(&self) -> Vec<MemInfo> {
+        let mut list: Vec<MemInfo> = self
+            .free
+            .iter()
+            .enumerate()
+            .flat_map(|(order, offsets)| {
+                offsets.iter().map(move |offset| MemInfo {
+                    base: self.info.base + offset,
+                    size: 1 << order,
+                })
+            })
+            .collect();
+        list.sort_by_key(|info| info.base);
+        list
+    }
+
+    //the whole blocks
+    fn alloced_list(&self) -> Vec<MemInfo> {
+        self.alloced
+            .iter()
+            .map(|(offset, order)| MemInfo {
+                base: self.info.base + offset,
+                size: 1 << order,
+            })
+            .collect()
+    }
+
+    fn restore(&mut self, free: Vec<MemInfo>, alloced: Vec<MemInfo>) {
+        self.free.iter_mut().for_each(|f| f.clear());
+        self.alloced.clear();
+        for info in free.iter() {
+            self.insert_range(info)
+        }
+        for info in alloced {
+            self.alloced
+                .insert(info.base - self.info.base, order_of(info.size));
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use super::*;
+use std::collections::BTreeMap;
+
+//allocates upward from a cursor for fast boot-time setup,
+//freed memory is only reclaimed when every block has been freed
+pub(super) struct BumpAllocator {
+    info: MemInfo,
+    cursor: u64,
+    alloced: BTreeMap<u64, u64>,
+}
+
+impl BumpAllocator {
+    pub(super) fn new(base: u64, size: u64) -> BumpAllocator {
+        BumpAllocator {
+            info: MemInfo { base, size },
+            cursor: base,
+            alloced: BTreeMap::new(),
+        }
+    }
+}
+
+impl AllocPolicy for BumpAllocator {
+    fn alloc(&mut self, size: u64, align: u64) -> Option<MemInfo> {
+        let base = align_up(self.cursor, align);
+        if base.checked_add(size)? > self.info.base + self.info.size {
+            return None;
+        }
+        self.cursor = base + size;
+        self.alloced.insert(base, size);
+        Some(MemInfo { base, size })
+    }
+
+    fn free(&mut self, addr: u64) {
+        if self.alloced.remove(&addr).is_none() {
+            panic!("invalid free @{}", addr);
+        }
+        if self.alloced.is_empty() {
+            self.cursor = self.info.base
+        }
+    }
+
+    //only the memory above the cursor
+    fn free_list(&self) -> Vec<MemInfo> {
+        let end = self.info.base + self.info.size;
+        if self.cursor == end {
+            vec![]
+        } else {
+            vec![MemInfo {
+                base: self.cursor,
+                size: end - self.cursor,
+            }]
+        }
+    }
+
+    fn alloced_list(&self) -> Vec<MemInfo> {
+        self.alloced
+            .iter()
+            .map(|(base, size)| MemInfo {
+                base: *base,
+                size: *size,
+            })
+            .collect()
+    }
+
+    //the cursor is where the free memory starts
+    fn restore(&mut self, free: Vec<MemInfo>, alloced: Vec<MemInfo>) {
+        self.alloced = alloced.iter().map(|info| (info.base, info.size)).collect();
+        self.cursor = free
+            .iter()
+            .map(|info| info.base)
+            .max()
+            .unwrap_or(self.info.base + self.info.size);
+    }
+
+    //only above the cursor
+    fn reserve(&mut self, base: u64, size: u64) -> Option<MemInfo> {
+        if base < self.cursor || base.checked_add(size)? > self.info.base + self.info.size {
+            return None;
+        }
+        self.cursor = base + size;
+        self.alloced.insert(base, size);
+        Some(MemInfo { base, size })
+    }
+
+    //the last block may grow, any block may shrink
+    fn resize(&mut self, addr: u64, size: u64) -> bool {
+        let block = if let Some(block) = self.alloced.get_mut(&addr) {
+            block
+        } else {
+            return false;
+        };
+        if addr + *block == self.cursor {
+            if addr
+                .checked_add(size)
+                .map_or(true, |end| end > self.info.base + self.info.size)
+            {
+                return false;
+            }
+            self.cursor = addr + size;
+        } else if size > *block {
+            return false;
+        }
+        *block = size;
+        true
+    }
+
+    fn free_range(&mut self, addr: u64, base: u64, size: u64) -> bool {
+        let end = if let Some(block) = self.alloced.remove(&addr) {
+            addr + block
+        } else {
+            return false;
+        };
+        if base != addr {
+            self.alloced.insert(addr, base - addr);
+        }
+        if end != base + size {
+            self.alloced.insert(base + size, end - base - size);
+        } else if end == self.cursor {
+            self.cursor = base
+        }
+        if self.alloced.is_empty() {
+            self.cursor = self.info.base
+        }
+        true
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AllocError {
+    //(size, align)
+    OutOfMemory(u64, u64),
+    //(base, size) of a reservation not free
+    NotFree(u64, u64),
+    //not the base of a block ever alloced
+    InvalidFree(u64),
+    //the base of a block already freed
+    DoubleFree(u64),
+    //realloc or resize of an address not the base of a live block
+    InvalidRealloc(u64),
+    //the policy can not free a part of the block @addr
+    PartialFree(u64),
+    //the block @addr has other handles, so it can not be changed in place
+    Shared(u64),
+    //the memory behind a block can not be made, e.g. a file can not be mapped
+    Backing(String),
+}
+
+impl Display for AllocError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            AllocError::OutOfMemory(size, align) => {
+                write!(f, "OutOfMemory!size {:#x} align {:#x}", size, align)
+            }
+            AllocError::NotFree(base, size) => {
+                write!(f, "NotFree!{:#x} - {:#x}", base, base.wrapping_add(*size))
+            }
+            AllocError::InvalidFree(addr) => write!(f, "InvalidFree!addr {:#x}", addr),
+            AllocError::DoubleFree(addr) => write!(f, "DoubleFree!addr {:#x}", addr),
+            AllocError::InvalidRealloc(addr) => write!(f, "InvalidRealloc!addr {:#x}", addr),
+            AllocError::PartialFree(addr) => {
+                write!(f, "PartialFree!block @{:#x} can not be freed in part", addr)
+            }
+            AllocError::Shared(addr) => write!(f, "Shared!block @{:#x} is shared", addr),
+            AllocError::Backing(msg) => write!(f, "Backing!{}", msg),
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use super::*;
+use intrusive_collections::rbtree::RBTree;
+use intrusive_collections::{intrusive_adapter, Bound, KeyAdapter, RBTreeLink};
+use std::collections::BTreeSet;
+
+struct Block {
+    link: RBTreeLink,
+    info: MemInfo,
+}
+
+intrusive_adapter!(BlockAdapter = Box<Block>:Block {link:RBTreeLink});
+
+impl<'a> KeyAdapter<'a> for BlockAdapter {
+    type Key = u64;
+    fn get_key(&self, b: &'a Block) -> u64 {
+        b.info.base
+    }
+}
+
+fn new_block(info: MemInfo) -> Box<Block> {
+    Box::new(Block {
+        link: RBTreeLink::new(),
+        info,
+    })
+}
+
+fn fits(info: &MemInfo, size: u64, align: u64) -> bool {
+    info.size >= size + (align_up(info.base, align) - info.base)
+}
+
+//the power of 2 of size
+pub(super) fn size_class(size: u64) -> usize {
+    63 - (size | 1).leading_zeros() as usize
+}
+
+//free blocks bucketed by size class in address order, with a bitmap of non-empty classes,
+//and all of them in size order
+struct SizeIndex {
+    classes: [BTreeSet<(u64, u64)>; 64],
+    nonempty: u64,
+    by_size: BTreeSet<(u64, u64)>,
+}
+
+impl SizeIndex {
+    fn new() -> SizeIndex {
+        SizeIndex {
+            classes: std::array::from_fn(|_| BTreeSet::new()),
+            nonempty: 0,
+            by_size: BTreeSet::new(),
+        }
+    }
+
+    fn insert(&mut self, info: &MemInfo) {
+        let class = size_class(info.size);
+        self.classes[class].insert((info.base, info.size));
+        self.nonempty |= 1 << class;
+        self.by_size.insert((info.size, info.base));
+    }
+
+    fn remove(&mut self, info: &MemInfo) {
+        let class = size_class(info.size);
+        self.classes[class].remove(&(info.base, info.size));
+        if self.classes[class].is_empty() {
+            self.nonempty &= !(1 << class);
+        }
+        self.by_size.remove(&(info.size, info.base));
+    }
+
+    fn clear(&mut self) {
+        self.classes.iter_mut().for_each(|c| c.clear());
+        self.nonempty = 0;
+        self.by_size.clear();
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) enum Fit {
+    First,
+    Best,
+    Next,
+}
+
+//free blocks in an address ordered tree, split on alloc and coalesced on free
+pub(super) struct FitAllocator {
+    info: MemInfo,
+    fit: Fit,
+    free_blocks: RBTree<BlockAdapter>,
+    alloced_blocks: RBTree<BlockAdapter>,
+    free_sizes: SizeIndex,
+    //next fit searches from the end of the last allocation
+    cursor: u64,
+}
+
+impl FitAllocator {
+    pub(super) fn new(base: u64, size: u64, fit: Fit) -> FitAllocator {
+        let mut allocator = FitAllocator {
+            info: MemInfo { base, size },
+            fit,
+            free_blocks: RBTree::new(BlockAdapter::new()),
+            alloced_blocks: RBTree::new(BlockAdapter::new()),
+            free_sizes: SizeIndex::new(),
+            cursor: base,
+        };
+        allocator.insert_free(new_block(MemInfo { base, size }));
+        allocator
+    }
+
+    fn insert_free(&mut self, block: Box<Block>) {
+        self.free_sizes.insert(&block.info);
+        self.free_blocks.insert(block);
+    }
+
+    fn remove_free(&mut self, base: u64) -> Box<Block> {
+        let block = self.free_blocks.find_mut(&base).remove().unwrap();
+        self.free_sizes.remove(&block.info);
+        block
+    }
+
+    //insert a free block, coalesced with its neighbours
+    fn release(&mut self, mut block: Box<Block>) {
+        let mut info = block.info;
+        let pre = self
+            .free_blocks
+            .upper_bound(Bound::Excluded(&info.base))
+            .get()
+            .map(|b| b.info);
+        if let Some(pre) = pre.filter(|pre| pre.base + pre.size == info.base) {
+            self.remove_free(pre.base);
+            info = MemInfo {
+                base: pre.base,
+                size: pre.size + info.size,
+            };
+        }
+        let post = self
+            .free_blocks
+            .find(&(info.base + info.size))
+            .get()
+            .map(|b| b.info);
+        if let Some(post) = post {
+            self.remove_free(post.base);
+            info.size += post.size;
+        }
+        block.info = info;
+        self.insert_free(block);
+    }
+
+    //the lowest addressed free block in [start, end) which fits.
+    //blocks of classes above the class of size + align - 1 fit whatever their base is,
+    //only the classes between are searched block by block
+    fn first_fit(&self, size: u64, align: u64, start: u64, end: u64) -> Option<u64> {
+        let sure = size_class(size.saturating_add(align.max(1) - 1));
+        let mut classes = self.free_sizes.nonempty & !((1 << size_class(size)) - 1);
+        let mut found: Option<u64> = None;
+        while classes != 0 {
+            let class = classes.trailing_zeros() as usize;
+            classes &= classes - 1;
+            let end = found.unwrap_or(end);
+            let mut blocks = self.free_sizes.classes[class].range((start, 0)..(end, 0));
+            let hit = if class > sure {
+                blocks.next()
+            } else {
+                blocks.find(|(b, s)| fits(&MemInfo { base: *b, size: *s }, size, align))
+            };
+            if let Some((base, _)) = hit {
+                found = Some(*base)
+            }
+        }
+        found
+    }
+
+    //the smallest free block which fits, the lower addressed one of the same size
+    fn best_fit(&self, size: u64, align: u64) -> Option<u64> {
+        self.free_sizes
+            .by_size
+            .range((size, 0)..)
+            .find(|(s, b)| fits(&MemInfo { base: *b, size: *s }, size, align))
+            .map(|(_, base)| *base)
+    }
+
+    fn search(&self, size: u64, align: u64) -> Option<u64> {
+        match self.fit {
+            Fit::First => self.first_fit(size, align, 0, u64::MAX),
+            Fit::Best => self.best_fit(size, align),
+            Fit::Next => self
+                .first_fit(size, align, self.cursor, u64::MAX)
+                .or_else(|| self.first_fit(size, align, 0, self.cursor)),
+        }
+    }
+}
+
+impl AllocPolicy for FitAllocator {
+    fn alloc(&mut self, size: u64, align: u64) -> Option<MemInfo> {
+        let mut block = self.remove_free(self.search(size, align)?);
+        let info = block.info;
+        let base = align_up(info.base, align);
+        if base != info.base {
+            self.insert_free(new_block(MemInfo {
+                base: info.base,
+                size: base - info.base,
+            }))
+        }
+        if info.base + info.size != base + size {
+            self.insert_free(new_block(MemInfo {
+                base: base + size,
+                size: info.base + info.size - base - size,
+            }))
+        }
+        block.info = MemInfo { base, size };
+        self.alloced_blocks.insert(block);
+        self.cursor = base + size;
+        Some(MemInfo { base, size })
+    }
+
+    fn free(&mut self, addr: u64) {
+        let block = if let Some(block) = self.alloced_blocks.find_mut(&addr).remove() {
+            block
+        } else {
+            panic!("invalid free @{}", addr);
+        };
+        self.release(block)
+    }
+
+    fn free_list(&self) -> Vec<MemInfo> {
+        self.free_blocks.iter().map(|b| b.info).collect()
+    }
+
+    fn alloced_list(&self) -> Vec<MemInfo> {
+        self.alloced_blocks.iter().map(|b| b.info).collect()
+    }
+
+    fn restore(&mut self, free: Vec<MemInfo>, alloced: Vec<MemInfo>) {
+        self.free_blocks.clear();
+        self.alloced_blocks.clear();
+        self.free_sizes.clear();
+        self.cursor = self.info.base;
+        for info in free {
+            self.insert_free(new_block(info))
+        }
+        for info in alloced {
+            self.alloced_blocks.insert(new_block(info));
+        }
+    }
+
+    fn reserve(&mut self, base: u64, size: u64) -> Option<MemInfo> {
+        let end = base.checked_add(size)?;
+        let info = self
+            .free_blocks
+            .upper_bound(Bound::Included(&base))
+            .get()
+            .map(|b| b.info)
+            .filter(|info| info.base + info.size >= end)?;
+        let mut block = self.remove_free(info.base);
+        if base != info.base {
+            self.insert_free(new_block(MemInfo {
+                base: info.base,
+                size: base - info.base,
+            }))
+        }
+        if info.base + info.size != base + size {
+            self.insert_free(new_block(MemInfo {
+                base: base + size,
+                size: info.base + info.size - base - size,
+            }))
+        }
+        block.info = MemInfo { base, size };
+        self.alloced_blocks.insert(block);
+        Some(MemInfo { base, size })
+    }
+
+    //grow into the free block right after it, or give the tail back
+    fn resize(&mut self, addr: u64, size: u64) -> bool {
+        let info = if let Some(block) = self.alloced_blocks.find(&addr).get() {
+            block.info
+        } else {
+            return false;
+        };
+        let end = info.base + info.size;
+        if size > info.size {
+            let post = self.free_blocks.find(&end).get().map(|b| b.info);
+            if post.map_or(true, |post| post.size < size - info.size) {
+                return false;
+            }
+            let mut post = self.remove_free(end);
+            if post.info.size != size - info.size {
+                post.info = MemInfo {
+                    base: addr + size,
+                    size: post.info.size - (size - info.size),
+                };
+                self.insert_free(post);
+            }
+        } else if size < info.size {
+            self.release(new_block(MemInfo {
+                base: addr + size,
+                size: info.size - size,
+            }))
+        }
+        let mut block = self.alloced_blocks.find_mut(&addr).remove().unwrap();
+        block.info.size = size;
+        self.alloced_blocks.insert(block);
+        true
+    }
+
+    fn free_range(&mut self, addr: u64, base: u64, size: u64) -> bool {
+        let mut block = if let Some(block) = self.alloced_blocks.find_mut(&addr).remove() {
+            block
+        } else {
+            return false;
+        };
+        let info = block.info;
+        if base != info.base {
+            self.alloced_blocks.insert(new_block(MemInfo {
+                base: info.base,
+                size: base - info.base,
+            }));
+        }
+        if info.base + info.size != base + size {
+            self.alloced_blocks.insert(new_block(MemInfo {
+                base: base + size,
+                size: info.base + info.size - base - size,
+            }));
+        }
+        block.info = MemInfo { base, size };
+        self.release(block);
+        true
+    }
+}
@@ -1,125 +1,316 @@
-pub mod list;
+mod buddy;
+mod bump;
+mod error;
+mod fit;
+mod slab;
+mod stats;
 #[cfg(test)]
 mod test;
 
 use super::*;
+use buddy::BuddyAllocator;
+use bump::BumpAllocator;
 use core::ops::Deref;
-use list::*;
-use std::sync::{Arc, Mutex};
+pub use error::AllocError;
+use fit::{Fit, FitAllocator};
+use slab::SlabAllocator;
+pub use stats::{AllocStats, LiveBlock};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
 
-#[cfg(test)]
-#[repr(C)]
-pub struct Allocator {
-    pub info: MemInfo,
-    pub free_blocks: Arc<List<MemInfo>>,
-    pub alloced_blocks: Arc<List<MemInfo>>,
+//how blocks are placed, Allocator, LockedAllocator and Heap can be built with any of them
+pub trait AllocPolicy: Send {
+    //None if no free block fits
+    fn alloc(&mut self, size: u64, align: u64) -> Option<MemInfo>;
+    //only called with the base of a live block, panic otherwise
+    fn free(&mut self, addr: u64);
+    //in address order
+    fn free_list(&self) -> Vec<MemInfo>;
+    fn alloced_list(&self) -> Vec<MemInfo>;
+    //replace the whole state with lists from free_list and alloced_list of the same policy
+    fn restore(&mut self, free: Vec<MemInfo>, alloced: Vec<MemInfo>);
+    //the optional ones below are not supported by default.
+    //allocate exactly [base, base + size), None if it is not free
+    fn reserve(&mut self, _base: u64, _size: u64) -> Option<MemInfo> {
+        None
+    }
+    //resize the live block at addr without moving it
+    fn resize(&mut self, _addr: u64, _size: u64) -> bool {
+        false
+    }
+    //free [base, base + size) inside the live block at addr, the parts left stay alive as blocks
+    fn free_range(&mut self, _addr: u64, _base: u64, _size: u64) -> bool {
+        false
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Policy {
+    //the lowest addressed block which fits
+    FirstFit,
+    //the smallest block which fits
+    BestFit,
+    //the first block which fits from the end of the last allocation
+    NextFit,
+    Buddy,
+    Slab,
+    Bump,
+}
+
+impl Policy {
+    pub fn build(&self, base: u64, size: u64) -> Box<dyn AllocPolicy> {
+        match self {
+            Policy::FirstFit => Box::new(FitAllocator::new(base, size, Fit::First)),
+            Policy::BestFit => Box::new(FitAllocator::new(base, size, Fit::Best)),
+            Policy::NextFit => Box::new(FitAllocator::new(base, size, Fit::Next)),
+            Policy::Buddy => Box::new(BuddyAllocator::new(base, size)),
+            Policy::Slab => Box::new(SlabAllocator::new(base, size)),
+            Policy::Bump => Box::new(BumpAllocator::new(base, size)),
+        }
+    }
 }
 
-#[cfg(not(test))]
 #[repr(C)]
 pub struct Allocator {
     pub info: MemInfo,
-    free_blocks: Arc<List<MemInfo>>,
-    alloced_blocks: Arc<List<MemInfo>>,
+    policy: Box<dyn AllocPolicy>,
+    live: BTreeMap<u64, LiveBlock>,
+    //bases of freed blocks not alloced again, to tell a double free from an invalid one
+    freed: BTreeSet<u64>,
+    alloced_bytes: u64,
+    high_water: u64,
+    alloc_count: u64,
 }
 
 impl Allocator {
     pub fn new(base: u64, size: u64) -> Allocator {
+        Allocator::with_policy(base, size, Policy::FirstFit)
+    }
+
+    pub fn with_policy(base: u64, size: u64, policy: Policy) -> Allocator {
+        Allocator::from_policy(base, size, policy.build(base, size))
+    }
+
+    //a custom policy managing [base, base + size)
+    pub fn from_policy(base: u64, size: u64, policy: Box<dyn AllocPolicy>) -> Allocator {
         Allocator {
-            info: MemInfo {
-                base: base,
-                size: size,
-            },
-            free_blocks: List::cons(
-                MemInfo {
-                    base: base,
-                    size: size,
-                },
-                &List::nil(),
-            ),
-            alloced_blocks: List::nil(),
+            info: MemInfo { base, size },
+            policy,
+            live: BTreeMap::new(),
+            freed: BTreeSet::new(),
+            alloced_bytes: 0,
+            high_water: 0,
+            alloc_count: 0,
+        }
+    }
+
+    pub fn alloc(&mut self, size: u64, align: u64) -> Result<MemInfo, AllocError> {
+        self.alloc_block(size, align, None)
+    }
+
+    //the label is shown in the dump of live blocks
+    pub fn alloc_labeled(
+        &mut self,
+        size: u64,
+        align: u64,
+        label: &str,
+    ) -> Result<MemInfo, AllocError> {
+        self.alloc_block(size, align, Some(label.to_string()))
+    }
+
+    fn alloc_block(
+        &mut self,
+        size: u64,
+        align: u64,
+        label: Option<String>,
+    ) -> Result<MemInfo, AllocError> {
+        let info = self
+            .policy
+            .alloc(size, align)
+            .ok_or(AllocError::OutOfMemory(size, align))?;
+        self.track(info, label);
+        Ok(info)
+    }
+
+    fn track(&mut self, info: MemInfo, label: Option<String>) {
+        let reused: Vec<u64> = self
+            .freed
+            .range(info.base..info.base + info.size.max(1))
+            .cloned()
+            .collect();
+        for base in reused {
+            self.freed.remove(&base);
+        }
+        self.live.insert(info.base, LiveBlock { info, label });
+        self.alloced_bytes += info.size;
+        self.high_water = std::cmp::max(self.high_water, self.alloced_bytes);
+        self.alloc_count += 1;
+    }
+
+    //carve out a fixed range, such as a MMIO hole or a boot vector
+    pub fn reserve(&mut self, base: u64, size: u64) -> Result<MemInfo, AllocError> {
+        let info = self
+            .policy
+            .reserve(base, size)
+            .ok_or(AllocError::NotFree(base, size))?;
+        self.track(info, None);
+        Ok(info)
+    }
+
+    pub fn free(&mut self, addr: u64) -> Result<(), AllocError> {
+        let block = if let Some(block) = self.live.remove(&addr) {
+            block
+        } else if self.freed.contains(&addr) {
+            return Err(AllocError::DoubleFree(addr));
+        } else {
+            return Err(AllocError::InvalidFree(addr));
+        };
+        self.policy.free(addr);
+        self.freed.insert(addr);
+        self.alloced_bytes -= block.info.size;
+        Ok(())
+    }
+
+    //resize the live block at addr without moving it, false if the policy can not
+    pub fn resize(&mut self, addr: u64, size: u64) -> Result<bool, AllocError> {
+        let block = if let Some(block) = self.live.get_mut(&addr) {
+            block
+        } else {
+            return Err(AllocError::InvalidRealloc(addr));
+        };
+        if !self.policy.resize(addr, size) {
+            return Ok(false);
         }
+        self.alloced_bytes = self.alloced_bytes - block.info.size + size;
+        self.high_water = std::cmp::max(self.high_water, self.alloced_bytes);
+        block.info.size = size;
+        Ok(true)
     }
 
-    pub fn alloc(&mut self, size: u64, align: u64) -> Option<MemInfo> {
-        let (block, free_blocks) = List::delete(&self.free_blocks, |item| {
-            let info = item.car().unwrap();
-            info.size >= size + (align_up(info.base, align) - info.base)
-        });
-        if let Some(item) = block {
-            let info = item.car().unwrap();
-            let result = Some(MemInfo {
-                base: align_up(info.base, align),
-                size: size,
-            });
-            self.free_blocks = free_blocks;
-            if align_up(info.base, align) != info.base {
-                self.free_blocks = List::cons(
-                    MemInfo {
-                        base: info.base,
-                        size: align_up(info.base, align) - info.base,
+    //resize in place if the policy can, otherwise move to a new block aligned to align,
+    //the old block is kept if no block fits
+    pub fn realloc(&mut self, addr: u64, size: u64, align: u64) -> Result<MemInfo, AllocError> {
+        let label = if let Some(block) = self.live.get(&addr) {
+            block.label.clone()
+        } else {
+            return Err(AllocError::InvalidRealloc(addr));
+        };
+        if align_down(addr, align) == addr && self.resize(addr, size)? {
+            return Ok(MemInfo { base: addr, size });
+        }
+        let info = self.alloc_block(size, align, label)?;
+        self.free(addr)?;
+        Ok(info)
+    }
+
+    //free a part of a live block, the parts left stay alive
+    pub fn free_range(&mut self, base: u64, size: u64) -> Result<(), AllocError> {
+        let range_end = match base.checked_add(size) {
+            Some(end) if size != 0 => end,
+            _ => return Err(AllocError::InvalidFree(base)),
+        };
+        let block = if let Some((_, block)) = self
+            .live
+            .range(..=base)
+            .next_back()
+            .filter(|(_, b)| range_end <= b.info.base + b.info.size)
+        {
+            block.clone()
+        } else {
+            return Err(AllocError::InvalidFree(base));
+        };
+        if block.info.base == base && block.info.size == size {
+            return self.free(base);
+        }
+        if !self.policy.free_range(block.info.base, base, size) {
+            return Err(AllocError::PartialFree(block.info.base));
+        }
+        self.live.remove(&block.info.base);
+        self.alloced_bytes -= size;
+        let end = block.info.base + block.info.size;
+        for info in [
+            MemInfo {
+                base: block.info.base,
+                size: base - block.info.base,
+            },
+            MemInfo {
+                base: base + size,
+                size: end - base - size,
+            },
+        ] {
+            if info.size != 0 {
+                self.live.insert(
+                    info.base,
+                    LiveBlock {
+                        info,
+                        label: block.label.clone(),
                     },
-                    &self.free_blocks,
-                )
+                );
             }
-            if info.size != size + (align_up(info.base, align) - info.base) {
-                self.free_blocks = List::cons(
-                    MemInfo {
-                        base: align_up(info.base, align) + size,
-                        size: info.size - size - (align_up(info.base, align) - info.base),
+        }
+        Ok(())
+    }
+
+    //in address order
+    pub fn free_list(&self) -> Vec<MemInfo> {
+        self.policy.free_list()
+    }
+
+    pub fn alloced_list(&self) -> Vec<MemInfo> {
+        self.policy.alloced_list()
+    }
+
+    //replace the whole state, blocks must be inside info and not overlapped
+    pub fn restore(&mut self, free: Vec<MemInfo>, alloced: Vec<MemInfo>) {
+        self.restore_labeled(free, alloced, vec![])
+    }
+
+    //as restore, with the labels of live blocks by base
+    pub fn restore_labeled(
+        &mut self,
+        free: Vec<MemInfo>,
+        alloced: Vec<MemInfo>,
+        labels: Vec<(u64, String)>,
+    ) {
+        let mut labels = labels.into_iter().collect::<BTreeMap<_, _>>();
+        self.live = alloced
+            .iter()
+            .map(|info| {
+                (
+                    info.base,
+                    LiveBlock {
+                        info: *info,
+                        label: labels.remove(&info.base),
                     },
-                    &self.free_blocks,
                 )
-            }
-            self.alloced_blocks = List::cons(result.unwrap(), &self.alloced_blocks);
-            result
-        } else {
-            None
-        }
+            })
+            .collect();
+        self.freed.clear();
+        self.alloced_bytes = alloced.iter().map(|info| info.size).sum();
+        self.high_water = std::cmp::max(self.high_water, self.alloced_bytes);
+        self.policy.restore(free, alloced)
     }
 
-    pub fn free(&mut self, addr: u64) {
-        // println!("free {}!", addr);
-        let (alloced_block, alloced_blocks) = List::delete(&self.alloced_blocks, |item| {
-            item.car().unwrap().base == addr
-        });
-        if let Some(item) = alloced_block {
-            let (pre_block, free_blocks) = List::delete(&self.free_blocks, |i| {
-                let info = i.car().unwrap();
-                info.base + info.size == item.car().unwrap().base
-            });
-            let pre_info = if let Some(pre) = pre_block {
-                let info = pre.car().unwrap();
-                self.free_blocks = free_blocks;
-                MemInfo {
-                    base: info.base,
-                    size: info.size + item.car().unwrap().size,
-                }
-            } else {
-                item.car().unwrap()
-            };
-
-            let (post_block, free_blocks) = List::delete(&self.free_blocks, |i| {
-                let info = i.car().unwrap();
-                pre_info.base + pre_info.size == info.base
-            });
-            let post_info = if let Some(post) = post_block {
-                let info = post.car().unwrap();
-                self.free_blocks = free_blocks;
-                MemInfo {
-                    base: pre_info.base,
-                    size: pre_info.size + info.size,
-                }
-            } else {
-                pre_info
-            };
-
-            self.free_blocks = List::cons(post_info, &self.free_blocks);
-            self.alloced_blocks = alloced_blocks;
-        } else {
-            panic!("invalid free @{}", addr);
+    pub fn stats(&self) -> AllocStats {
+        let free = self.policy.free_list();
+        AllocStats {
+            alloced_bytes: self.alloced_bytes,
+            free_bytes: free.iter().map(|info| info.size).sum(),
+            largest_free: free.iter().map(|info| info.size).max().unwrap_or(0),
+            high_water: self.high_water,
+            live_blocks: self.live.len() as u64,
+            alloc_count: self.alloc_count,
         }
     }
+
+    //in address order, with the sizes requested
+    pub fn live_blocks(&self) -> Vec<LiveBlock> {
+        self.live.values().cloned().collect()
+    }
+
+    pub fn live_block(&self, addr: u64) -> Option<&LiveBlock> {
+        self.live.get(&addr)
+    }
 }
 
 pub struct LockedAllocator {
@@ -132,12 +323,35 @@ impl LockedAllocator {
             inner: Mutex::new(Allocator::new(base, size)),
         }
     }
-    pub fn alloc(&self, size: u64, align: u64) -> Option<MemInfo> {
+    pub fn with_policy(base: u64, size: u64, policy: Policy) -> LockedAllocator {
+        LockedAllocator {
+            inner: Mutex::new(Allocator::with_policy(base, size, policy)),
+        }
+    }
+    pub fn alloc(&self, size: u64, align: u64) -> Result<MemInfo, AllocError> {
         self.inner.lock().unwrap().alloc(size, align)
     }
-    pub fn free(&self, addr: u64) {
+    pub fn alloc_labeled(&self, size: u64, align: u64, label: &str) -> Result<MemInfo, AllocError> {
+        self.inner.lock().unwrap().alloc_labeled(size, align, label)
+    }
+    pub fn free(&self, addr: u64) -> Result<(), AllocError> {
         self.inner.lock().unwrap().free(addr)
     }
+    pub fn reserve(&self, base: u64, size: u64) -> Result<MemInfo, AllocError> {
+        self.inner.lock().unwrap().reserve(base, size)
+    }
+    pub fn realloc(&self, addr: u64, size: u64, align: u64) -> Result<MemInfo, AllocError> {
+        self.inner.lock().unwrap().realloc(addr, size, align)
+    }
+    pub fn free_range(&self, base: u64, size: u64) -> Result<(), AllocError> {
+        self.inner.lock().unwrap().free_range(base, size)
+    }
+    pub fn stats(&self) -> AllocStats {
+        self.inner.lock().unwrap().stats()
+    }
+    pub fn live_blocks(&self) -> Vec<LiveBlock> {
+        self.inner.lock().unwrap().live_blocks()
+    }
 }
 
 impl Deref for LockedAllocator {
@@ -0,0 +1,164 @@
+use super::fit::{size_class, Fit, FitAllocator};
+use super::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+const MIN_CLASS: usize = 3;
+const MAX_CLASS: usize = 10;
+const SLAB_SIZE: u64 = 0x1000;
+
+//the class of the power of 2 objects a request fits in, None if it is too large
+fn object_class(size: u64, align: u64) -> Option<usize> {
+    let size = std::cmp::max(size, align).max(1);
+    if size > 1 << MAX_CLASS {
+        None
+    } else {
+        Some(std::cmp::max(
+            size_class(size.next_power_of_two()),
+            MIN_CLASS,
+        ))
+    }
+}
+
+//merge adjacent blocks of a list sorted by base
+fn coalesce(list: Vec<MemInfo>) -> Vec<MemInfo> {
+    let mut coalesced: Vec<MemInfo> = vec![];
+    for info in list {
+        match coalesced.last_mut() {
+            Some(last) if last.base + last.size == info.base => last.size += info.size,
+            _ => coalesced.push(info),
+        }
+    }
+    coalesced
+}
+
+//size classes of power of 2 objects carved from SLAB_SIZE slabs, larger requests fall back to first fit.
+//slabs are never given back, freed objects are only reused by the same class
+pub(super) struct SlabAllocator {
+    fallback: FitAllocator,
+    //free objects by class
+    objects: Vec<BTreeSet<u64>>,
+    //base -> (size, class), the object size for objects
+    alloced: BTreeMap<u64, (u64, Option<usize>)>,
+}
+
+impl SlabAllocator {
+    pub(super) fn new(base: u64, size: u64) -> SlabAllocator {
+        SlabAllocator {
+            fallback: FitAllocator::new(base, size, Fit::First),
+            objects: vec![BTreeSet::new(); MAX_CLASS + 1],
+            alloced: BTreeMap::new(),
+        }
+    }
+}
+
+impl AllocPolicy for SlabAllocator {
+    fn alloc(&mut self, size: u64, align: u64) -> Option<MemInfo> {
+        let class = if let Some(class) = object_class(size, align) {
+            class
+        } else {
+            let info = self.fallback.alloc(size, align)?;
+            self.alloced.insert(info.base, (info.size, None));
+            return Some(info);
+        };
+        if self.objects[class].is_empty() {
+            //a whole slab can not be carved near the end of memory, the object is a block of its own
+            let slab = match self.fallback.alloc(SLAB_SIZE, 1 << class) {
+                Some(slab) => slab,
+                None => {
+                    let info = self.fallback.alloc(size, align)?;
+                    self.alloced.insert(info.base, (info.size, None));
+                    return Some(info);
+                }
+            };
+            self.objects[class].extend((slab.base..slab.base + slab.size).step_by(1 << class));
+        }
+        let base = self.objects[class].pop_first().unwrap();
+        self.alloced.insert(base, (1 << class, Some(class)));
+        Some(MemInfo { base, size })
+    }
+
+    fn free(&mut self, addr: u64) {
+        match self.alloced.remove(&addr) {
+            Some((_, Some(class))) => {
+                self.objects[class].insert(addr);
+            }
+            Some((_, None)) => self.fallback.free(addr),
+            None => panic!("invalid free @{}", addr),
+        }
+    }
+
+    fn free_list(&self) -> Vec<MemInfo> {
+        let mut list = self.fallback.free_list();
+        for (class, objects) in self.objects.iter().enumerate() {
+            list.extend(objects.iter().map(|base| MemInfo {
+                base: *base,
+                size: 1 << class,
+            }))
+        }
+        list.sort_by_key(|info| info.base);
+        list
+    }
+
+    //the whole objects
+    fn alloced_list(&self) -> Vec<MemInfo> {
+        self.alloced
+            .iter()
+            .map(|(base, (size, _))| MemInfo {
+                base: *base,
+                size: *size,
+            })
+            .collect()
+    }
+
+    //free objects are given back to the fallback coalesced, live objects become blocks of their own,
+    //so they are freed to the fallback as well
+    fn restore(&mut self, mut free: Vec<MemInfo>, alloced: Vec<MemInfo>) {
+        self.objects.iter_mut().for_each(|o| o.clear());
+        self.alloced = alloced
+            .iter()
+            .map(|info| (info.base, (info.size, None)))
+            .collect();
+        free.sort_by_key(|info| info.base);
+        self.fallback.restore(coalesce(free), alloced);
+    }
+
+    fn reserve(&mut self, base: u64, size: u64) -> Option<MemInfo> {
+        let info = self.fallback.reserve(base, size)?;
+        self.alloced.insert(base, (size, None));
+        Some(info)
+    }
+
+    //objects are resized within their class, the others by the fallback
+    fn resize(&mut self, addr: u64, size: u64) -> bool {
+        match self.alloced.get_mut(&addr) {
+            Some((object, Some(_))) => size <= *object,
+            Some((block, None)) if self.fallback.resize(addr, size) => {
+                *block = size;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn free_range(&mut self, addr: u64, base: u64, size: u64) -> bool {
+        let info = match self.alloced.get(&addr) {
+            Some((block, None)) => MemInfo {
+                base: addr,
+                size: *block,
+            },
+            _ => return false,
+        };
+        if !self.fallback.free_range(addr, base, size) {
+            return false;
+        }
+        self.alloced.remove(&addr);
+        if base != info.base {
+            self.alloced.insert(info.base, (base - info.base, None));
+        }
+        if info.base + info.size != base + size {
+            self.alloced
+                .insert(base + size, (info.base + info.size - base - size, None));
+        }
+        true
+    }
+}
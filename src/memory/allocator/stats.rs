@@ -0,0 +1,66 @@
+use super::*;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[repr(C)]
+pub struct AllocStats {
+    //requested bytes of live blocks
+    pub alloced_bytes: u64,
+    pub free_bytes: u64,
+    pub largest_free: u64,
+    //peak of alloced_bytes
+    pub high_water: u64,
+    pub live_blocks: u64,
+    //allocations ever succeeded
+    pub alloc_count: u64,
+}
+
+impl AllocStats {
+    //0 if the free bytes are in one block, close to 1 if they are scattered in small ones
+    pub fn fragmentation(&self) -> f64 {
+        if self.free_bytes == 0 {
+            0.0
+        } else {
+            1.0 - self.largest_free as f64 / self.free_bytes as f64
+        }
+    }
+}
+
+impl Display for AllocStats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "alloced {:#x} bytes in {} blocks, free {:#x} bytes, largest free {:#x}, fragmentation {:.3}, high water {:#x}, {} allocs",
+            self.alloced_bytes,
+            self.live_blocks,
+            self.free_bytes,
+            self.largest_free,
+            self.fragmentation(),
+            self.high_water,
+            self.alloc_count
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LiveBlock {
+    pub info: MemInfo,
+    pub label: Option<String>,
+}
+
+impl Display for LiveBlock {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:#016x} - {:#016x} size {:#x}",
+            self.info.base,
+            self.info.base + self.info.size,
+            self.info.size
+        )?;
+        if let Some(label) = &self.label {
+            write!(f, " {}", label)?;
+        }
+        Ok(())
+    }
+}
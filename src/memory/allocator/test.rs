@@ -4,122 +4,39 @@ use std::sync::mpsc::Sender;
 use std::sync::{mpsc, Arc};
 use std::thread;
 
-#[test]
-fn list_basic() {
-    let list = List::cons(
-        MemInfo { base: 2, size: 0 },
-        &List::cons(
-            MemInfo { base: 4, size: 0 },
-            &List::cons(MemInfo { base: 0, size: 3 }, &List::nil()),
-        ),
-    );
-    assert_eq!(list.cdr().car().unwrap().base, 4);
-    assert_eq!(list.cdr().cdr().car().unwrap().size, 3);
-
-    let append = List::cons(
-        MemInfo { base: 7, size: 0 },
-        &List::cons(MemInfo { base: 5, size: 6 }, &list),
-    );
-    assert_eq!(append.car().unwrap().base, 7);
-    assert_eq!(append.cdr().car().unwrap().base, 5);
-    assert_eq!(append.cdr().cdr().car().unwrap().base, 2);
-    assert_eq!(append.last().car().unwrap().size, 3);
-}
-
-#[test]
-fn list_iter() {
-    let list = List::cons(
-        MemInfo { base: 1, size: 1 },
-        &List::cons(
-            MemInfo { base: 2, size: 2 },
-            &List::cons(MemInfo { base: 3, size: 3 }, &List::nil()),
-        ),
-    );
-    let mut id: u64 = 1;
-    list.iter().for_each(|i| {
-        assert_eq!(i.car().unwrap().size, i.car().unwrap().base);
-        assert_eq!(i.car().unwrap().base, id);
-        id += 1;
-    });
-    assert_eq!(list.iter().count(), 3)
-}
-
-#[test]
-fn list_append() {
-    let list1 = List::cons(
-        MemInfo { base: 1, size: 1 },
-        &List::cons(
-            MemInfo { base: 2, size: 2 },
-            &List::cons(MemInfo { base: 3, size: 3 }, &List::nil()),
-        ),
-    );
-    let list2 = List::cons(
-        MemInfo { base: 4, size: 4 },
-        &List::cons(
-            MemInfo { base: 5, size: 5 },
-            &List::cons(MemInfo { base: 6, size: 6 }, &List::nil()),
-        ),
-    );
-    let mut id: u64 = 1;
-    List::append(&list1, &list2).iter().for_each(|i| {
-        assert_eq!(i.car().unwrap().size, i.car().unwrap().base);
-        assert_eq!(i.car().unwrap().base, id);
-        id += 1;
-    });
-    assert_eq!(List::append(&list1, &list2).iter().count(), 6)
-}
-
-#[test]
-fn list_delete() {
-    let list1 = List::cons(
-        MemInfo { base: 1, size: 1 },
-        &List::cons(
-            MemInfo { base: 2, size: 2 },
-            &List::cons(MemInfo { base: 3, size: 3 }, &List::nil()),
-        ),
-    );
-    let (item, list2) = List::delete(&list1, |item| item.car().unwrap().base == 2);
-    assert_eq!(item.unwrap().car().unwrap().size, 2);
-    assert_eq!(list2.iter().count(), 2)
-}
-
 #[test]
 fn basic_alloc() {
     let allocator = &mut Allocator::new(1, 9);
-    assert_eq!(allocator.alloc(4, 1), Some(MemInfo { base: 1, size: 4 }));
-    assert_eq!(allocator.alloc(2, 4), Some(MemInfo { base: 8, size: 2 }));
-    assert_eq!(allocator.alloc(1, 1), Some(MemInfo { base: 5, size: 1 }));
-    assert_eq!(allocator.alloc(2, 1), Some(MemInfo { base: 6, size: 2 }));
-    assert_eq!(allocator.alloc(1, 1), None);
+    assert_eq!(allocator.alloc(4, 1), Ok(MemInfo { base: 1, size: 4 }));
+    assert_eq!(allocator.alloc(2, 4), Ok(MemInfo { base: 8, size: 2 }));
+    assert_eq!(allocator.alloc(1, 1), Ok(MemInfo { base: 5, size: 1 }));
+    assert_eq!(allocator.alloc(2, 1), Ok(MemInfo { base: 6, size: 2 }));
+    assert_eq!(allocator.alloc(1, 1), Err(AllocError::OutOfMemory(1, 1)));
 }
 
 #[test]
 fn basic_free() {
     let allocator = &mut Allocator::new(1, 9);
-    assert_eq!(allocator.alloc(4, 1), Some(MemInfo { base: 1, size: 4 }));
-    assert_eq!(allocator.alloc(2, 4), Some(MemInfo { base: 8, size: 2 }));
-    assert_eq!(allocator.alloc(1, 1), Some(MemInfo { base: 5, size: 1 }));
-    assert_eq!(allocator.alloc(2, 1), Some(MemInfo { base: 6, size: 2 }));
-    assert_eq!(allocator.alloc(1, 1), None);
-    allocator.free(8);
-    allocator.free(5);
-    allocator.free(1);
-    allocator.free(6);
-    assert!(allocator.free_blocks.iter().count() == 1);
-    assert_eq!(
-        allocator.free_blocks.car(),
-        Some(MemInfo { base: 1, size: 9 })
-    );
-    assert_eq!(allocator.alloced_blocks.car(), None);
+    assert_eq!(allocator.alloc(4, 1), Ok(MemInfo { base: 1, size: 4 }));
+    assert_eq!(allocator.alloc(2, 4), Ok(MemInfo { base: 8, size: 2 }));
+    assert_eq!(allocator.alloc(1, 1), Ok(MemInfo { base: 5, size: 1 }));
+    assert_eq!(allocator.alloc(2, 1), Ok(MemInfo { base: 6, size: 2 }));
+    assert_eq!(allocator.alloc(1, 1), Err(AllocError::OutOfMemory(1, 1)));
+    allocator.free(8).unwrap();
+    allocator.free(5).unwrap();
+    allocator.free(1).unwrap();
+    allocator.free(6).unwrap();
+    assert_eq!(allocator.free_list(), vec![MemInfo { base: 1, size: 9 }]);
+    assert_eq!(allocator.alloced_list(), vec![]);
 }
 
 #[test]
 fn basic_concurrency_alloc() {
     let allocator = Arc::new(LockedAllocator::new(1, 9));
     let (tx, rx) = mpsc::channel();
-    fn do_job<F: Fn(&LockedAllocator) -> Option<MemInfo> + Send + 'static>(
+    fn do_job<F: Fn(&LockedAllocator) -> Result<MemInfo, AllocError> + Send + 'static>(
         job: F,
-        tx: &Sender<Option<MemInfo>>,
+        tx: &Sender<Result<MemInfo, AllocError>>,
         la: &Arc<LockedAllocator>,
     ) {
         let done = mpsc::Sender::clone(tx);
@@ -140,13 +57,13 @@ fn basic_concurrency_alloc() {
         addrs.push(rx.recv().unwrap());
         println!("done!")
     }
-    assert_eq!(allocator.alloc(1, 1), None);
+    assert_eq!(allocator.alloc(1, 1), Err(AllocError::OutOfMemory(1, 1)));
 
     let mut handles = vec![];
     for block in addrs {
         let la = Arc::clone(&allocator);
         let handle = thread::spawn(move || {
-            la.free(block.unwrap().base);
+            la.free(block.unwrap().base).unwrap();
         });
         handles.push(handle);
     }
@@ -155,8 +72,725 @@ fn basic_concurrency_alloc() {
     }
     {
         let inner = allocator.inner.lock().unwrap();
-        assert!(inner.free_blocks.iter().count() == 1);
-        assert_eq!(inner.free_blocks.car(), Some(MemInfo { base: 1, size: 9 }));
-        assert_eq!(inner.alloced_blocks.car(), None);
+        assert_eq!(inner.free_list(), vec![MemInfo { base: 1, size: 9 }]);
+        assert_eq!(inner.alloced_list(), vec![]);
     }
 }
+
+//the cons-list allocator: first fit in address order, split and coalesce
+struct Reference {
+    free: Vec<MemInfo>,
+    alloced: Vec<MemInfo>,
+}
+
+impl Reference {
+    fn alloc(&mut self, size: u64, align: u64) -> Option<MemInfo> {
+        let i = self
+            .free
+            .iter()
+            .position(|info| info.size >= size + (align_up(info.base, align) - info.base))?;
+        let info = self.free.remove(i);
+        let base = align_up(info.base, align);
+        if info.base + info.size != base + size {
+            self.free.insert(
+                i,
+                MemInfo {
+                    base: base + size,
+                    size: info.base + info.size - base - size,
+                },
+            )
+        }
+        if base != info.base {
+            self.free.insert(
+                i,
+                MemInfo {
+                    base: info.base,
+                    size: base - info.base,
+                },
+            )
+        }
+        self.alloced.push(MemInfo { base, size });
+        Some(MemInfo { base, size })
+    }
+
+    fn free(&mut self, addr: u64) {
+        let i = self
+            .alloced
+            .iter()
+            .position(|info| info.base == addr)
+            .unwrap();
+        let mut info = self.alloced.remove(i);
+        let i = self
+            .free
+            .iter()
+            .position(|f| f.base > addr)
+            .unwrap_or(self.free.len());
+        if i < self.free.len() && info.base + info.size == self.free[i].base {
+            info.size += self.free.remove(i).size
+        }
+        if i > 0 && self.free[i - 1].base + self.free[i - 1].size == info.base {
+            self.free[i - 1].size += info.size
+        } else {
+            self.free.insert(i, info)
+        }
+    }
+}
+
+#[test]
+fn first_fit_alloc() {
+    let allocator = &mut Allocator::new(0, 0x100);
+    let a = allocator.alloc(0x10, 1).unwrap();
+    let b = allocator.alloc(0x40, 1).unwrap();
+    let c = allocator.alloc(0x10, 1).unwrap();
+    allocator.free(b.base).unwrap();
+    //the lowest addressed block which fits wins over a better one
+    assert_eq!(
+        allocator.alloc(0x8, 1),
+        Ok(MemInfo {
+            base: 0x10,
+            size: 0x8
+        })
+    );
+    //alignment padding is kept free
+    assert_eq!(
+        allocator.alloc(0x10, 0x20),
+        Ok(MemInfo {
+            base: 0x20,
+            size: 0x10
+        })
+    );
+    assert_eq!(
+        allocator.free_list(),
+        vec![
+            MemInfo {
+                base: 0x18,
+                size: 0x8
+            },
+            MemInfo {
+                base: 0x30,
+                size: 0x20
+            },
+            MemInfo {
+                base: 0x60,
+                size: 0xa0
+            }
+        ]
+    );
+    assert_eq!(allocator.alloc(0x28, 1).unwrap().base, 0x60);
+    allocator.free(a.base).unwrap();
+    allocator.free(c.base).unwrap();
+    allocator.free(0x10).unwrap();
+    allocator.free(0x20).unwrap();
+    allocator.free(0x60).unwrap();
+    assert_eq!(
+        allocator.free_list(),
+        vec![MemInfo {
+            base: 0,
+            size: 0x100
+        }]
+    );
+}
+
+#[test]
+fn invalid_free() {
+    let allocator = &mut Allocator::new(0, 0x100);
+    allocator.alloc(0x10, 1).unwrap();
+    assert_eq!(allocator.free(8), Err(AllocError::InvalidFree(8)));
+    assert_eq!(allocator.alloced_list().len(), 1);
+}
+
+#[test]
+fn double_free() {
+    let allocator = &mut Allocator::new(0, 0x100);
+    let a = allocator.alloc(0x10, 1).unwrap();
+    allocator.free(a.base).unwrap();
+    assert_eq!(allocator.free(a.base), Err(AllocError::DoubleFree(0)));
+    //alloced again, then freed twice again
+    assert_eq!(allocator.alloc(0x20, 1).unwrap().base, 0);
+    allocator.free(0).unwrap();
+    assert_eq!(allocator.free(0), Err(AllocError::DoubleFree(0)));
+    assert_eq!(allocator.free(0x10), Err(AllocError::InvalidFree(0x10)));
+    assert_eq!(allocator.stats().alloced_bytes, 0);
+}
+
+#[test]
+fn random_alloc_free() {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let allocator = &mut Allocator::new(0x1000, 0x10_0000);
+    let reference = &mut Reference {
+        free: vec![MemInfo {
+            base: 0x1000,
+            size: 0x10_0000,
+        }],
+        alloced: vec![],
+    };
+    let mut alloced = vec![];
+    for _ in 0..10000 {
+        if alloced.is_empty() || rng.gen_range(0, 3) != 0 {
+            let size = rng.gen_range(1, 0x1000);
+            let align = 1 << rng.gen_range(0, 10);
+            let info = allocator.alloc(size, align).ok();
+            assert_eq!(info, reference.alloc(size, align));
+            if let Some(info) = info {
+                alloced.push(info.base)
+            }
+        } else {
+            let addr = alloced.swap_remove(rng.gen_range(0, alloced.len()));
+            allocator.free(addr).unwrap();
+            reference.free(addr);
+        }
+        assert_eq!(allocator.free_list(), reference.free);
+    }
+    let mut list = allocator.alloced_list();
+    list.sort_by_key(|info| info.base);
+    reference.alloced.sort_by_key(|info| info.base);
+    assert_eq!(list, reference.alloced);
+}
+
+#[test]
+fn restore_state() {
+    let allocator = &mut Allocator::new(0, 0x100);
+    allocator.alloc(0x10, 1).unwrap();
+    let free = vec![
+        MemInfo {
+            base: 0x20,
+            size: 0x20,
+        },
+        MemInfo {
+            base: 0x80,
+            size: 0x80,
+        },
+    ];
+    let alloced = vec![
+        MemInfo {
+            base: 0,
+            size: 0x20,
+        },
+        MemInfo {
+            base: 0x40,
+            size: 0x40,
+        },
+    ];
+    allocator.restore_labeled(
+        free.clone(),
+        alloced.clone(),
+        vec![(0x40, "buffer".to_string())],
+    );
+    assert_eq!(allocator.free_list(), free);
+    assert_eq!(allocator.alloced_list(), alloced);
+    assert_eq!(allocator.live_block(0).unwrap().label, None);
+    assert_eq!(
+        allocator.live_block(0x40).unwrap().label.as_deref(),
+        Some("buffer")
+    );
+    assert_eq!(allocator.alloc(0x30, 0x40).unwrap().base, 0x80);
+    allocator.free(0x40).unwrap();
+    assert_eq!(
+        allocator.free_list(),
+        vec![
+            MemInfo {
+                base: 0x20,
+                size: 0x60
+            },
+            MemInfo {
+                base: 0xb0,
+                size: 0x50
+            }
+        ]
+    );
+}
+
+#[test]
+fn best_fit_alloc() {
+    let allocator = &mut Allocator::with_policy(0, 0x100, Policy::BestFit);
+    let a = allocator.alloc(0x40, 1).unwrap();
+    allocator.alloc(0x10, 1).unwrap();
+    let c = allocator.alloc(0x10, 1).unwrap();
+    allocator.alloc(0x10, 1).unwrap();
+    allocator.free(a.base).unwrap();
+    allocator.free(c.base).unwrap();
+    //the smallest block which fits wins over a lower one
+    assert_eq!(allocator.alloc(0x8, 1).unwrap().base, 0x50);
+    //the lower one of the same size
+    allocator.alloc(0x8, 1).unwrap();
+    assert_eq!(allocator.alloc(0x20, 1).unwrap().base, 0);
+    //alignment is taken into account
+    assert_eq!(allocator.alloc(0x10, 0x40).unwrap().base, 0x80);
+}
+
+#[test]
+fn next_fit_alloc() {
+    let allocator = &mut Allocator::with_policy(0, 0x100, Policy::NextFit);
+    let a = allocator.alloc(0x10, 1).unwrap();
+    let b = allocator.alloc(0x10, 1).unwrap();
+    allocator.free(a.base).unwrap();
+    //searches from the end of the last allocation
+    assert_eq!(allocator.alloc(0x10, 1).unwrap().base, 0x20);
+    assert_eq!(allocator.alloc(0xc0, 1).unwrap().base, 0x30);
+    assert_eq!(allocator.alloc(0x10, 1).unwrap().base, 0xf0);
+    //then wraps around
+    assert_eq!(allocator.alloc(0x10, 1).unwrap().base, 0);
+    allocator.free(b.base).unwrap();
+    assert_eq!(allocator.alloc(0x10, 1).unwrap().base, 0x10);
+    assert_eq!(
+        allocator.alloc(0x1, 1),
+        Err(AllocError::OutOfMemory(0x1, 1))
+    );
+}
+
+#[test]
+fn buddy_alloc() {
+    let allocator = &mut Allocator::with_policy(0x1000, 0x100, Policy::Buddy);
+    let a = allocator.alloc(0x10, 1).unwrap();
+    assert_eq!(
+        a,
+        MemInfo {
+            base: 0x1000,
+            size: 0x10
+        }
+    );
+    //rounded up to a power of 2
+    let b = allocator.alloc(0x11, 1).unwrap();
+    assert_eq!(b.base, 0x1020);
+    //split from a larger one
+    assert_eq!(allocator.alloc(0x8, 0x20).unwrap().base, 0x1040);
+    assert_eq!(
+        allocator.alloced_list(),
+        vec![
+            MemInfo {
+                base: 0x1000,
+                size: 0x10
+            },
+            MemInfo {
+                base: 0x1020,
+                size: 0x20
+            },
+            MemInfo {
+                base: 0x1040,
+                size: 0x20
+            }
+        ]
+    );
+    allocator.free(0x1040).unwrap();
+    allocator.free(a.base).unwrap();
+    allocator.free(b.base).unwrap();
+    //buddies merge back
+    assert_eq!(
+        allocator.free_list(),
+        vec![MemInfo {
+            base: 0x1000,
+            size: 0x100
+        }]
+    );
+    assert_eq!(
+        allocator.alloc(0x101, 1),
+        Err(AllocError::OutOfMemory(0x101, 1))
+    );
+    //alignments above the one of the base
+    assert_eq!(
+        allocator.alloc(0x10, 0x2000),
+        Err(AllocError::OutOfMemory(0x10, 0x2000))
+    );
+}
+
+#[test]
+fn buddy_unaligned_size() {
+    let allocator = &mut Allocator::with_policy(0, 0x70, Policy::Buddy);
+    assert_eq!(
+        allocator.free_list(),
+        vec![
+            MemInfo {
+                base: 0,
+                size: 0x40
+            },
+            MemInfo {
+                base: 0x40,
+                size: 0x20
+            },
+            MemInfo {
+                base: 0x60,
+                size: 0x10
+            }
+        ]
+    );
+    assert_eq!(allocator.alloc(0x8, 1).unwrap().base, 0x60);
+    assert_eq!(allocator.alloc(0x40, 1).unwrap().base, 0);
+    assert_eq!(
+        allocator.alloc(0x40, 1),
+        Err(AllocError::OutOfMemory(0x40, 1))
+    );
+}
+
+#[test]
+fn slab_alloc() {
+    let allocator = &mut Allocator::with_policy(0, 0x10000, Policy::Slab);
+    //objects of one class are packed in a slab
+    let a = allocator.alloc(0x5, 1).unwrap();
+    let b = allocator.alloc(0x8, 1).unwrap();
+    assert_eq!(a, MemInfo { base: 0, size: 0x5 });
+    assert_eq!(b.base, 0x8);
+    //another class takes another slab
+    assert_eq!(allocator.alloc(0x20, 1).unwrap().base, 0x1000);
+    //large requests fall back
+    assert_eq!(allocator.alloc(0x800, 0x100).unwrap().base, 0x2000);
+    allocator.free(a.base).unwrap();
+    assert_eq!(allocator.alloc(0x3, 1).unwrap().base, 0);
+    allocator.free(0x2000).unwrap();
+    assert_eq!(allocator.alloc(0x1000, 1).unwrap().base, 0x2000);
+
+    //objects are blocks of their own after restore, memory is whole again once they are freed
+    let (free, alloced) = (allocator.free_list(), allocator.alloced_list());
+    allocator.restore(free, alloced.clone());
+    for info in alloced {
+        allocator.free(info.base).unwrap();
+    }
+    assert_eq!(allocator.alloc(0x10000, 1).unwrap().base, 0);
+
+    //small requests are served by the fallback when a whole slab can not be carved
+    let allocator = &mut Allocator::with_policy(0, 0x1000, Policy::Slab);
+    assert_eq!(allocator.alloc(0x800, 1).unwrap().base, 0);
+    let c = allocator.alloc(0x10, 1).unwrap();
+    assert_eq!(c.base, 0x800);
+    allocator.free(c.base).unwrap();
+    allocator.free(0).unwrap();
+    assert_eq!(allocator.alloc(0x1000, 1).unwrap().base, 0);
+}
+
+#[test]
+fn bump_alloc() {
+    let allocator = &mut Allocator::with_policy(0, 0x100, Policy::Bump);
+    let a = allocator.alloc(0x10, 1).unwrap();
+    let b = allocator.alloc(0x10, 0x40).unwrap();
+    assert_eq!(b.base, 0x40);
+    allocator.free(a.base).unwrap();
+    //freed memory is not reused while blocks are alive
+    assert_eq!(allocator.alloc(0x10, 1).unwrap().base, 0x50);
+    assert_eq!(
+        allocator.free_list(),
+        vec![MemInfo {
+            base: 0x60,
+            size: 0xa0
+        }]
+    );
+    assert_eq!(
+        allocator.alloc(0xa1, 1),
+        Err(AllocError::OutOfMemory(0xa1, 1))
+    );
+    allocator.free(b.base).unwrap();
+    allocator.free(0x50).unwrap();
+    assert_eq!(allocator.alloc(0x100, 1).unwrap().base, 0);
+}
+
+const POLICIES: [Policy; 6] = [
+    Policy::FirstFit,
+    Policy::BestFit,
+    Policy::NextFit,
+    Policy::Buddy,
+    Policy::Slab,
+    Policy::Bump,
+];
+
+#[test]
+fn policy_random_alloc_free() {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    for policy in POLICIES.iter() {
+        let allocator = &mut Allocator::with_policy(0x10_0000, 0x10_0000, *policy);
+        let mut alloced: Vec<MemInfo> = vec![];
+        for _ in 0..2000 {
+            if alloced.is_empty() || rng.gen_range(0, 3) != 0 {
+                let size = rng.gen_range(1, 0x2000);
+                let align = 1 << rng.gen_range(0, 10);
+                if let Ok(info) = allocator.alloc(size, align) {
+                    assert_eq!(info.size, size);
+                    assert_eq!(info.base & (align - 1), 0);
+                    assert!(info.base + info.size <= 0x20_0000);
+                    assert!(
+                        alloced.iter().all(
+                            |a| a.base + a.size <= info.base || info.base + info.size <= a.base
+                        ),
+                        "{:?} overlapped by {:?}",
+                        policy,
+                        info
+                    );
+                    alloced.push(info)
+                }
+            } else {
+                let info = alloced.swap_remove(rng.gen_range(0, alloced.len()));
+                allocator.free(info.base).unwrap();
+            }
+        }
+        //free and live blocks never overlap
+        let mut blocks = allocator.free_list();
+        blocks.append(&mut allocator.alloced_list());
+        blocks.sort_by_key(|info| info.base);
+        for pair in blocks.windows(2) {
+            assert!(pair[0].base + pair[0].size <= pair[1].base, "{:?}", policy);
+        }
+        //save and restore
+        let (free, live) = (allocator.free_list(), allocator.alloced_list());
+        let restored = &mut Allocator::with_policy(0x10_0000, 0x10_0000, *policy);
+        restored.restore(free.clone(), live.clone());
+        //free objects of slabs are given back merged
+        let mut merged: Vec<MemInfo> = vec![];
+        for info in free {
+            match merged.last_mut() {
+                Some(last) if *policy == Policy::Slab && last.base + last.size == info.base => {
+                    last.size += info.size
+                }
+                _ => merged.push(info),
+            }
+        }
+        assert_eq!(restored.free_list(), merged, "{:?}", policy);
+        assert_eq!(restored.alloced_list(), live, "{:?}", policy);
+        for info in alloced.iter() {
+            restored.free(info.base).unwrap()
+        }
+        assert!(restored.alloced_list().is_empty());
+    }
+}
+
+#[test]
+fn alloc_stats() {
+    let allocator = &mut Allocator::new(0, 0x100);
+    let a = allocator.alloc(0x10, 1).unwrap();
+    let b = allocator.alloc(0x20, 1).unwrap();
+    allocator.alloc(0x30, 1).unwrap();
+    allocator.free(b.base).unwrap();
+    let stats = allocator.stats();
+    assert_eq!(
+        stats,
+        AllocStats {
+            alloced_bytes: 0x40,
+            free_bytes: 0xc0,
+            largest_free: 0xa0,
+            high_water: 0x60,
+            live_blocks: 2,
+            alloc_count: 3,
+        }
+    );
+    assert!((stats.fragmentation() - 1.0 / 6.0).abs() < 1e-9);
+    allocator.free(a.base).unwrap();
+    assert_eq!(allocator.stats().high_water, 0x60);
+    assert_eq!(
+        allocator.alloc(0x100, 1),
+        Err(AllocError::OutOfMemory(0x100, 1))
+    );
+    assert_eq!(allocator.stats().alloc_count, 3);
+}
+
+#[test]
+fn live_blocks_dump() {
+    let allocator = &LockedAllocator::with_policy(0x1000, 0x100, Policy::Buddy);
+    let a = allocator.alloc_labeled(0x10, 1, "desc ring").unwrap();
+    allocator.alloc(0x11, 1).unwrap();
+    let blocks = allocator.live_blocks();
+    //sizes requested, not rounded by the policy
+    assert_eq!(
+        blocks[1].info,
+        MemInfo {
+            base: 0x1020,
+            size: 0x11
+        }
+    );
+    assert_eq!(blocks[1].label, None);
+    assert_eq!(
+        blocks[0].to_string(),
+        "0x00000000001000 - 0x00000000001010 size 0x10 desc ring"
+    );
+    allocator.free(a.base).unwrap();
+    assert_eq!(allocator.live_blocks().len(), 1);
+    assert_eq!(allocator.stats().alloced_bytes, 0x11);
+}
+
+#[test]
+fn reserve_range() {
+    for policy in [
+        Policy::FirstFit,
+        Policy::BestFit,
+        Policy::NextFit,
+        Policy::Slab,
+    ]
+    .iter()
+    {
+        let allocator = &mut Allocator::with_policy(0, 0x10000, *policy);
+        assert_eq!(
+            allocator.reserve(0x2000, 0x1000),
+            Ok(MemInfo {
+                base: 0x2000,
+                size: 0x1000
+            })
+        );
+        assert_eq!(
+            allocator.reserve(0x2800, 0x10),
+            Err(AllocError::NotFree(0x2800, 0x10))
+        );
+        assert_eq!(
+            allocator.reserve(0xff00, 0x200),
+            Err(AllocError::NotFree(0xff00, 0x200))
+        );
+        //dynamic allocations go around it
+        let big = allocator.alloc(0x3000, 0x1000).unwrap();
+        assert_eq!(big.base, 0x3000, "{:?}", policy);
+        allocator.free(0x2000).unwrap();
+        assert_eq!(allocator.reserve(0x2800, 0x10).unwrap().base, 0x2800);
+    }
+    let allocator = &mut Allocator::with_policy(0, 0x100, Policy::Bump);
+    allocator.alloc(0x10, 1).unwrap();
+    assert_eq!(
+        allocator.reserve(0x8, 0x10),
+        Err(AllocError::NotFree(0x8, 0x10))
+    );
+    assert_eq!(allocator.reserve(0x80, 0x10).unwrap().base, 0x80);
+    assert_eq!(allocator.alloc(0x10, 1).unwrap().base, 0x90);
+    //not supported
+    let allocator = &mut Allocator::with_policy(0, 0x100, Policy::Buddy);
+    assert_eq!(
+        allocator.reserve(0, 0x10),
+        Err(AllocError::NotFree(0, 0x10))
+    );
+}
+
+#[test]
+fn realloc_block() {
+    let allocator = &mut Allocator::new(0, 0x100);
+    let a = allocator.alloc_labeled(0x10, 1, "a").unwrap();
+    let b = allocator.alloc(0x10, 1).unwrap();
+    allocator.alloc(0x10, 1).unwrap();
+    allocator.free(b.base).unwrap();
+    //grows in place into the free neighbour
+    assert_eq!(
+        allocator.realloc(a.base, 0x18, 1),
+        Ok(MemInfo {
+            base: 0,
+            size: 0x18
+        })
+    );
+    assert_eq!(
+        allocator.free_list()[0],
+        MemInfo {
+            base: 0x18,
+            size: 0x8
+        }
+    );
+    assert_eq!(allocator.stats().alloced_bytes, 0x28);
+    //moves if it does not fit
+    let moved = allocator.realloc(a.base, 0x40, 1).unwrap();
+    assert_eq!(moved.base, 0x30);
+    assert_eq!(
+        allocator.live_block(0x30).unwrap().label.as_deref(),
+        Some("a")
+    );
+    //both blocks are alive while moving
+    assert_eq!(allocator.stats().high_water, 0x68);
+    //shrinks in place
+    assert_eq!(allocator.realloc(0x30, 0x20, 1).unwrap().base, 0x30);
+    assert_eq!(
+        allocator.free_list()[1],
+        MemInfo {
+            base: 0x50,
+            size: 0xb0
+        }
+    );
+    //the old block is kept if nothing fits
+    assert_eq!(
+        allocator.realloc(0x30, 0x100, 1),
+        Err(AllocError::OutOfMemory(0x100, 1))
+    );
+    assert_eq!(allocator.live_block(0x30).unwrap().info.size, 0x20);
+    //misaligned blocks move
+    assert_eq!(allocator.realloc(0x30, 0x20, 0x40).unwrap().base, 0);
+}
+
+#[test]
+fn invalid_realloc() {
+    let allocator = &mut Allocator::new(0, 0x100);
+    allocator.alloc(0x10, 1).unwrap();
+    assert_eq!(
+        allocator.realloc(0x10, 0x20, 1),
+        Err(AllocError::InvalidRealloc(0x10))
+    );
+}
+
+#[test]
+fn free_sub_range() {
+    let allocator = &mut Allocator::new(0, 0x100);
+    allocator.alloc_labeled(0x40, 1, "boot").unwrap();
+    allocator.free_range(0x10, 0x10).unwrap();
+    assert_eq!(
+        allocator.free_list(),
+        vec![
+            MemInfo {
+                base: 0x10,
+                size: 0x10
+            },
+            MemInfo {
+                base: 0x40,
+                size: 0xc0
+            }
+        ]
+    );
+    let live = allocator.live_blocks();
+    assert_eq!(live.len(), 2);
+    assert_eq!(
+        live[1].info,
+        MemInfo {
+            base: 0x20,
+            size: 0x20
+        }
+    );
+    assert_eq!(live[1].label.as_deref(), Some("boot"));
+    //the tail coalesces with the free block above
+    allocator.free_range(0x30, 0x10).unwrap();
+    assert_eq!(
+        allocator.free_list()[1],
+        MemInfo {
+            base: 0x30,
+            size: 0xd0
+        }
+    );
+    allocator.free(0).unwrap();
+    allocator.free(0x20).unwrap();
+    assert_eq!(
+        allocator.free_list(),
+        vec![MemInfo {
+            base: 0,
+            size: 0x100
+        }]
+    );
+    assert_eq!(allocator.stats().alloced_bytes, 0);
+}
+
+#[test]
+fn free_range_out_of_block() {
+    let allocator = &mut Allocator::new(0, 0x100);
+    allocator.alloc(0x20, 1).unwrap();
+    assert_eq!(
+        allocator.free_range(0x10, 0x20),
+        Err(AllocError::InvalidFree(0x10))
+    );
+    let buddy = &mut Allocator::with_policy(0, 0x100, Policy::Buddy);
+    buddy.alloc(0x20, 1).unwrap();
+    assert_eq!(
+        buddy.free_range(0x10, 0x10),
+        Err(AllocError::PartialFree(0))
+    );
+    assert_eq!(buddy.live_block(0).unwrap().info.size, 0x20);
+}
+
+#[test]
+fn free_empty_range() {
+    let allocator = &mut Allocator::new(0, 0x1000);
+    let block = allocator.alloc(0x100, 1).unwrap();
+    assert_eq!(
+        allocator.free_range(block.base + 0x10, 0),
+        Err(AllocError::InvalidFree(block.base + 0x10))
+    );
+    assert_eq!(
+        allocator.free_range(block.base + 0x10, u64::MAX),
+        Err(AllocError::InvalidFree(block.base + 0x10))
+    );
+    assert_eq!(allocator.free_list().len(), 1);
+    allocator.alloc(0x20, 1).unwrap();
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::ops::BitOr;
+
+//R/W/X permissions of a mapping
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Perm(u8);
+
+impl Perm {
+    pub const NONE: Perm = Perm(0);
+    pub const R: Perm = Perm(1);
+    pub const W: Perm = Perm(2);
+    pub const X: Perm = Perm(4);
+    pub const RW: Perm = Perm(3);
+    pub const RX: Perm = Perm(5);
+    pub const RWX: Perm = Perm(7);
+
+    pub fn from_bits(bits: u8) -> Perm {
+        Perm(bits & Perm::RWX.0)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn contains(&self, perm: Perm) -> bool {
+        self.0 & perm.0 == perm.0
+    }
+}
+
+impl BitOr for Perm {
+    type Output = Perm;
+    fn bitor(self, rhs: Perm) -> Perm {
+        Perm(self.0 | rhs.0)
+    }
+}
+
+impl Display for Perm {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            if self.contains(Perm::R) { "r" } else { "-" },
+            if self.contains(Perm::W) { "w" } else { "-" },
+            if self.contains(Perm::X) { "x" } else { "-" },
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MemType {
+    Cacheable,
+    Uncacheable,
+    //side effects on access, instructions can not be fetched from it
+    Device,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Attr {
+    pub perm: Perm,
+    pub mem_type: MemType,
+}
+
+impl Attr {
+    pub fn new(perm: Perm, mem_type: MemType) -> Attr {
+        Attr { perm, mem_type }
+    }
+
+    pub fn allows(&self, kind: AccessType) -> bool {
+        match kind {
+            AccessType::Read => self.perm.contains(Perm::R),
+            AccessType::Write => self.perm.contains(Perm::W),
+            AccessType::Fetch => self.perm.contains(Perm::X) && self.mem_type != MemType::Device,
+        }
+    }
+}
+
+//mappings without explicit attributes are normal memory with all permissions
+impl Default for Attr {
+    fn default() -> Attr {
+        Attr::new(Perm::RWX, MemType::Cacheable)
+    }
+}
+
+impl Display for Attr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{:?}", self.perm, self.mem_type)
+    }
+}
+
+//kinds of permission-checked accesses
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccessType {
+    Read,
+    Write,
+    Fetch,
+}
@@ -1,6 +1,9 @@
 pub mod allocator;
+pub mod attr;
 pub mod region;
 
+pub(crate) mod model;
+
 pub mod prelude;
 
 #[cfg(test)]
@@ -0,0 +1,260 @@
+//memory models and byte-order-agnostic access code shared by the Rc based Region in memory::region
+//and the Arc based one in sync, generic over the interior mutability each flavor uses
+use super::region::{BytesAccess, U16Access, U32Access, U64Access, U8Access};
+use super::MemInfo;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::RwLock;
+
+pub(crate) trait ModelCell<T> {
+    fn new(v: T) -> Self;
+    fn with<R, F: FnOnce(&T) -> R>(&self, f: F) -> R;
+    fn with_mut<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R;
+}
+
+impl<T> ModelCell<T> for RefCell<T> {
+    fn new(v: T) -> Self {
+        RefCell::new(v)
+    }
+
+    fn with<R, F: FnOnce(&T) -> R>(&self, f: F) -> R {
+        f(&self.borrow())
+    }
+
+    fn with_mut<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        f(&mut self.borrow_mut())
+    }
+}
+
+//readers share the lock, so harts only reading memory never block each other
+impl<T> ModelCell<T> for RwLock<T> {
+    fn new(v: T) -> Self {
+        RwLock::new(v)
+    }
+
+    fn with<R, F: FnOnce(&T) -> R>(&self, f: F) -> R {
+        f(&self.read().unwrap())
+    }
+
+    fn with_mut<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        f(&mut self.write().unwrap())
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ModelHasher(u64);
+
+impl Hasher for ModelHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, _: &[u8]) {
+        panic!("not implement")
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.0 = i
+    }
+}
+
+pub(crate) const LAZY_PAGE_SHIFT: u64 = 12;
+const LAZY_PAGE_SIZE: usize = 1 << LAZY_PAGE_SHIFT;
+pub(crate) const LAZY_PAGE_MASK: u64 = LAZY_PAGE_SIZE as u64 - 1;
+
+type LazyPage = Box<[u8; LAZY_PAGE_SIZE]>;
+
+pub(crate) type LazyPages = HashMap<u64, LazyPage, BuildHasherDefault<ModelHasher>>;
+
+//pages are allocated on first write, untouched memory reads as zero
+pub(crate) struct LazyModel<C> {
+    pub(crate) inner: C,
+}
+
+impl<C: ModelCell<LazyPages>> LazyModel<C> {
+    pub(crate) fn new() -> LazyModel<C> {
+        LazyModel {
+            inner: C::new(HashMap::default()),
+        }
+    }
+
+    //split [addr, addr + len) into (page number, offset in page, offset in data, len) chunks,
+    //ranges are checked by the region, the chunks stop at the end of the address space anyway
+    fn chunks(addr: u64, len: usize) -> impl Iterator<Item = (u64, usize, usize, usize)> {
+        let mut done: usize = 0;
+        std::iter::from_fn(move || {
+            if done >= len {
+                return None;
+            }
+            let cur = addr.checked_add(done as u64)?;
+            let page_offset = (cur & LAZY_PAGE_MASK) as usize;
+            let size = std::cmp::min(LAZY_PAGE_SIZE - page_offset, len - done);
+            let chunk = (cur >> LAZY_PAGE_SHIFT, page_offset, done, size);
+            done += size;
+            Some(chunk)
+        })
+    }
+}
+
+impl<C: ModelCell<LazyPages>> U8Access for LazyModel<C> {
+    fn write(&self, addr: &u64, data: u8) {
+        self.inner.with_mut(|pages| {
+            let page = pages
+                .entry(*addr >> LAZY_PAGE_SHIFT)
+                .or_insert_with(|| Box::new([0; LAZY_PAGE_SIZE]));
+            page[(*addr & LAZY_PAGE_MASK) as usize] = data;
+        })
+    }
+
+    fn read(&self, addr: &u64) -> u8 {
+        self.inner.with(|pages| {
+            if let Some(page) = pages.get(&(*addr >> LAZY_PAGE_SHIFT)) {
+                page[(*addr & LAZY_PAGE_MASK) as usize]
+            } else {
+                0
+            }
+        })
+    }
+}
+
+impl<C: ModelCell<LazyPages>> BytesAccess for LazyModel<C> {
+    fn write(&self, addr: &u64, data: &[u8]) -> Result<usize, String> {
+        self.inner.with_mut(|pages| {
+            for (page, page_offset, offset, size) in Self::chunks(*addr, data.len()) {
+                let p = pages
+                    .entry(page)
+                    .or_insert_with(|| Box::new([0; LAZY_PAGE_SIZE]));
+                p[page_offset..page_offset + size].copy_from_slice(&data[offset..offset + size]);
+            }
+        });
+        Ok(data.len())
+    }
+
+    fn read(&self, addr: &u64, data: &mut [u8]) -> Result<usize, String> {
+        self.inner.with(|pages| {
+            for (page, page_offset, offset, size) in Self::chunks(*addr, data.len()) {
+                if let Some(p) = pages.get(&page) {
+                    data[offset..offset + size].copy_from_slice(&p[page_offset..page_offset + size])
+                } else {
+                    data[offset..offset + size].fill(0)
+                }
+            }
+        });
+        Ok(data.len())
+    }
+}
+
+impl<C: ModelCell<LazyPages>> U16Access for LazyModel<C> {}
+
+impl<C: ModelCell<LazyPages>> U32Access for LazyModel<C> {}
+
+impl<C: ModelCell<LazyPages>> U64Access for LazyModel<C> {}
+
+pub(crate) struct Model<C> {
+    pub(crate) info: MemInfo,
+    pub(crate) inner: C,
+}
+
+impl<C: ModelCell<Box<[u8]>>> Model<C> {
+    pub(crate) fn new(info: MemInfo) -> Model<C> {
+        let size = info.size;
+        if std::mem::size_of::<u64>() != std::mem::size_of::<usize>() {
+            assert!(size < 0x1_0000_0000, "global heap alloc max size can not exceed 4g when usize is 4, please use lazy_alloc!")
+        }
+        Model {
+            info,
+            inner: C::new(vec![0; size as usize].into_boxed_slice()),
+        }
+    }
+}
+
+impl<C: ModelCell<Box<[u8]>>> U8Access for Model<C> {
+    fn write(&self, addr: &u64, data: u8) {
+        let offset = (*addr - self.info.base) as usize;
+        self.inner.with_mut(|m| m[offset] = data)
+    }
+
+    fn read(&self, addr: &u64) -> u8 {
+        let offset = (*addr - self.info.base) as usize;
+        self.inner.with(|m| m[offset])
+    }
+}
+
+impl<C: ModelCell<Box<[u8]>>> BytesAccess for Model<C> {
+    fn write(&self, addr: &u64, data: &[u8]) -> Result<usize, String> {
+        let offset = (*addr - self.info.base) as usize;
+        self.inner
+            .with_mut(|m| m[offset..offset + data.len()].copy_from_slice(data));
+        Ok(data.len())
+    }
+
+    fn read(&self, addr: &u64, data: &mut [u8]) -> Result<usize, String> {
+        let offset = (*addr - self.info.base) as usize;
+        self.inner
+            .with(|m| data.copy_from_slice(&m[offset..offset + data.len()]));
+        Ok(data.len())
+    }
+}
+
+impl<C: ModelCell<Box<[u8]>>> U16Access for Model<C> {}
+
+impl<C: ModelCell<Box<[u8]>>> U32Access for Model<C> {}
+
+impl<C: ModelCell<Box<[u8]>>> U64Access for Model<C> {}
+
+//Memory::$try_write and Memory::$try_read of a flavor, on top of its memory_access!.
+//IO devices decide alignment by themselves, models check it and the others forward the access
+//with $write and $read
+macro_rules! memory_sized {
+    ($try_write:ident, $try_read:ident, $write:ident, $read:ident, $access:ident, $t:ty, $size:expr) => {
+        impl Memory {
+            fn $try_write(&self, addr: &u64, data: $t) -> Result<(), AccessError> {
+                memory_access!(self,
+                    io => {
+                        $access::write(io, addr, data);
+                        Ok(())
+                    },
+                    model => {
+                        check_align(addr, $size)?;
+                        $access::write(model, addr, data);
+                        Ok(())
+                    },
+                    $write, addr, data)
+            }
+
+            fn $try_read(&self, addr: &u64) -> Result<$t, AccessError> {
+                memory_access!(self,
+                    io => Ok($access::read(io, addr)),
+                    model => {
+                        check_align(addr, $size)?;
+                        Ok($access::read(model, addr))
+                    },
+                    $read, addr)
+            }
+        }
+    };
+}
+
+pub(crate) use memory_sized;
+
+//Memory::try_write_bytes and Memory::try_read_bytes of a flavor, on top of its memory_access!
+macro_rules! memory_bytes {
+    () => {
+        impl Memory {
+            fn try_write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+                memory_access!(self,
+                    io => BytesAccess::write(io, addr, data).map_err(|e| AccessError::DeviceError(*addr, e)),
+                    model => BytesAccess::write(model, addr, data).map_err(|e| AccessError::DeviceError(*addr, e)),
+                    try_write_bytes, addr, data)
+            }
+
+            fn try_read_bytes(&self, addr: &u64, data: &mut [u8]) -> Result<usize, AccessError> {
+                memory_access!(self,
+                    io => BytesAccess::read(io, addr, data).map_err(|e| AccessError::DeviceError(*addr, e)),
+                    model => BytesAccess::read(model, addr, data).map_err(|e| AccessError::DeviceError(*addr, e)),
+                    try_read_bytes, addr, data)
+            }
+        }
+    };
+}
+
+pub(crate) use memory_bytes;
@@ -1,4 +1,6 @@
+pub use crate::memory::attr::{AccessType, Attr, MemType, Perm};
 pub use crate::memory::region::{
-    BytesAccess, IOAccess, SizedAccess, U16Access, U32Access, U64Access, U8Access,
+    AccessError, AmoOp, AtomicAccess, BytesAccess, Endian, IOAccess, IOSnapshot, SizedAccess, U16Access, U32Access, U64Access, U8Access,
+    UnalignedPolicy, WritePolicy,
 };
 pub use terminus_spaceport_proc_macros::*;
@@ -0,0 +1,308 @@
+use super::*;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AmoOp {
+    Swap,
+    Add,
+    And,
+    Or,
+    Xor,
+    //signed
+    Min,
+    Max,
+    //unsigned
+    MinU,
+    MaxU,
+}
+
+macro_rules! amo_apply {
+    ($name:ident, $t:ty, $st:ty) => {
+        pub fn $name(&self, old: $t, data: $t) -> $t {
+            match self {
+                AmoOp::Swap => data,
+                AmoOp::Add => old.wrapping_add(data),
+                AmoOp::And => old & data,
+                AmoOp::Or => old | data,
+                AmoOp::Xor => old ^ data,
+                AmoOp::Min => std::cmp::min(old as $st, data as $st) as $t,
+                AmoOp::Max => std::cmp::max(old as $st, data as $st) as $t,
+                AmoOp::MinU => std::cmp::min(old, data),
+                AmoOp::MaxU => std::cmp::max(old, data),
+            }
+        }
+    };
+}
+
+impl AmoOp {
+    amo_apply!(apply_u32, u32, i32);
+    amo_apply!(apply_u64, u64, i64);
+
+    //byte-wise ops do not depend on the byte order
+    fn is_bitwise(&self) -> bool {
+        matches!(self, AmoOp::Swap | AmoOp::And | AmoOp::Or | AmoOp::Xor)
+    }
+}
+
+//read-modify-write is atomic because models are only accessed by one thread,
+//IO devices enable it by derive_io(.., Atomic) and may reject by returning Err.
+//all methods return the old value, compare-exchange succeeds if it equals current
+pub trait AtomicAccess: BytesAccess {
+    fn amo_u32(&self, addr: &u64, op: AmoOp, data: u32) -> Result<u32, String> {
+        let mut bytes = [0u8; 4];
+        BytesAccess::read(self, addr, &mut bytes)?;
+        let old = u32::from_le_bytes(bytes);
+        BytesAccess::write(self, addr, &op.apply_u32(old, data).to_le_bytes())?;
+        Ok(old)
+    }
+
+    fn amo_u64(&self, addr: &u64, op: AmoOp, data: u64) -> Result<u64, String> {
+        let mut bytes = [0u8; 8];
+        BytesAccess::read(self, addr, &mut bytes)?;
+        let old = u64::from_le_bytes(bytes);
+        BytesAccess::write(self, addr, &op.apply_u64(old, data).to_le_bytes())?;
+        Ok(old)
+    }
+
+    fn compare_exchange_u32(&self, addr: &u64, current: u32, new: u32) -> Result<u32, String> {
+        let mut bytes = [0u8; 4];
+        BytesAccess::read(self, addr, &mut bytes)?;
+        let old = u32::from_le_bytes(bytes);
+        if old == current {
+            BytesAccess::write(self, addr, &new.to_le_bytes())?;
+        }
+        Ok(old)
+    }
+
+    fn compare_exchange_u64(&self, addr: &u64, current: u64, new: u64) -> Result<u64, String> {
+        let mut bytes = [0u8; 8];
+        BytesAccess::read(self, addr, &mut bytes)?;
+        let old = u64::from_le_bytes(bytes);
+        if old == current {
+            BytesAccess::write(self, addr, &new.to_le_bytes())?;
+        }
+        Ok(old)
+    }
+}
+
+impl AtomicAccess for Model {}
+
+impl AtomicAccess for LazyModel {}
+
+impl AtomicAccess for MmapModel {}
+
+//alignment is checked against the backing address, IO devices decide by themselves
+impl Memory {
+    fn atomic(&self, addr: &u64, size: usize) -> Result<&dyn AtomicAccess, AccessError> {
+        let model: &dyn AtomicAccess = match self {
+            Memory::IO(io) => {
+                return io.atomic().ok_or_else(|| {
+                    AccessError::DeviceError(*addr, "atomic access is not supported!".to_string())
+                })
+            }
+            Memory::Model(model) => model,
+            Memory::LazyModel(model) => model,
+            Memory::MmapModel(model) => model,
+            _ => unreachable!(),
+        };
+        check_align(addr, size)?;
+        Ok(model)
+    }
+}
+
+macro_rules! region_atomic {
+    ($amo:ident, $apply:ident, $try_amo:ident, $try_amo_endian:ident, $cas:ident, $try_cas:ident, $try_cas_endian:ident, $try_read_endian:ident, $conv:ident, $t:ty, $size:expr) => {
+        impl Memory {
+            fn $try_amo(
+                &self,
+                addr: &u64,
+                op: AmoOp,
+                data: $t,
+                endian: Endian,
+            ) -> Result<$t, AccessError> {
+                let model = match self {
+                    Memory::Block(_, region) => {
+                        return region.$try_amo_endian(addr, op, data, endian)
+                    }
+                    Memory::RootBlock(region) => {
+                        return region.$try_amo_endian(addr, op, data, endian)
+                    }
+                    Memory::Remap(remap) => {
+                        return remap.region.$try_amo_endian(addr, op, data, endian)
+                    }
+                    Memory::Bridge(bridge) => return bridge.$try_amo(addr, op, data, endian),
+                    Memory::Iommu(iommu) => {
+                        let pa = iommu.translate(AccessKind::Write, addr, $size)?;
+                        return iommu.target().$try_amo_endian(&pa, op, data, endian);
+                    }
+                    _ => self.atomic(addr, $size)?,
+                };
+                let err = |e| AccessError::DeviceError(*addr, e);
+                if endian == Endian::Little || op.is_bitwise() {
+                    return model
+                        .$amo(addr, op, endian.$conv(data))
+                        .map(|v| endian.$conv(v))
+                        .map_err(err);
+                }
+                //arithmetic in the other byte order, compare-exchange until nothing changed in between
+                let mut bytes = [0u8; $size];
+                BytesAccess::read(model, addr, &mut bytes).map_err(err)?;
+                let mut old = <$t>::from_le_bytes(bytes);
+                loop {
+                    let new = endian.$conv(op.$apply(endian.$conv(old), data));
+                    let cur = model.$cas(addr, old, new).map_err(err)?;
+                    if cur == old {
+                        return Ok(endian.$conv(old));
+                    }
+                    old = cur;
+                }
+            }
+
+            fn $try_cas(&self, addr: &u64, current: $t, new: $t) -> Result<$t, AccessError> {
+                match self {
+                    Memory::Block(_, region) => {
+                        region.$try_cas_endian(addr, current, new, Endian::Little)
+                    }
+                    Memory::RootBlock(region) => {
+                        region.$try_cas_endian(addr, current, new, Endian::Little)
+                    }
+                    Memory::Remap(remap) => {
+                        remap
+                            .region
+                            .$try_cas_endian(addr, current, new, Endian::Little)
+                    }
+                    Memory::Bridge(bridge) => bridge.$try_cas(addr, current, new),
+                    Memory::Iommu(iommu) => {
+                        let pa = iommu.translate(AccessKind::Write, addr, $size)?;
+                        iommu
+                            .target()
+                            .$try_cas_endian(&pa, current, new, Endian::Little)
+                    }
+                    _ => self
+                        .atomic(addr, $size)?
+                        .$cas(addr, current, new)
+                        .map_err(|e| AccessError::DeviceError(*addr, e)),
+                }
+            }
+        }
+
+        //misaligned atomics are never split, whatever the unaligned policy is
+        impl Region {
+            pub fn $try_amo(&self, addr: &u64, op: AmoOp, data: $t) -> Result<$t, AccessError> {
+                self.$try_amo_endian(addr, op, data, self.endian.get())
+            }
+
+            pub fn $try_amo_endian(
+                &self,
+                addr: &u64,
+                op: AmoOp,
+                data: $t,
+                endian: Endian,
+            ) -> Result<$t, AccessError> {
+                if !self.check_writable(addr, $size)? {
+                    return self.$try_read_endian(addr, endian);
+                }
+                let old = self
+                    .memory
+                    .$try_amo(&self.translate(addr, $size)?, op, data, endian)
+                    .map_err(|e| e.at(addr))?;
+                self.watcher
+                    .notify(AccessKind::Read, addr, &endian.$conv(old).to_le_bytes());
+                self.watcher.notify(
+                    AccessKind::Write,
+                    addr,
+                    &endian.$conv(op.$apply(old, data)).to_le_bytes(),
+                );
+                Ok(old)
+            }
+
+            pub fn $try_cas(&self, addr: &u64, current: $t, new: $t) -> Result<$t, AccessError> {
+                self.$try_cas_endian(addr, current, new, self.endian.get())
+            }
+
+            pub fn $try_cas_endian(
+                &self,
+                addr: &u64,
+                current: $t,
+                new: $t,
+                endian: Endian,
+            ) -> Result<$t, AccessError> {
+                if !self.check_writable(addr, $size)? {
+                    return self.$try_read_endian(addr, endian);
+                }
+                let old = self
+                    .memory
+                    .$try_cas(
+                        &self.translate(addr, $size)?,
+                        endian.$conv(current),
+                        endian.$conv(new),
+                    )
+                    .map_err(|e| e.at(addr))?;
+                self.watcher
+                    .notify(AccessKind::Read, addr, &old.to_le_bytes());
+                if old == endian.$conv(current) {
+                    self.watcher
+                        .notify(AccessKind::Write, addr, &endian.$conv(new).to_le_bytes());
+                }
+                Ok(endian.$conv(old))
+            }
+        }
+    };
+}
+
+region_atomic!(
+    amo_u32,
+    apply_u32,
+    try_amo_u32,
+    try_amo_u32_endian,
+    compare_exchange_u32,
+    try_compare_exchange_u32,
+    try_compare_exchange_u32_endian,
+    try_read_u32_endian,
+    conv_u32,
+    u32,
+    4
+);
+region_atomic!(
+    amo_u64,
+    apply_u64,
+    try_amo_u64,
+    try_amo_u64_endian,
+    compare_exchange_u64,
+    try_compare_exchange_u64,
+    try_compare_exchange_u64_endian,
+    try_read_u64_endian,
+    conv_u64,
+    u64,
+    8
+);
+
+//keep the String error for device errors
+impl AtomicAccess for Region {
+    fn amo_u32(&self, addr: &u64, op: AmoOp, data: u32) -> Result<u32, String> {
+        match self.try_amo_u32(addr, op, data) {
+            Err(AccessError::DeviceError(_, msg)) => Err(msg),
+            res => Ok(res.unwrap_or_else(|e| panic!("{}", e))),
+        }
+    }
+
+    fn amo_u64(&self, addr: &u64, op: AmoOp, data: u64) -> Result<u64, String> {
+        match self.try_amo_u64(addr, op, data) {
+            Err(AccessError::DeviceError(_, msg)) => Err(msg),
+            res => Ok(res.unwrap_or_else(|e| panic!("{}", e))),
+        }
+    }
+
+    fn compare_exchange_u32(&self, addr: &u64, current: u32, new: u32) -> Result<u32, String> {
+        match self.try_compare_exchange_u32(addr, current, new) {
+            Err(AccessError::DeviceError(_, msg)) => Err(msg),
+            res => Ok(res.unwrap_or_else(|e| panic!("{}", e))),
+        }
+    }
+
+    fn compare_exchange_u64(&self, addr: &u64, current: u64, new: u64) -> Result<u64, String> {
+        match self.try_compare_exchange_u64(addr, current, new) {
+            Err(AccessError::DeviceError(_, msg)) => Err(msg),
+            res => Ok(res.unwrap_or_else(|e| panic!("{}", e))),
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use super::*;
+use crate::space::Space;
+
+type BridgeHook = Box<dyn Fn(AccessKind, u64, usize) -> Result<(), String>>;
+
+//a window of a bus into a space mounted on it, with the decode of the space behind it.
+//accesses are forwarded in memory byte order, so the outermost view decides as for remaps
+pub(super) struct Bridge {
+    space: Rc<Space>,
+    offset: u64,
+    hook: RefCell<Option<BridgeHook>>,
+}
+
+impl Bridge {
+    fn hook(&self, kind: AccessKind, addr: &u64, size: usize) -> Result<(), AccessError> {
+        if let Some(hook) = self.hook.borrow().as_ref() {
+            hook(kind, *addr, size).map_err(|e| AccessError::DeviceError(*addr, e))?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub(super) fn try_write_u8(&self, addr: &u64, data: u8) -> Result<(), AccessError> {
+        self.hook(AccessKind::Write, addr, 1)?;
+        self.space.try_write_u8(addr, data)
+    }
+
+    pub(super) fn try_read_u8(&self, addr: &u64) -> Result<u8, AccessError> {
+        self.hook(AccessKind::Read, addr, 1)?;
+        self.space.try_read_u8(addr)
+    }
+
+    pub(super) fn try_write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        self.hook(AccessKind::Write, addr, data.len())?;
+        self.space.try_write_bytes(addr, data)
+    }
+
+    pub(super) fn try_read_bytes(&self, addr: &u64, data: &mut [u8]) -> Result<usize, AccessError> {
+        self.hook(AccessKind::Read, addr, data.len())?;
+        self.space.try_read_bytes(addr, data)
+    }
+
+    pub(super) fn backdoor_write_bytes(
+        &self,
+        addr: &u64,
+        data: &[u8],
+    ) -> Result<usize, AccessError> {
+        self.space.backdoor_write_bytes(addr, data)
+    }
+
+    pub(super) fn host_slice(&self, addr: &u64, len: usize) -> Result<HostSlice<'_>, AccessError> {
+        self.space.host_slice(addr, len)
+    }
+
+    pub(super) fn host_slice_mut(
+        &self,
+        addr: &u64,
+        len: usize,
+    ) -> Result<HostSliceMut<'_>, AccessError> {
+        self.space.host_slice_mut(addr, len)
+    }
+}
+
+macro_rules! bridge_sized {
+    ($le_write:ident, $le_read:ident, $try_write_endian:ident, $try_read_endian:ident, $t:ty, $size:expr) => {
+        impl Bridge {
+            pub(super) fn $le_write(&self, addr: &u64, data: $t) -> Result<(), AccessError> {
+                self.hook(AccessKind::Write, addr, $size)?;
+                self.space.$try_write_endian(addr, data, Endian::Little)
+            }
+
+            pub(super) fn $le_read(&self, addr: &u64) -> Result<$t, AccessError> {
+                self.hook(AccessKind::Read, addr, $size)?;
+                self.space.$try_read_endian(addr, Endian::Little)
+            }
+        }
+    };
+}
+
+bridge_sized!(
+    le_write_u16,
+    le_read_u16,
+    try_write_u16_endian,
+    try_read_u16_endian,
+    u16,
+    2
+);
+bridge_sized!(
+    le_write_u32,
+    le_read_u32,
+    try_write_u32_endian,
+    try_read_u32_endian,
+    u32,
+    4
+);
+bridge_sized!(
+    le_write_u64,
+    le_read_u64,
+    try_write_u64_endian,
+    try_read_u64_endian,
+    u64,
+    8
+);
+
+//atomics are writes to the space, so they break its reservations and reach its watchpoints
+macro_rules! bridge_atomic {
+    ($try_amo:ident, $try_amo_endian:ident, $try_cas:ident, $try_cas_endian:ident, $t:ty, $size:expr) => {
+        impl Bridge {
+            pub(super) fn $try_amo(
+                &self,
+                addr: &u64,
+                op: AmoOp,
+                data: $t,
+                endian: Endian,
+            ) -> Result<$t, AccessError> {
+                self.hook(AccessKind::Write, addr, $size)?;
+                self.space.$try_amo_endian(addr, op, data, endian)
+            }
+
+            pub(super) fn $try_cas(
+                &self,
+                addr: &u64,
+                current: $t,
+                new: $t,
+            ) -> Result<$t, AccessError> {
+                self.hook(AccessKind::Write, addr, $size)?;
+                self.space
+                    .$try_cas_endian(addr, current, new, Endian::Little)
+            }
+        }
+    };
+}
+
+bridge_atomic!(
+    try_amo_u32,
+    try_amo_u32_endian,
+    try_compare_exchange_u32,
+    try_compare_exchange_u32_endian,
+    u32,
+    4
+);
+bridge_atomic!(
+    try_amo_u64,
+    try_amo_u64_endian,
+    try_compare_exchange_u64,
+    try_compare_exchange_u64_endian,
+    u64,
+    8
+);
+
+impl Region {
+    //mount [offset, offset + size) of space at base, the space must not be mounted into itself
+    pub fn bridge(base: u64, size: u64, space: &Rc<Space>, offset: u64) -> Rc<Region> {
+        Rc::new(Region::new(
+            Memory::Bridge(Bridge {
+                space: Rc::clone(space),
+                offset,
+                hook: RefCell::new(None),
+            }),
+            base,
+            size,
+        ))
+    }
+
+    pub fn bridged_space(&self) -> Option<&Rc<Space>> {
+        if let Memory::Bridge(bridge) = &self.memory {
+            Some(&bridge.space)
+        } else {
+            None
+        }
+    }
+
+    //called with the address in the space before every access forwarded by the bridge,
+    //an error fails the access with AccessError::DeviceError
+    pub fn set_bridge_hook<F: Fn(AccessKind, u64, usize) -> Result<(), String> + 'static>(
+        &self,
+        hook: F,
+    ) {
+        if let Memory::Bridge(bridge) = &self.memory {
+            *bridge.hook.borrow_mut() = Some(Box::new(hook))
+        } else {
+            panic!("{} is not a bridge!", self.get_type())
+        }
+    }
+
+    pub fn clear_bridge_hook(&self) {
+        if let Memory::Bridge(bridge) = &self.memory {
+            *bridge.hook.borrow_mut() = None
+        }
+    }
+}
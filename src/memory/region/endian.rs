@@ -0,0 +1,89 @@
+use super::*;
+
+//byte order of sized accesses, the backing store is addressed by bytes and does not have one.
+//the outermost view decides, remaps and heap blocks are accessed through in memory byte order,
+//so a big-endian remap of a region shares the memory with little-endian users of the region
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+macro_rules! endian_conv {
+    ($name:ident, $t:ty) => {
+        //convert between the value in memory byte order(little) and in this byte order,
+        //the conversion is its own inverse
+        pub(crate) fn $name(&self, data: $t) -> $t {
+            match self {
+                Endian::Little => data,
+                Endian::Big => data.swap_bytes(),
+            }
+        }
+    };
+}
+
+impl Endian {
+    endian_conv!(conv_u16, u16);
+    endian_conv!(conv_u32, u32);
+    endian_conv!(conv_u64, u64);
+}
+
+macro_rules! region_endian {
+    ($try_write:ident, $try_read:ident, $try_write_endian:ident, $try_read_endian:ident, $le_write:ident, $le_read:ident, $conv:ident, $t:ty) => {
+        impl Region {
+            pub fn $try_write(&self, addr: &u64, data: $t) -> Result<(), AccessError> {
+                self.$try_write_endian(addr, data, self.endian.get())
+            }
+
+            pub fn $try_read(&self, addr: &u64) -> Result<$t, AccessError> {
+                self.$try_read_endian(addr, self.endian.get())
+            }
+
+            //override the endianness of the region per call
+            pub fn $try_write_endian(
+                &self,
+                addr: &u64,
+                data: $t,
+                endian: Endian,
+            ) -> Result<(), AccessError> {
+                self.$le_write(addr, endian.$conv(data))
+            }
+
+            pub fn $try_read_endian(&self, addr: &u64, endian: Endian) -> Result<$t, AccessError> {
+                self.$le_read(addr).map(|v| endian.$conv(v))
+            }
+        }
+    };
+}
+
+region_endian!(
+    try_write_u16,
+    try_read_u16,
+    try_write_u16_endian,
+    try_read_u16_endian,
+    le_write_u16,
+    le_read_u16,
+    conv_u16,
+    u16
+);
+region_endian!(
+    try_write_u32,
+    try_read_u32,
+    try_write_u32_endian,
+    try_read_u32_endian,
+    le_write_u32,
+    le_read_u32,
+    conv_u32,
+    u32
+);
+region_endian!(
+    try_write_u64,
+    try_read_u64,
+    try_write_u64_endian,
+    try_read_u64_endian,
+    le_write_u64,
+    le_read_u64,
+    conv_u64,
+    u64
+);
@@ -0,0 +1,135 @@
+use super::*;
+use std::cell::{Ref, RefMut};
+use std::ops::DerefMut;
+
+enum HostRef<'a> {
+    Model(Ref<'a, [u8]>),
+    Mmap { _flag: Ref<'a, ()>, slice: &'a [u8] },
+}
+
+enum HostRefMut<'a> {
+    Model(RefMut<'a, [u8]>),
+    Mmap {
+        _flag: RefMut<'a, ()>,
+        slice: &'a mut [u8],
+    },
+}
+
+//host memory of a guest address range, the region is borrowed while the guard is alive,
+//so a write through the region during a HostSlice, or any access during a HostSliceMut, panics
+pub struct HostSlice<'a>(HostRef<'a>);
+
+pub struct HostSliceMut<'a>(HostRefMut<'a>);
+
+impl Deref for HostSlice<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.0 {
+            HostRef::Model(r) => r,
+            HostRef::Mmap { slice, .. } => slice,
+        }
+    }
+}
+
+impl Deref for HostSliceMut<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.0 {
+            HostRefMut::Model(r) => r,
+            HostRefMut::Mmap { slice, .. } => slice,
+        }
+    }
+}
+
+impl DerefMut for HostSliceMut<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match &mut self.0 {
+            HostRefMut::Model(r) => r,
+            HostRefMut::Mmap { slice, .. } => slice,
+        }
+    }
+}
+
+fn borrowed(addr: &u64) -> AccessError {
+    AccessError::DeviceError(*addr, "host memory has been borrowed!".to_string())
+}
+
+impl Memory {
+    fn host_slice(&self, addr: &u64, len: usize) -> Result<HostSlice<'_>, AccessError> {
+        match self {
+            Memory::Model(model) => {
+                let offset = (*addr - model.info.base) as usize;
+                let inner = model.inner.try_borrow().map_err(|_| borrowed(addr))?;
+                Ok(HostSlice(HostRef::Model(Ref::map(inner, |m| {
+                    &m[offset..offset + len]
+                }))))
+            }
+            Memory::MmapModel(model) => {
+                let (flag, s) = model.host_slice(addr, len).map_err(|_| borrowed(addr))?;
+                Ok(HostSlice(HostRef::Mmap {
+                    _flag: flag,
+                    slice: s,
+                }))
+            }
+            Memory::Block(_, region) => region.host_slice(addr, len),
+            Memory::RootBlock(region) => region.host_slice(addr, len),
+            Memory::Remap(remap) => remap.region.host_slice(addr, len),
+            Memory::Bridge(bridge) => bridge.host_slice(addr, len),
+            Memory::Iommu(iommu) => iommu.host_slice(addr, len),
+            Memory::LazyModel(_) | Memory::IO(_) => Err(AccessError::NotMappable(*addr)),
+        }
+    }
+
+    fn host_slice_mut(&self, addr: &u64, len: usize) -> Result<HostSliceMut<'_>, AccessError> {
+        match self {
+            Memory::Model(model) => {
+                let offset = (*addr - model.info.base) as usize;
+                let inner = model.inner.try_borrow_mut().map_err(|_| borrowed(addr))?;
+                Ok(HostSliceMut(HostRefMut::Model(RefMut::map(inner, |m| {
+                    &mut m[offset..offset + len]
+                }))))
+            }
+            Memory::MmapModel(model) => {
+                let (flag, s) = model
+                    .host_slice_mut(addr, len)
+                    .map_err(|_| borrowed(addr))?;
+                Ok(HostSliceMut(HostRefMut::Mmap {
+                    _flag: flag,
+                    slice: s,
+                }))
+            }
+            Memory::Block(_, region) => region.host_slice_mut(addr, len),
+            Memory::RootBlock(region) => region.host_slice_mut(addr, len),
+            Memory::Remap(remap) => remap.region.host_slice_mut(addr, len),
+            Memory::Bridge(bridge) => bridge.host_slice_mut(addr, len),
+            Memory::Iommu(iommu) => iommu.host_slice_mut(addr, len),
+            Memory::LazyModel(_) | Memory::IO(_) => Err(AccessError::NotMappable(*addr)),
+        }
+    }
+}
+
+//accesses through host slices bypass watchpoints and traces
+impl Region {
+    pub fn is_host_mappable(&self) -> bool {
+        matches!(self.leaf().memory, Memory::Model(_) | Memory::MmapModel(_))
+    }
+
+    pub fn host_slice(&self, addr: &u64, len: usize) -> Result<HostSlice<'_>, AccessError> {
+        self.memory
+            .host_slice(&self.translate(addr, len)?, len)
+            .map_err(|e| e.at(addr))
+    }
+
+    //read only regions in the chain fail with ReadOnly, or NotMappable if writes are ignored so
+    //callers fall back to checked writes which drop them
+    pub fn host_slice_mut(&self, addr: &u64, len: usize) -> Result<HostSliceMut<'_>, AccessError> {
+        if !self.check_writable(addr, len)? {
+            return Err(AccessError::NotMappable(*addr));
+        }
+        self.memory
+            .host_slice_mut(&self.translate(addr, len)?, len)
+            .map_err(|e| e.at(addr))
+    }
+}
@@ -0,0 +1,292 @@
+use super::*;
+use crate::memory::attr::Perm;
+use std::collections::BTreeMap;
+
+//iova -> pa translation of [iova, iova + size)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct IommuEntry {
+    pub iova: u64,
+    pub pa: u64,
+    pub size: u64,
+    pub perm: Perm,
+}
+
+impl IommuEntry {
+    fn contains(&self, iova: u64) -> bool {
+        iova >= self.iova && iova - self.iova < self.size
+    }
+}
+
+type IommuWalker = Box<dyn Fn(u64) -> Option<IommuEntry>>;
+type IommuFaultHandler = Box<dyn Fn(AccessKind, u64, usize)>;
+
+//the address space seen by one initiator, translated into the target by windows mapped by the
+//model or by a walker of page tables programmed by the guest. there is no iotlb, every access
+//is translated again, so changes of the tables take effect at once
+pub(super) struct Iommu {
+    target: Rc<Region>,
+    windows: RefCell<BTreeMap<u64, IommuEntry>>,
+    walker: RefCell<Option<IommuWalker>>,
+    fault: RefCell<Option<IommuFaultHandler>>,
+}
+
+impl Iommu {
+    pub(super) fn target(&self) -> &Rc<Region> {
+        &self.target
+    }
+
+    //windows first, then the walker
+    fn lookup(&self, iova: u64) -> Option<IommuEntry> {
+        let window = self
+            .windows
+            .borrow()
+            .range(..=iova)
+            .next_back()
+            .map(|(_, e)| *e)
+            .filter(|e| e.contains(iova));
+        window.or_else(|| self.walker.borrow().as_ref().and_then(|walk| walk(iova)))
+    }
+
+    fn fault(&self, kind: AccessKind, iova: u64, size: usize) -> AccessError {
+        if let Some(handler) = self.fault.borrow().as_ref() {
+            handler(kind, iova, size)
+        }
+        AccessError::Fault(iova, kind)
+    }
+
+    //(pa, len) pieces of [iova, iova + size), permissions are not checked for the backdoor
+    fn chunks(
+        &self,
+        kind: AccessKind,
+        iova: u64,
+        size: usize,
+        check: bool,
+    ) -> Result<Vec<(u64, usize)>, AccessError> {
+        let mut chunks = vec![];
+        let mut cur = iova;
+        let mut rest = size as u64;
+        loop {
+            let entry = self
+                .lookup(cur)
+                .filter(|e| e.contains(cur))
+                .ok_or_else(|| self.fault(kind, iova, size))?;
+            let allowed = match kind {
+                AccessKind::Read => entry.perm.contains(Perm::R),
+                AccessKind::Write => entry.perm.contains(Perm::W),
+            };
+            if check && !allowed {
+                return Err(self.fault(kind, iova, size));
+            }
+            let len = std::cmp::min(rest, entry.size - (cur - entry.iova));
+            chunks.push((entry.pa + (cur - entry.iova), len as usize));
+            rest -= len;
+            if rest == 0 {
+                return Ok(chunks);
+            }
+            cur += len;
+        }
+    }
+
+    //the pa of an access which must not be split, e.g. atomics
+    pub(super) fn translate(
+        &self,
+        kind: AccessKind,
+        iova: &u64,
+        size: usize,
+    ) -> Result<u64, AccessError> {
+        match self.chunks(kind, *iova, size, true)?.as_slice() {
+            [(pa, _)] => Ok(*pa),
+            _ => Err(AccessError::DeviceError(
+                *iova,
+                format!("{}-byte access crosses iommu mappings!", size),
+            )),
+        }
+    }
+
+    pub(super) fn try_write_u8(&self, addr: &u64, data: u8) -> Result<(), AccessError> {
+        self.target
+            .try_write_u8(&self.translate(AccessKind::Write, addr, 1)?, data)
+    }
+
+    pub(super) fn try_read_u8(&self, addr: &u64) -> Result<u8, AccessError> {
+        self.target
+            .try_read_u8(&self.translate(AccessKind::Read, addr, 1)?)
+    }
+
+    fn write_chunks(&self, chunks: &[(u64, usize)], data: &[u8]) -> Result<usize, AccessError> {
+        let mut offset = 0;
+        for (pa, len) in chunks {
+            self.target
+                .try_write_bytes(pa, &data[offset..offset + len])?;
+            offset += len;
+        }
+        Ok(data.len())
+    }
+
+    pub(super) fn try_write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        self.write_chunks(
+            &self.chunks(AccessKind::Write, *addr, data.len(), true)?,
+            data,
+        )
+    }
+
+    pub(super) fn try_read_bytes(&self, addr: &u64, data: &mut [u8]) -> Result<usize, AccessError> {
+        let mut offset = 0;
+        for (pa, len) in self.chunks(AccessKind::Read, *addr, data.len(), true)? {
+            self.target
+                .try_read_bytes(&pa, &mut data[offset..offset + len])?;
+            offset += len;
+        }
+        Ok(data.len())
+    }
+
+    pub(super) fn backdoor_write_bytes(
+        &self,
+        addr: &u64,
+        data: &[u8],
+    ) -> Result<usize, AccessError> {
+        let mut offset = 0;
+        for (pa, len) in self.chunks(AccessKind::Write, *addr, data.len(), false)? {
+            self.target
+                .backdoor_write_bytes(&pa, &data[offset..offset + len])?;
+            offset += len;
+        }
+        Ok(data.len())
+    }
+
+    //buffers crossing mappings are not contiguous in the target, they have to be bounced
+    pub(super) fn host_slice(&self, addr: &u64, len: usize) -> Result<HostSlice<'_>, AccessError> {
+        match self.chunks(AccessKind::Read, *addr, len, true)?.as_slice() {
+            [(pa, _)] => self.target.host_slice(pa, len),
+            _ => Err(AccessError::NotMappable(*addr)),
+        }
+    }
+
+    pub(super) fn host_slice_mut(
+        &self,
+        addr: &u64,
+        len: usize,
+    ) -> Result<HostSliceMut<'_>, AccessError> {
+        match self.chunks(AccessKind::Write, *addr, len, true)?.as_slice() {
+            [(pa, _)] => self.target.host_slice_mut(pa, len),
+            _ => Err(AccessError::NotMappable(*addr)),
+        }
+    }
+}
+
+macro_rules! iommu_sized {
+    ($le_write:ident, $le_read:ident, $t:ty, $size:expr) => {
+        impl Iommu {
+            pub(super) fn $le_write(&self, addr: &u64, data: $t) -> Result<(), AccessError> {
+                let chunks = self.chunks(AccessKind::Write, *addr, $size, true)?;
+                match chunks.as_slice() {
+                    [(pa, _)] => self.target.$le_write(pa, data),
+                    _ => self.write_chunks(&chunks, &data.to_le_bytes()).map(|_| ()),
+                }
+            }
+
+            pub(super) fn $le_read(&self, addr: &u64) -> Result<$t, AccessError> {
+                let chunks = self.chunks(AccessKind::Read, *addr, $size, true)?;
+                match chunks.as_slice() {
+                    [(pa, _)] => self.target.$le_read(pa),
+                    _ => {
+                        let mut bytes = [0u8; $size];
+                        self.try_read_bytes(addr, &mut bytes)?;
+                        Ok(<$t>::from_le_bytes(bytes))
+                    }
+                }
+            }
+        }
+    };
+}
+
+iommu_sized!(le_write_u16, le_read_u16, u16, 2);
+iommu_sized!(le_write_u32, le_read_u32, u32, 4);
+iommu_sized!(le_write_u64, le_read_u64, u64, 8);
+
+impl Region {
+    //an iommu view of target at base, nothing is mapped at first.
+    //each initiator gets its own view, e.g. as the memory of its virtio queues
+    pub fn iommu(base: u64, size: u64, target: &Rc<Region>) -> Rc<Region> {
+        Rc::new(Region::new(
+            Memory::Iommu(Iommu {
+                target: Rc::clone(target),
+                windows: RefCell::new(BTreeMap::new()),
+                walker: RefCell::new(None),
+                fault: RefCell::new(None),
+            }),
+            base,
+            size,
+        ))
+    }
+
+    fn as_iommu(&self) -> &Iommu {
+        if let Memory::Iommu(iommu) = &self.memory {
+            iommu
+        } else {
+            panic!("{} is not an iommu!", self.get_type())
+        }
+    }
+
+    //map [iova, iova + size) to [pa, pa + size) of the target
+    pub fn iommu_map(
+        &self,
+        iova: u64,
+        pa: u64,
+        size: u64,
+        perm: Perm,
+    ) -> std::result::Result<(), String> {
+        let iommu = self.as_iommu();
+        if size == 0 || iova.checked_add(size).is_none() {
+            return Err(format!(
+                "invalid iommu window {:#x} size {:#x}!",
+                iova, size
+            ));
+        }
+        let mut windows = iommu.windows.borrow_mut();
+        let overlapped = windows
+            .range(..iova + size)
+            .next_back()
+            .map(|(_, e)| *e)
+            .filter(|e| e.iova + e.size > iova);
+        if let Some(e) = overlapped {
+            return Err(format!(
+                "iommu window {:#x} - {:#x} overlaps {:#x} - {:#x}!",
+                iova,
+                iova + size,
+                e.iova,
+                e.iova + e.size
+            ));
+        }
+        windows.insert(
+            iova,
+            IommuEntry {
+                iova,
+                pa,
+                size,
+                perm,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn iommu_unmap(&self, iova: u64) -> Option<IommuEntry> {
+        self.as_iommu().windows.borrow_mut().remove(&iova)
+    }
+
+    //translations missed by the windows, e.g. walking the page tables in guest memory.
+    //the returned entry must contain the iova
+    pub fn set_iommu_walker<F: Fn(u64) -> Option<IommuEntry> + 'static>(&self, walker: F) {
+        *self.as_iommu().walker.borrow_mut() = Some(Box::new(walker))
+    }
+
+    pub fn clear_iommu_walker(&self) {
+        *self.as_iommu().walker.borrow_mut() = None
+    }
+
+    //called with the iova and the size of every faulting access before it fails with
+    //AccessError::Fault
+    pub fn set_iommu_fault_handler<F: Fn(AccessKind, u64, usize) + 'static>(&self, handler: F) {
+        *self.as_iommu().fault.borrow_mut() = Some(Box::new(handler))
+    }
+}
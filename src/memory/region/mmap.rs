@@ -0,0 +1,191 @@
+extern crate libc;
+
+use super::*;
+use std::cell::{BorrowError, BorrowMutError, Ref, RefMut};
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MmapMode {
+    //copy-on-write, the file is never modified
+    Private,
+    //write-through, dirty pages are persisted back to the file
+    Shared,
+}
+
+pub(super) struct MmapModel {
+    info: MemInfo,
+    ptr: *mut u8,
+    mode: MmapMode,
+    //borrowed like a RefCell, so that host slices never alias with other accesses
+    flag: RefCell<()>,
+}
+
+impl MmapModel {
+    pub(super) fn new(info: MemInfo, file: &File, mode: MmapMode) -> Result<MmapModel, String> {
+        if std::mem::size_of::<u64>() != std::mem::size_of::<usize>() {
+            assert!(
+                info.size < 0x1_0000_0000,
+                "mmap size can not exceed 4g when usize is 4!"
+            )
+        }
+        if info.size == 0 {
+            return Err("mmap size can not be 0!".to_string());
+        }
+        let len = info.size as usize;
+        let file_len = file.metadata().map_err(|e| e.to_string())?.len();
+        let fd = file.as_raw_fd();
+        let ptr = match mode {
+            MmapMode::Shared => {
+                if file_len < info.size {
+                    file.set_len(info.size).map_err(|e| e.to_string())?;
+                }
+                MmapModel::map(std::ptr::null_mut(), len, libc::MAP_SHARED, fd)?
+            }
+            MmapMode::Private => {
+                //back the whole range with zero pages, then map the file over its head,
+                //so that accessing beyond the end of file won't raise SIGBUS
+                let ptr = MmapModel::map(
+                    std::ptr::null_mut(),
+                    len,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                    -1,
+                )?;
+                let file_map_len = std::cmp::min(file_len, info.size) as usize;
+                if file_map_len != 0 {
+                    if let Err(e) =
+                        MmapModel::map(ptr, file_map_len, libc::MAP_PRIVATE | libc::MAP_FIXED, fd)
+                    {
+                        unsafe { libc::munmap(ptr as *mut libc::c_void, len) };
+                        return Err(e);
+                    }
+                }
+                ptr
+            }
+        };
+        Ok(MmapModel {
+            info,
+            ptr,
+            mode,
+            flag: RefCell::new(()),
+        })
+    }
+
+    fn map(
+        addr: *mut u8,
+        len: usize,
+        flags: libc::c_int,
+        fd: libc::c_int,
+    ) -> Result<*mut u8, String> {
+        let ptr = unsafe {
+            libc::mmap(
+                addr as *mut libc::c_void,
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                flags,
+                fd,
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            Err(format!("mmap fail! {}", std::io::Error::last_os_error()))
+        } else {
+            Ok(ptr as *mut u8)
+        }
+    }
+
+    pub(super) fn mode(&self) -> MmapMode {
+        self.mode
+    }
+
+    fn offset(&self, addr: &u64, len: usize) -> usize {
+        let offset = *addr - self.info.base;
+        assert!(
+            offset + len as u64 <= self.info.size,
+            "addr {:#x} len {} out of mmap range {:#x?}",
+            *addr,
+            len,
+            self.info
+        );
+        offset as usize
+    }
+
+    pub(super) fn host_slice(
+        &self,
+        addr: &u64,
+        len: usize,
+    ) -> Result<(Ref<'_, ()>, &[u8]), BorrowError> {
+        let flag = self.flag.try_borrow()?;
+        let offset = self.offset(addr, len);
+        Ok((flag, unsafe {
+            std::slice::from_raw_parts(self.ptr.add(offset), len)
+        }))
+    }
+
+    //the returned slice is exclusive as long as the flag is held
+    #[allow(clippy::mut_from_ref)]
+    pub(super) fn host_slice_mut(
+        &self,
+        addr: &u64,
+        len: usize,
+    ) -> Result<(RefMut<'_, ()>, &mut [u8]), BorrowMutError> {
+        let flag = self.flag.try_borrow_mut()?;
+        let offset = self.offset(addr, len);
+        Ok((flag, unsafe {
+            std::slice::from_raw_parts_mut(self.ptr.add(offset), len)
+        }))
+    }
+}
+
+impl U8Access for MmapModel {
+    fn write(&self, addr: &u64, data: u8) {
+        let _flag = self.flag.borrow_mut();
+        unsafe { *self.ptr.add(self.offset(addr, 1)) = data }
+    }
+
+    fn read(&self, addr: &u64) -> u8 {
+        let _flag = self.flag.borrow();
+        unsafe { *self.ptr.add(self.offset(addr, 1)) }
+    }
+}
+
+impl BytesAccess for MmapModel {
+    fn write(&self, addr: &u64, data: &[u8]) -> Result<usize, String> {
+        let _flag = self.flag.borrow_mut();
+        let offset = self.offset(addr, data.len());
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), self.ptr.add(offset), data.len());
+        }
+        Ok(data.len())
+    }
+
+    fn read(&self, addr: &u64, data: &mut [u8]) -> Result<usize, String> {
+        let _flag = self.flag.borrow();
+        let offset = self.offset(addr, data.len());
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.ptr.add(offset), data.as_mut_ptr(), data.len());
+        }
+        Ok(data.len())
+    }
+}
+
+impl U16Access for MmapModel {}
+
+impl U32Access for MmapModel {}
+
+impl U64Access for MmapModel {}
+
+impl Drop for MmapModel {
+    fn drop(&mut self) {
+        unsafe {
+            if self.mode == MmapMode::Shared {
+                libc::msync(
+                    self.ptr as *mut libc::c_void,
+                    self.info.size as usize,
+                    libc::MS_SYNC,
+                );
+            }
+            libc::munmap(self.ptr as *mut libc::c_void, self.info.size as usize);
+        }
+    }
+}
@@ -1,15 +1,63 @@
 #[cfg(test)]
 mod test;
 
+mod mmap;
+
+pub use mmap::MmapMode;
+use mmap::MmapModel;
+
+pub mod snapshot;
+
+pub use snapshot::IOSnapshot;
+
+mod watch;
+
+mod amo;
+
+pub use amo::{AmoOp, AtomicAccess};
+
+mod host;
+
+pub use host::{HostSlice, HostSliceMut};
+
+mod endian;
+
+pub use endian::Endian;
+
+mod rom;
+
+pub use rom::{RomPolicy, WritePolicy};
+
+mod resize;
+
+mod bridge;
+
+use bridge::Bridge;
+
+mod iommu;
+
+pub use iommu::IommuEntry;
+use iommu::Iommu;
+
+pub(crate) use watch::Watcher;
+pub use watch::{Access, AccessKind, WatchKind};
+
 use super::*;
-use crate::memory::allocator::{Allocator, LockedAllocator};
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::hash::{BuildHasherDefault, Hasher};
+use crate::memory::allocator::{
+    AllocError, AllocStats, Allocator, LiveBlock, LockedAllocator, Policy,
+};
+use crate::memory::attr::{AccessType, Attr};
+use crate::memory::model;
+use crate::memory::model::{memory_bytes, memory_sized, LazyPages};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::Write;
 use std::marker::Sized;
 use std::mem::size_of;
 use std::ops::Deref;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 
 pub trait BytesAccess {
     fn write(&self, addr: &u64, data: &[u8]) -> Result<usize, String>;
@@ -97,126 +145,150 @@ pub trait U64Access: BytesAccess {
     }
 }
 
-pub trait IOAccess: U8Access + BytesAccess + U16Access + U32Access + U64Access {}
-
-#[derive(Default)]
-struct ModelHasher(u64);
-
-impl Hasher for ModelHasher {
-    fn finish(&self) -> u64 {
-        self.0
+pub trait IOAccess: U8Access + BytesAccess + U16Access + U32Access + U64Access {
+    fn snapshot(&self) -> Option<&dyn IOSnapshot> {
+        None
     }
-    fn write(&mut self, _: &[u8]) {
-        panic!("not implement")
-    }
-    fn write_u64(&mut self, i: u64) {
-        self.0 = i
-    }
-}
 
-struct LazyModel {
-    inner: RefCell<HashMap<u64, u8, BuildHasherDefault<ModelHasher>>>,
-}
-
-impl LazyModel {
-    fn new() -> LazyModel {
-        LazyModel {
-            inner: RefCell::new(HashMap::default()),
-        }
+    //atomics are rejected unless enabled by derive_io(.., Atomic)
+    fn atomic(&self) -> Option<&dyn AtomicAccess> {
+        None
     }
 }
 
-impl U8Access for LazyModel {
-    fn write(&self, addr: &u64, data: u8) {
-        self.inner.borrow_mut().insert(*addr, data);
-    }
-
-    fn read(&self, addr: &u64) -> u8 {
-        if let Some(&v) = self.inner.borrow().get(addr) {
-            v
-        } else {
-            0
-        }
-    }
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AccessError {
+    //(addr, size)
+    Unaligned(u64, usize),
+    //(addr, size)
+    OutOfRange(u64, usize),
+    Unmapped(u64),
+    DeviceError(u64, String),
+    //no host memory behind the address, e.g. IO and lazy models
+    NotMappable(u64),
+    //rejected by the attributes of the mapping
+    Denied(u64, AccessType, Attr),
+    //write to a region with WritePolicy::Fault
+    ReadOnly(u64),
+    //no translation or permission in an iommu view
+    Fault(u64, AccessKind),
 }
 
-impl BytesAccess for LazyModel {
-    fn write(&self, addr: &u64, data: &[u8]) -> Result<usize, String> {
-        {
-            data.iter().enumerate().for_each(|(offset, d)| {
-                self.inner.borrow_mut().insert(*addr + offset as u64, *d);
-            });
+impl Display for AccessError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            AccessError::Unaligned(addr, size) => {
+                write!(f, "Unaligned!{}-byte access @{:#x}", size, addr)
+            }
+            AccessError::OutOfRange(addr, size) => {
+                match (*size as u64)
+                    .checked_sub(1)
+                    .and_then(|last| addr.checked_add(last))
+                {
+                    Some(last) => write!(f, "OutOfRange!addr {:#x}-{:#x}", addr, last),
+                    None => write!(f, "OutOfRange!{}-byte access @{:#x}", size, addr),
+                }
+            }
+            AccessError::Unmapped(addr) => write!(f, "Unmapped!addr {:#x}", addr),
+            AccessError::DeviceError(addr, msg) => write!(f, "DeviceError!@{:#x}:{}", addr, msg),
+            AccessError::NotMappable(addr) => write!(f, "NotMappable!addr {:#x}", addr),
+            AccessError::Denied(addr, kind, attr) => {
+                write!(f, "Denied!{:?} @{:#x} with {}", kind, addr, attr)
+            }
+            AccessError::ReadOnly(addr) => write!(f, "ReadOnly!addr {:#x}", addr),
+            AccessError::Fault(addr, kind) => write!(f, "Fault!{:?} @{:#x}", kind, addr),
         }
-        Ok(data.len())
     }
+}
 
-    fn read(&self, addr: &u64, data: &mut [u8]) -> Result<usize, String> {
-        {
-            data.iter_mut().enumerate().for_each(|(offset, d)| {
-                *d = if let Some(&v) = self.inner.borrow().get(&(*addr + offset as u64)) {
-                    v
-                } else {
-                    0
-                }
-            });
+impl AccessError {
+    //report the error at the address seen by the caller
+    pub(crate) fn at(self, addr: &u64) -> AccessError {
+        match self {
+            AccessError::Unaligned(_, size) => AccessError::Unaligned(*addr, size),
+            AccessError::OutOfRange(_, size) => AccessError::OutOfRange(*addr, size),
+            AccessError::Unmapped(_) => AccessError::Unmapped(*addr),
+            AccessError::DeviceError(_, msg) => AccessError::DeviceError(*addr, msg),
+            AccessError::NotMappable(_) => AccessError::NotMappable(*addr),
+            AccessError::Denied(_, kind, attr) => AccessError::Denied(*addr, kind, attr),
+            AccessError::ReadOnly(_) => AccessError::ReadOnly(*addr),
+            AccessError::Fault(_, kind) => AccessError::Fault(*addr, kind),
         }
-        Ok(data.len())
     }
 }
 
-impl U16Access for LazyModel {}
-
-impl U32Access for LazyModel {}
-
-impl U64Access for LazyModel {}
-
-struct Model {
-    info: MemInfo,
-    inner: RefCell<Box<[u8]>>,
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum UnalignedPolicy {
+    //report AccessError::Unaligned
+    Fault,
+    //split into naturally aligned sub-accesses
+    Split,
 }
 
-impl Model {
-    fn new(info: MemInfo) -> Model {
-        let size = info.size;
-        if std::mem::size_of::<u64>() != std::mem::size_of::<usize>() {
-            assert!(size < 0x1_0000_0000, "global heap alloc max size can not exceed 4g when usize is 4, please use lazy_alloc!")
+//split [addr, addr + size) into the largest naturally aligned (addr, size) chunks
+fn aligned_chunks(addr: u64, size: usize) -> impl Iterator<Item = (u64, usize)> {
+    let end = addr + size as u64;
+    let mut cur = addr;
+    std::iter::from_fn(move || {
+        if cur >= end {
+            return None;
         }
-        Model {
-            info,
-            inner: RefCell::new(vec![0; size as usize].into_boxed_slice()),
+        let mut chunk: u64 = 8;
+        while cur & (chunk - 1) != 0 || cur + chunk > end {
+            chunk >>= 1;
         }
-    }
+        let item = (cur, chunk as usize);
+        cur += chunk;
+        Some(item)
+    })
 }
 
-impl U8Access for Model {
-    fn write(&self, addr: &u64, data: u8) {
-        self.inner.borrow_mut()[(*addr - self.info.base) as usize] = data;
-    }
-
-    fn read(&self, addr: &u64) -> u8 {
-        self.inner.borrow()[(*addr - self.info.base) as usize]
+//little-endian compose a value from aligned sub-reads
+pub(crate) fn split_read<F: FnMut(&u64, usize) -> Result<u64, AccessError>>(
+    addr: &u64,
+    size: usize,
+    mut read: F,
+) -> Result<u64, AccessError> {
+    let mut data: u64 = 0;
+    for (a, s) in aligned_chunks(*addr, size) {
+        data |= read(&a, s)? << ((a - *addr) << 3);
     }
+    Ok(data)
 }
 
-impl BytesAccess for Model {
-    fn write(&self, addr: &u64, data: &[u8]) -> Result<usize, String> {
-        let offset = (*addr - self.info.base) as usize;
-        self.inner.borrow_mut()[offset..offset + data.len()].copy_from_slice(data);
-        Ok(data.len())
+//little-endian decompose a value into aligned sub-writes, every chunk is decoded before any is
+//written so a bus error leaves memory untouched
+pub(crate) fn split_write<T, D, F>(
+    addr: &u64,
+    size: usize,
+    data: u64,
+    mut decode: D,
+    mut write: F,
+) -> Result<(), AccessError>
+where
+    D: FnMut(&u64, usize) -> Result<T, AccessError>,
+    F: FnMut(T, &u64, usize, u64) -> Result<(), AccessError>,
+{
+    let chunks = aligned_chunks(*addr, size)
+        .map(|(a, s)| decode(&a, s).map(|t| (t, a, s)))
+        .collect::<Result<Vec<_>, _>>()?;
+    for (t, a, s) in chunks {
+        write(t, &a, s, data >> ((a - *addr) << 3))?;
     }
+    Ok(())
+}
 
-    fn read(&self, addr: &u64, data: &mut [u8]) -> Result<usize, String> {
-        let offset = (*addr - self.info.base) as usize;
-        data.copy_from_slice(&self.inner.borrow()[offset..offset + data.len()]);
-        Ok(data.len())
+pub(crate) fn check_align(addr: &u64, size: usize) -> Result<(), AccessError> {
+    if *addr & (size as u64 - 1) != 0 {
+        Err(AccessError::Unaligned(*addr, size))
+    } else {
+        Ok(())
     }
 }
 
-impl U16Access for Model {}
-
-impl U32Access for Model {}
+type LazyModel = model::LazyModel<RefCell<LazyPages>>;
 
-impl U64Access for Model {}
+type Model = model::Model<RefCell<Box<[u8]>>>;
 
 struct Remap {
     region: Rc<Region>,
@@ -235,10 +307,13 @@ impl Remap {
 enum Memory {
     Model(Model),
     LazyModel(LazyModel),
+    MmapModel(MmapModel),
     Block(Rc<Heap>, Rc<Region>),
     RootBlock(Box<Region>),
     Remap(Remap),
     IO(Box<dyn IOAccess>),
+    Bridge(Bridge),
+    Iommu(Iommu),
 }
 
 impl Memory {
@@ -246,6 +321,7 @@ impl Memory {
         match self {
             Memory::Model(_) => "Model".to_string(),
             Memory::LazyModel(_) => "LazyModel".to_string(),
+            Memory::MmapModel(model) => format!("MmapModel({:?})", model.mode()),
             Memory::Block(_, _) => "Block".to_string(),
             Memory::RootBlock(_) => "Block".to_string(),
             Memory::Remap(remap) => format!(
@@ -255,287 +331,512 @@ impl Memory {
                 remap.info.base + remap.info.size
             ),
             Memory::IO(_) => "IO".to_string(),
+            Memory::Bridge(bridge) => format!("Bridge(@{:#x})", bridge.offset()),
+            Memory::Iommu(_) => "Iommu".to_string(),
         }
     }
 }
 
 macro_rules! memory_access {
-    ($x:ident, $f:ident, $obj:expr, $($p:expr),+) => {match $obj {
-            Memory::IO(io) => $x::$f(io.deref(),$($p,)+),
-            Memory::Model(model) => $x::$f(model,$($p,)+),
-            Memory::LazyModel(model) => $x::$f(model,$($p,)+),
-            Memory::Block(_, region) =>  $x::$f(region.deref(),$($p,)+),
-            Memory::RootBlock(region) =>  $x::$f(region.deref(),$($p,)+),
-            Memory::Remap(remap) => $x::$f(remap.region.deref(),$($p,)+),
+    ($obj:expr, $io:ident => $io_access:expr, $model:ident => $model_access:expr, $f:ident, $($p:expr),+) => {match $obj {
+            Memory::IO(io) => {
+                let $io = io.deref();
+                $io_access
+            }
+            Memory::Model($model) => $model_access,
+            Memory::LazyModel($model) => $model_access,
+            Memory::MmapModel($model) => $model_access,
+            Memory::Block(_, region) => region.$f($($p,)+),
+            Memory::RootBlock(region) => region.$f($($p,)+),
+            Memory::Remap(remap) => remap.region.$f($($p,)+),
+            Memory::Bridge(bridge) => bridge.$f($($p,)+),
+            Memory::Iommu(iommu) => iommu.$f($($p,)+),
         }
         }
 }
 
-impl U8Access for Memory {
-    fn write(&self, addr: &u64, data: u8) {
-        memory_access!(U8Access, write, self, addr, data)
-    }
-
-    fn read(&self, addr: &u64) -> u8 {
-        memory_access!(U8Access, read, self, addr)
-    }
-}
-
-impl U16Access for Memory {
-    fn write(&self, addr: &u64, data: u16) {
-        memory_access!(U16Access, write, self, addr, data)
-    }
-
-    fn read(&self, addr: &u64) -> u16 {
-        memory_access!(U16Access, read, self, addr)
-    }
-}
-
-impl U32Access for Memory {
-    fn write(&self, addr: &u64, data: u32) {
-        memory_access!(U32Access, write, self, addr, data)
-    }
-
-    fn read(&self, addr: &u64) -> u32 {
-        memory_access!(U32Access, read, self, addr)
-    }
-}
-
-impl U64Access for Memory {
-    fn write(&self, addr: &u64, data: u64) {
-        memory_access!(U64Access, write, self, addr, data)
-    }
-
-    fn read(&self, addr: &u64) -> u64 {
-        memory_access!(U64Access, read, self, addr)
-    }
-}
-
-impl BytesAccess for Memory {
-    fn write(&self, addr: &u64, data: &[u8]) -> Result<usize, String> {
-        memory_access!(BytesAccess, write, self, addr, data)
-    }
-
-    fn read(&self, addr: &u64, data: &mut [u8]) -> Result<usize, String> {
-        memory_access!(BytesAccess, read, self, addr, data)
-    }
-}
+//alignment is checked against the backing address, IO devices decide by themselves
+memory_sized!(
+    try_write_u8,
+    try_read_u8,
+    try_write_u8,
+    try_read_u8,
+    U8Access,
+    u8,
+    1
+);
+memory_sized!(
+    try_write_u16,
+    try_read_u16,
+    le_write_u16,
+    le_read_u16,
+    U16Access,
+    u16,
+    2
+);
+memory_sized!(
+    try_write_u32,
+    try_read_u32,
+    le_write_u32,
+    le_read_u32,
+    U32Access,
+    u32,
+    4
+);
+memory_sized!(
+    try_write_u64,
+    try_read_u64,
+    le_write_u64,
+    le_read_u64,
+    U64Access,
+    u64,
+    8
+);
+memory_bytes!();
 
 #[repr(C)]
 pub struct Region {
     memory: Memory,
     pub info: MemInfo,
+    unaligned: Cell<UnalignedPolicy>,
+    endian: Cell<Endian>,
+    write_policy: Cell<WritePolicy>,
+    watcher: Watcher,
+    heaps: RefCell<Vec<Weak<Heap>>>,
 }
 
 impl Region {
+    fn new(memory: Memory, base: u64, size: u64) -> Region {
+        Region {
+            memory,
+            info: MemInfo { base, size },
+            unaligned: Cell::new(UnalignedPolicy::Fault),
+            endian: Cell::new(Endian::Little),
+            write_policy: Cell::new(WritePolicy::Writable),
+            watcher: Watcher::new(),
+            heaps: RefCell::new(vec![]),
+        }
+    }
+
     pub fn get_type(&self) -> String {
         self.memory.get_type()
     }
 
     pub fn io(base: u64, size: u64, io: Box<dyn IOAccess>) -> Rc<Region> {
-        Rc::new(Region {
-            memory: Memory::IO(io),
-            info: MemInfo {
-                base: base,
-                size: size,
-            },
-        })
+        Rc::new(Region::new(Memory::IO(io), base, size))
     }
 
     fn lazy_model(base: u64, size: u64) -> Region {
-        Region {
-            memory: Memory::LazyModel(LazyModel::new()),
-            info: MemInfo {
-                base: base,
-                size: size,
-            },
-        }
+        Region::new(Memory::LazyModel(LazyModel::new()), base, size)
     }
 
     fn model(base: u64, size: u64) -> Region {
-        Region {
-            memory: Memory::Model(Model::new(MemInfo {
-                base: base,
-                size: size,
-            })),
-            info: MemInfo {
-                base: base,
-                size: size,
-            },
-        }
+        Region::new(
+            Memory::Model(Model::new(MemInfo { base, size })),
+            base,
+            size,
+        )
+    }
+
+    fn mmap_model(base: u64, size: u64, file: &File, mode: MmapMode) -> Result<Region, String> {
+        let model = MmapModel::new(MemInfo { base, size }, file, mode)?;
+        Ok(Region::new(Memory::MmapModel(model), base, size))
     }
 
     fn block(base: u64, size: u64, heap: &Rc<Heap>, memory: &Rc<Region>) -> Rc<Region> {
-        Rc::new(Region {
-            memory: Memory::Block(Rc::clone(heap), Rc::clone(memory)),
-            info: MemInfo {
-                base: base,
-                size: size,
-            },
-        })
+        Rc::new(Region::new(
+            Memory::Block(Rc::clone(heap), Rc::clone(memory)),
+            base,
+            size,
+        ))
     }
 
     fn root_block(base: u64, size: u64, memory: Region) -> Rc<Region> {
-        Rc::new(Region {
-            memory: Memory::RootBlock(Box::new(memory)),
-            info: MemInfo {
-                base: base,
-                size: size,
-            },
-        })
+        Rc::new(Region::new(Memory::RootBlock(Box::new(memory)), base, size))
     }
 
     pub fn remap(base: u64, memory: &Rc<Region>) -> Rc<Region> {
         let info = memory.info;
-        Rc::new(Region {
-            memory: Memory::Remap(Remap::new(memory, info)),
-            info: MemInfo {
-                base: base,
-                size: info.size,
-            },
-        })
+        Rc::new(Region::new(
+            Memory::Remap(Remap::new(memory, info)),
+            base,
+            info.size,
+        ))
     }
 
     pub fn remap_partial(base: u64, memory: &Rc<Region>, offset: u64, size: u64) -> Rc<Region> {
         assert!(offset + size <= memory.info.size);
         assert!(offset & 0x7 == 0);
         let info = memory.info;
-        Rc::new(Region {
-            memory: Memory::Remap(Remap::new(
+        Rc::new(Region::new(
+            Memory::Remap(Remap::new(
                 memory,
                 MemInfo {
                     base: info.base + offset,
                     size: size,
                 },
             )),
-            info: MemInfo {
-                base: base,
-                size: size,
-            },
-        })
+            base,
+            size,
+        ))
     }
-    fn translate(&self, va: &u64, size: usize) -> Option<u64> {
-        assert!(
-            *va >= self.info.base
-                && *va < self.info.base + self.info.size
-                && *va + size as u64 - 1 >= self.info.base
-                && *va + size as u64 - 1 < self.info.base + self.info.size,
-                "addr {:#x}-{:#x} translate fail!range {:#x?}",
-                *va,
-                *va + size as u64 - 1,
-                self.info
-        );
+
+    fn translate(&self, va: &u64, size: usize) -> Result<u64, AccessError> {
+        let end = va
+            .checked_sub(self.info.base)
+            .and_then(|offset| offset.checked_add(size as u64));
+        if !matches!(end, Some(end) if end <= self.info.size) {
+            return Err(AccessError::OutOfRange(*va, size));
+        }
+        match &self.memory {
+            Memory::Remap(remap) => Ok(va - self.info.base + remap.info.base),
+            Memory::Bridge(bridge) => Ok(va - self.info.base + bridge.offset()),
+            _ => Ok(*va),
+        }
+    }
+
+    pub fn unaligned_policy(&self) -> UnalignedPolicy {
+        self.unaligned.get()
+    }
+
+    pub fn set_unaligned_policy(&self, policy: UnalignedPolicy) {
+        self.unaligned.set(policy)
+    }
+
+    pub fn endian(&self) -> Endian {
+        self.endian.get()
+    }
+
+    //byte order of U16/U32/U64 and atomic accesses through this region
+    pub fn set_endian(&self, endian: Endian) {
+        self.endian.set(endian)
+    }
+
+    //accesses through remaps and heap blocks of this region are watched as well
+    pub fn add_watchpoint<F: Fn(&Access) + 'static>(
+        &self,
+        base: u64,
+        size: u64,
+        kind: WatchKind,
+        callback: F,
+    ) -> usize {
+        self.watcher.add(base, size, kind, callback)
+    }
+
+    pub fn remove_watchpoint(&self, id: usize) -> bool {
+        self.watcher.remove(id)
+    }
+
+    pub fn set_trace(&self, sink: Option<Box<dyn Write>>) {
+        self.watcher.set_trace(sink)
+    }
+
+    fn split_unaligned(&self, addr: &u64, size: usize) -> bool {
+        self.unaligned.get() == UnalignedPolicy::Split && *addr & (size as u64 - 1) != 0
+    }
+
+    //the region this one is built on
+    fn inner(&self) -> Option<&Region> {
         match &self.memory {
-            Memory::Remap(remap) => Some(va - self.info.base + remap.info.base),
+            Memory::Block(_, region) => Some(region),
+            Memory::RootBlock(region) => Some(region),
+            Memory::Remap(remap) => Some(&remap.region),
             _ => None,
         }
     }
+
+    //aligned sub-access of a split access in memory byte order, watchers along the chain are
+    //notified by the caller once for the whole access
+    pub(crate) fn read_chunk(&self, addr: &u64, size: usize) -> Result<u64, AccessError> {
+        let pa = self.translate(addr, size)?;
+        if let Some(inner) = self.inner() {
+            return inner.read_chunk(&pa, size).map_err(|e| e.at(addr));
+        }
+        match size {
+            1 => self.memory.try_read_u8(&pa).map(|v| v as u64),
+            2 => self.memory.try_read_u16(&pa).map(|v| v as u64),
+            4 => self.memory.try_read_u32(&pa).map(|v| v as u64),
+            _ => self.memory.try_read_u64(&pa),
+        }
+        .map_err(|e| e.at(addr))
+    }
+
+    //decode a sub-write along the chain without writing
+    pub(crate) fn check_chunk(&self, addr: &u64, size: usize) -> Result<(), AccessError> {
+        self.check_writable(addr, size)?;
+        let pa = self.translate(addr, size)?;
+        if let Some(inner) = self.inner() {
+            return inner.check_chunk(&pa, size).map_err(|e| e.at(addr));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_chunk(&self, addr: &u64, size: usize, data: u64) -> Result<(), AccessError> {
+        if !self.check_writable(addr, size)? {
+            return Ok(());
+        }
+        let pa = self.translate(addr, size)?;
+        if let Some(inner) = self.inner() {
+            return inner.write_chunk(&pa, size, data).map_err(|e| e.at(addr));
+        }
+        match size {
+            1 => self.memory.try_write_u8(&pa, data as u8),
+            2 => self.memory.try_write_u16(&pa, data as u16),
+            4 => self.memory.try_write_u32(&pa, data as u32),
+            _ => self.memory.try_write_u64(&pa, data),
+        }
+        .map_err(|e| e.at(addr))
+    }
+
+    pub(crate) fn notify(&self, kind: AccessKind, addr: &u64, data: &[u8]) {
+        self.watcher.notify(kind, addr, data);
+        if let Some(inner) = self.inner() {
+            if let Ok(pa) = self.translate(addr, data.len()) {
+                inner.notify(kind, &pa, data)
+            }
+        }
+    }
+
+    pub fn try_write_u8(&self, addr: &u64, data: u8) -> Result<(), AccessError> {
+        if !self.check_writable(addr, 1)? {
+            return Ok(());
+        }
+        self.memory
+            .try_write_u8(&self.translate(addr, 1)?, data)
+            .map_err(|e| e.at(addr))?;
+        self.watcher
+            .notify(AccessKind::Write, addr, &data.to_le_bytes());
+        Ok(())
+    }
+
+    pub fn try_read_u8(&self, addr: &u64) -> Result<u8, AccessError> {
+        let data = self
+            .memory
+            .try_read_u8(&self.translate(addr, 1)?)
+            .map_err(|e| e.at(addr))?;
+        self.watcher
+            .notify(AccessKind::Read, addr, &data.to_le_bytes());
+        Ok(data)
+    }
+
+    //sized accesses in memory byte order, converted by endian.rs
+    fn le_write_u16(&self, addr: &u64, data: u16) -> Result<(), AccessError> {
+        if !self.check_writable(addr, 2)? {
+            return Ok(());
+        }
+        if self.split_unaligned(addr, 2) {
+            split_write(
+                addr,
+                2,
+                data as u64,
+                |a, s| self.check_chunk(a, s),
+                |_, a, s, d| self.write_chunk(a, s, d),
+            )?;
+            self.notify(AccessKind::Write, addr, &data.to_le_bytes());
+            return Ok(());
+        }
+        self.memory
+            .try_write_u16(&self.translate(addr, 2)?, data)
+            .map_err(|e| e.at(addr))?;
+        self.watcher
+            .notify(AccessKind::Write, addr, &data.to_le_bytes());
+        Ok(())
+    }
+
+    fn le_read_u16(&self, addr: &u64) -> Result<u16, AccessError> {
+        if self.split_unaligned(addr, 2) {
+            let data = split_read(addr, 2, |a, s| self.read_chunk(a, s))? as u16;
+            self.notify(AccessKind::Read, addr, &data.to_le_bytes());
+            return Ok(data);
+        }
+        let data = self
+            .memory
+            .try_read_u16(&self.translate(addr, 2)?)
+            .map_err(|e| e.at(addr))?;
+        self.watcher
+            .notify(AccessKind::Read, addr, &data.to_le_bytes());
+        Ok(data)
+    }
+
+    fn le_write_u32(&self, addr: &u64, data: u32) -> Result<(), AccessError> {
+        if !self.check_writable(addr, 4)? {
+            return Ok(());
+        }
+        if self.split_unaligned(addr, 4) {
+            split_write(
+                addr,
+                4,
+                data as u64,
+                |a, s| self.check_chunk(a, s),
+                |_, a, s, d| self.write_chunk(a, s, d),
+            )?;
+            self.notify(AccessKind::Write, addr, &data.to_le_bytes());
+            return Ok(());
+        }
+        self.memory
+            .try_write_u32(&self.translate(addr, 4)?, data)
+            .map_err(|e| e.at(addr))?;
+        self.watcher
+            .notify(AccessKind::Write, addr, &data.to_le_bytes());
+        Ok(())
+    }
+
+    fn le_read_u32(&self, addr: &u64) -> Result<u32, AccessError> {
+        if self.split_unaligned(addr, 4) {
+            let data = split_read(addr, 4, |a, s| self.read_chunk(a, s))? as u32;
+            self.notify(AccessKind::Read, addr, &data.to_le_bytes());
+            return Ok(data);
+        }
+        let data = self
+            .memory
+            .try_read_u32(&self.translate(addr, 4)?)
+            .map_err(|e| e.at(addr))?;
+        self.watcher
+            .notify(AccessKind::Read, addr, &data.to_le_bytes());
+        Ok(data)
+    }
+
+    fn le_write_u64(&self, addr: &u64, data: u64) -> Result<(), AccessError> {
+        if !self.check_writable(addr, 8)? {
+            return Ok(());
+        }
+        if self.split_unaligned(addr, 8) {
+            split_write(
+                addr,
+                8,
+                data,
+                |a, s| self.check_chunk(a, s),
+                |_, a, s, d| self.write_chunk(a, s, d),
+            )?;
+            self.notify(AccessKind::Write, addr, &data.to_le_bytes());
+            return Ok(());
+        }
+        self.memory
+            .try_write_u64(&self.translate(addr, 8)?, data)
+            .map_err(|e| e.at(addr))?;
+        self.watcher
+            .notify(AccessKind::Write, addr, &data.to_le_bytes());
+        Ok(())
+    }
+
+    fn le_read_u64(&self, addr: &u64) -> Result<u64, AccessError> {
+        if self.split_unaligned(addr, 8) {
+            let data = split_read(addr, 8, |a, s| self.read_chunk(a, s))?;
+            self.notify(AccessKind::Read, addr, &data.to_le_bytes());
+            return Ok(data);
+        }
+        let data = self
+            .memory
+            .try_read_u64(&self.translate(addr, 8)?)
+            .map_err(|e| e.at(addr))?;
+        self.watcher
+            .notify(AccessKind::Read, addr, &data.to_le_bytes());
+        Ok(data)
+    }
+
+    pub fn try_write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        if !self.check_writable(addr, data.len())? {
+            return Ok(data.len());
+        }
+        let size = self
+            .memory
+            .try_write_bytes(&self.translate(addr, data.len())?, data)
+            .map_err(|e| e.at(addr))?;
+        self.watcher.notify(AccessKind::Write, addr, data);
+        Ok(size)
+    }
+
+    pub fn try_read_bytes(&self, addr: &u64, data: &mut [u8]) -> Result<usize, AccessError> {
+        let size = self
+            .memory
+            .try_read_bytes(&self.translate(addr, data.len())?, data)
+            .map_err(|e| e.at(addr))?;
+        self.watcher.notify(AccessKind::Read, addr, data);
+        Ok(size)
+    }
 }
 
 impl U8Access for Region {
     fn write(&self, addr: &u64, data: u8) {
-        if let Some(ref a) = self.translate(addr, 1) {
-            U8Access::write(&self.memory, a, data)
-        } else {
-            U8Access::write(&self.memory, addr, data)
-        }
+        self.try_write_u8(addr, data)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
     fn read(&self, addr: &u64) -> u8 {
-        if let Some(ref a) = self.translate(addr, 1) {
-            U8Access::read(&self.memory, a)
-        } else {
-            U8Access::read(&self.memory, addr)
-        }
+        self.try_read_u8(addr).unwrap_or_else(|e| panic!("{}", e))
     }
 }
 
+//keep the String error for device errors
 impl BytesAccess for Region {
     fn write(&self, addr: &u64, data: &[u8]) -> Result<usize, String> {
-        if let Some(ref a) = self.translate(addr, data.len()) {
-            BytesAccess::write(&self.memory, a, data)
-        } else {
-            BytesAccess::write(&self.memory, addr, data)
+        match self.try_write_bytes(addr, data) {
+            Err(AccessError::DeviceError(_, msg)) => Err(msg),
+            res => Ok(res.unwrap_or_else(|e| panic!("{}", e))),
         }
     }
 
     fn read(&self, addr: &u64, data: &mut [u8]) -> Result<usize, String> {
-        if let Some(ref a) = self.translate(addr, data.len()) {
-            BytesAccess::read(&self.memory, a, data)
-        } else {
-            BytesAccess::read(&self.memory, addr, data)
+        match self.try_read_bytes(addr, data) {
+            Err(AccessError::DeviceError(_, msg)) => Err(msg),
+            res => Ok(res.unwrap_or_else(|e| panic!("{}", e))),
         }
     }
 }
 
 impl U16Access for Region {
     fn write(&self, addr: &u64, data: u16) {
-        if let Some(ref a) = self.translate(addr, 2) {
-            U16Access::write(&self.memory, a, data)
-        } else {
-            U16Access::write(&self.memory, addr, data)
-        }
+        self.try_write_u16(addr, data)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
     fn read(&self, addr: &u64) -> u16 {
-        if let Some(ref a) = self.translate(addr, 2) {
-            U16Access::read(&self.memory, a)
-        } else {
-            U16Access::read(&self.memory, addr)
-        }
+        self.try_read_u16(addr).unwrap_or_else(|e| panic!("{}", e))
     }
 }
 
 impl U32Access for Region {
     fn write(&self, addr: &u64, data: u32) {
-        if let Some(ref a) = self.translate(addr, 4) {
-            U32Access::write(&self.memory, a, data)
-        } else {
-            U32Access::write(&self.memory, addr, data)
-        }
+        self.try_write_u32(addr, data)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
     fn read(&self, addr: &u64) -> u32 {
-        if let Some(ref a) = self.translate(addr, 4) {
-            U32Access::read(&self.memory, a)
-        } else {
-            U32Access::read(&self.memory, addr)
-        }
+        self.try_read_u32(addr).unwrap_or_else(|e| panic!("{}", e))
     }
 }
 
 impl U64Access for Region {
     fn write(&self, addr: &u64, data: u64) {
-        if let Some(ref a) = self.translate(addr, 8) {
-            U64Access::write(&self.memory, a, data)
-        } else {
-            U64Access::write(&self.memory, addr, data)
-        }
+        self.try_write_u64(addr, data)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
     fn read(&self, addr: &u64) -> u64 {
-        if let Some(ref a) = self.translate(addr, 8) {
-            U64Access::read(&self.memory, a)
-        } else {
-            U64Access::read(&self.memory, addr)
-        }
+        self.try_read_u64(addr).unwrap_or_else(|e| panic!("{}", e))
     }
 }
 
 impl SizedAccess for Region {}
 
+//a block which can not be freed is reported and leaked, drop must not panic
 impl Drop for Region {
     fn drop(&mut self) {
-        if let Memory::Block(heap, _) = &self.memory {
+        let freed = if let Memory::Block(heap, _) = &self.memory {
             heap.free(self.info.base)
         } else if let Memory::RootBlock(_) = &self.memory {
             GHEAP.free(self.info.base)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = freed {
+            eprintln!("block {:?} is leaked! {}", self.info, e)
         }
     }
 }
 
 trait Free {
-    fn free(&self, addr: u64);
+    fn free(&self, addr: u64) -> std::result::Result<(), AllocError>;
 }
 
 #[cfg(test)]
@@ -554,30 +855,60 @@ pub struct Heap {
 
 impl Heap {
     pub fn new(memory: &Rc<Region>) -> Rc<Heap> {
-        Rc::new(Heap {
+        Heap::with_policy(memory, Policy::FirstFit)
+    }
+
+    pub fn with_policy(memory: &Rc<Region>, policy: Policy) -> Rc<Heap> {
+        let heap = Rc::new(Heap {
             memory: Rc::clone(memory),
-            allocator: RefCell::new(Allocator::new(memory.info.base, memory.info.size)),
-        })
+            allocator: RefCell::new(Allocator::with_policy(
+                memory.info.base,
+                memory.info.size,
+                policy,
+            )),
+        });
+        {
+            let mut heaps = memory.heaps.borrow_mut();
+            heaps.retain(|h| h.strong_count() != 0);
+            heaps.push(Rc::downgrade(&heap));
+        }
+        heap
     }
 
     pub fn alloc(
         self: &Rc<Self>,
         size: u64,
         align: u64,
-    ) -> std::result::Result<Rc<Region>, String> {
-        if let Some(info) = self.allocator.borrow_mut().alloc(size, align) {
-            Ok(Region::block(info.base, info.size, self, &self.memory))
-        } else {
-            Err("oom!".to_string())
-        }
+    ) -> std::result::Result<Rc<Region>, AllocError> {
+        let info = self.allocator.borrow_mut().alloc(size, align)?;
+        Ok(Region::block(info.base, info.size, self, &self.memory))
     }
+
+    pub fn alloc_labeled(
+        self: &Rc<Self>,
+        size: u64,
+        align: u64,
+        label: &str,
+    ) -> std::result::Result<Rc<Region>, AllocError> {
+        let info = self.allocator.borrow_mut().alloc_labeled(size, align, label)?;
+        Ok(Region::block(info.base, info.size, self, &self.memory))
+    }
+
     pub fn get_region(&self) -> &Rc<Region> {
         &self.memory
     }
+
+    pub fn stats(&self) -> AllocStats {
+        self.allocator.borrow().stats()
+    }
+
+    pub fn live_blocks(&self) -> Vec<LiveBlock> {
+        self.allocator.borrow().live_blocks()
+    }
 }
 
 impl Free for Heap {
-    fn free(&self, addr: u64) {
+    fn free(&self, addr: u64) -> std::result::Result<(), AllocError> {
         self.allocator.borrow_mut().free(addr)
     }
 }
@@ -595,37 +926,79 @@ pub struct GlobalHeap {
 
 #[cfg(not(test))]
 pub struct GlobalHeap {
-    allocator: LockedAllocator,
+    pub(crate) allocator: LockedAllocator,
 }
 
 impl GlobalHeap {
-    pub fn lazy_alloc(&self, size: u64, align: u64) -> std::result::Result<Rc<Region>, String> {
-        if let Some(info) = self.allocator.alloc(size, align) {
-            Ok(Region::root_block(
-                info.base,
-                info.size,
-                Region::lazy_model(info.base, info.size),
-            ))
-        } else {
-            Err("oom!".to_string())
+    pub fn stats(&self) -> AllocStats {
+        self.allocator.stats()
+    }
+
+    pub fn live_blocks(&self) -> Vec<LiveBlock> {
+        self.allocator.live_blocks()
+    }
+
+    //only before anything is allocated
+    pub fn set_policy(&self, policy: Policy) -> std::result::Result<(), String> {
+        let mut allocator = self.allocator.lock().unwrap();
+        if !allocator.alloced_list().is_empty() {
+            return Err("global heap is in use!".to_string());
         }
+        *allocator = Allocator::with_policy(allocator.info.base, allocator.info.size, policy);
+        Ok(())
     }
 
-    pub fn alloc(&self, size: u64, align: u64) -> std::result::Result<Rc<Region>, String> {
-        if let Some(info) = self.allocator.alloc(size, align) {
-            Ok(Region::root_block(
-                info.base,
-                info.size,
-                Region::model(info.base, info.size),
-            ))
+    pub fn lazy_alloc(&self, size: u64, align: u64) -> std::result::Result<Rc<Region>, AllocError> {
+        let info = self.allocator.alloc(size, align)?;
+        Ok(Region::root_block(
+            info.base,
+            info.size,
+            Region::lazy_model(info.base, info.size),
+        ))
+    }
+
+    pub fn alloc(&self, size: u64, align: u64) -> std::result::Result<Rc<Region>, AllocError> {
+        let info = self.allocator.alloc(size, align)?;
+        Ok(Region::root_block(
+            info.base,
+            info.size,
+            Region::model(info.base, info.size),
+        ))
+    }
+
+    //if size is 0, file length is used
+    pub fn mmap_alloc(
+        &self,
+        file_name: &str,
+        size: u64,
+        align: u64,
+        mode: MmapMode,
+    ) -> std::result::Result<Rc<Region>, AllocError> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(mode == MmapMode::Shared)
+            .open(file_name)
+            .map_err(|e| AllocError::Backing(format!("can not open {}! {}", file_name, e)))?;
+        let size = if size == 0 {
+            file.metadata()
+                .map_err(|e| AllocError::Backing(e.to_string()))?
+                .len()
         } else {
-            Err("oom!".to_string())
+            size
+        };
+        let info = self.allocator.alloc(size, align)?;
+        match Region::mmap_model(info.base, info.size, &file, mode) {
+            Ok(model) => Ok(Region::root_block(info.base, info.size, model)),
+            Err(e) => {
+                self.allocator.free(info.base)?;
+                Err(AllocError::Backing(e))
+            }
         }
     }
 }
 
 impl Free for GlobalHeap {
-    fn free(&self, addr: u64) {
+    fn free(&self, addr: u64) -> std::result::Result<(), AllocError> {
         self.allocator.free(addr)
     }
 }
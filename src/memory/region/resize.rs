@@ -0,0 +1,102 @@
+use super::*;
+
+//blocks are resized and split in place, so they must not be shared.
+//the block is kept unchanged on failure
+impl Heap {
+    //blocks of other heaps are reported by invalid
+    fn block_mut<'a>(
+        self: &Rc<Self>,
+        region: &'a mut Rc<Region>,
+        invalid: fn(u64) -> AllocError,
+    ) -> std::result::Result<&'a mut Region, AllocError> {
+        let base = region.info.base;
+        match &region.memory {
+            Memory::Block(heap, _) if Rc::ptr_eq(heap, self) => {}
+            _ => return Err(invalid(base)),
+        }
+        Rc::get_mut(region).ok_or(AllocError::Shared(base))
+    }
+
+    //a block at a fixed address
+    pub fn reserve(
+        self: &Rc<Self>,
+        base: u64,
+        size: u64,
+    ) -> std::result::Result<Rc<Region>, AllocError> {
+        let info = self.allocator.borrow_mut().reserve(base, size)?;
+        Ok(Region::block(info.base, info.size, self, &self.memory))
+    }
+
+    //resized in place if the allocator can, otherwise moved with the content copied
+    pub fn realloc(
+        self: &Rc<Self>,
+        region: &mut Rc<Region>,
+        size: u64,
+        align: u64,
+    ) -> std::result::Result<(), AllocError> {
+        let block = self.block_mut(region, AllocError::InvalidRealloc)?;
+        let info = block.info;
+        if align_down(info.base, align) == info.base
+            && self.allocator.borrow_mut().resize(info.base, size) == Ok(true)
+        {
+            block.info.size = size;
+            return Ok(());
+        }
+        let label = self
+            .allocator
+            .borrow()
+            .live_block(info.base)
+            .and_then(|b| b.label.clone());
+        let new = match label {
+            Some(label) => self.alloc_labeled(size, align, &label),
+            None => self.alloc(size, align),
+        }?;
+        let mut data = vec![0u8; std::cmp::min(info.size, size) as usize];
+        self.memory
+            .try_read_bytes(&info.base, &mut data)
+            .and_then(|_| self.memory.try_write_bytes(&new.info.base, &data))
+            .map_err(|e| AllocError::Backing(e.to_string()))?;
+        *region = new;
+        Ok(())
+    }
+
+    //free [base, base + size) of the block, the block keeps the part below the range if any,
+    //the part above is returned as a new block if the block keeps the other part.
+    //ranges out of the block, and the whole block which is freed by dropping it, are invalid
+    pub fn free_range(
+        self: &Rc<Self>,
+        region: &mut Rc<Region>,
+        base: u64,
+        size: u64,
+    ) -> std::result::Result<Option<Rc<Region>>, AllocError> {
+        let block = self.block_mut(region, AllocError::InvalidFree)?;
+        let info = block.info;
+        let end = info.base + info.size;
+        if base < info.base
+            || base.checked_add(size).map_or(true, |e| e > end)
+            || (base == info.base && size == info.size)
+        {
+            return Err(AllocError::InvalidFree(base));
+        }
+        self.allocator.borrow_mut().free_range(base, size)?;
+        let upper = MemInfo {
+            base: base + size,
+            size: end - base - size,
+        };
+        if base == info.base {
+            block.info = upper;
+            return Ok(None);
+        }
+        block.info.size = base - info.base;
+        if upper.size == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(Region::block(
+                upper.base,
+                upper.size,
+                self,
+                &self.memory,
+            )))
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use super::*;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WritePolicy {
+    Writable,
+    //report AccessError::ReadOnly
+    Fault,
+    //drop writes silently
+    Ignore,
+}
+
+//write policies a read only mapping can have
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RomPolicy {
+    Fault,
+    Ignore,
+}
+
+impl From<RomPolicy> for WritePolicy {
+    fn from(policy: RomPolicy) -> WritePolicy {
+        match policy {
+            RomPolicy::Fault => WritePolicy::Fault,
+            RomPolicy::Ignore => WritePolicy::Ignore,
+        }
+    }
+}
+
+impl Memory {
+    fn backdoor_write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        match self {
+            Memory::Block(_, region) => region.backdoor_write_bytes(addr, data),
+            Memory::RootBlock(region) => region.backdoor_write_bytes(addr, data),
+            Memory::Remap(remap) => remap.region.backdoor_write_bytes(addr, data),
+            Memory::Bridge(bridge) => bridge.backdoor_write_bytes(addr, data),
+            Memory::Iommu(iommu) => iommu.backdoor_write_bytes(addr, data),
+            _ => self.try_write_bytes(addr, data),
+        }
+    }
+}
+
+impl Region {
+    //boot roms and fuse arrays, a view of memory which can only be written by the backdoor
+    pub fn read_only(base: u64, memory: &Rc<Region>, policy: RomPolicy) -> Rc<Region> {
+        let region = Region::remap(base, memory);
+        region.set_write_policy(policy.into());
+        region
+    }
+
+    pub fn write_policy(&self) -> WritePolicy {
+        self.write_policy.get()
+    }
+
+    //the policy also applies to writes through remaps and heap blocks of this region
+    pub fn set_write_policy(&self, policy: WritePolicy) {
+        self.write_policy.set(policy)
+    }
+
+    //Ok(false) if the write should be dropped, out of range writes are not
+    pub(super) fn check_writable(&self, addr: &u64, size: usize) -> Result<bool, AccessError> {
+        match self.write_policy.get() {
+            WritePolicy::Writable => Ok(true),
+            WritePolicy::Fault => {
+                self.translate(addr, size)?;
+                Err(AccessError::ReadOnly(*addr))
+            }
+            WritePolicy::Ignore => {
+                self.translate(addr, size)?;
+                Ok(false)
+            }
+        }
+    }
+
+    //privileged write for loaders, bypasses write policies and watchpoints
+    pub fn backdoor_write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        self.memory
+            .backdoor_write_bytes(&self.translate(addr, data.len())?, data)
+            .map_err(|e| e.at(addr))
+    }
+}
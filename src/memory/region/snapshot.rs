@@ -0,0 +1,334 @@
+use super::*;
+use crate::memory::model::{LAZY_PAGE_MASK, LAZY_PAGE_SHIFT};
+use std::io;
+use std::io::Read;
+
+//payloads of models are copied through buffers of this size, not as a whole
+const STATE_CHUNK: usize = 0x10000;
+
+//optional state save/load of IO devices, enabled by derive_io(.., Snapshot)
+pub trait IOSnapshot {
+    fn save(&self, w: &mut dyn Write) -> io::Result<()>;
+    fn load(&self, r: &mut dyn Read) -> io::Result<()>;
+}
+
+//all integers are little-endian, bytes and strings are prefixed by u64 length
+pub fn put_u8(w: &mut dyn Write, v: u8) -> io::Result<()> {
+    w.write_all(&[v])
+}
+
+pub fn put_u32(w: &mut dyn Write, v: u32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+pub fn put_u64(w: &mut dyn Write, v: u64) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+pub fn put_bytes(w: &mut dyn Write, v: &[u8]) -> io::Result<()> {
+    put_u64(w, v.len() as u64)?;
+    w.write_all(v)
+}
+
+pub fn put_str(w: &mut dyn Write, v: &str) -> io::Result<()> {
+    put_bytes(w, v.as_bytes())
+}
+
+pub fn get_u8(r: &mut dyn Read) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+pub fn get_u32(r: &mut dyn Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub fn get_u64(r: &mut dyn Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub fn get_bytes(r: &mut dyn Read) -> io::Result<Vec<u8>> {
+    let len = get_u64(r)?;
+    let mut buf = vec![];
+    //don't trust len before the data is really there
+    r.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    Ok(buf)
+}
+
+pub fn get_str(r: &mut dyn Read) -> io::Result<String> {
+    String::from_utf8(get_bytes(r)?).map_err(|e| invalid(e.to_string()))
+}
+
+pub(crate) fn invalid<T: Into<String>>(msg: T) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+const STATE_MODEL: u8 = 0;
+const STATE_LAZY_MODEL: u8 = 1;
+const STATE_MMAP_MODEL: u8 = 2;
+const STATE_IO: u8 = 3;
+
+impl Memory {
+    fn state_kind(&self) -> Option<u8> {
+        match self {
+            Memory::Model(_) => Some(STATE_MODEL),
+            Memory::LazyModel(_) => Some(STATE_LAZY_MODEL),
+            Memory::MmapModel(_) => Some(STATE_MMAP_MODEL),
+            Memory::IO(io) if io.snapshot().is_some() => Some(STATE_IO),
+            _ => None,
+        }
+    }
+}
+
+impl LazyModel {
+    //(offset, data) of allocated pages inside [base, base + size), in address order
+    fn save(&self, info: &MemInfo, w: &mut dyn Write) -> io::Result<()> {
+        let pages = self.inner.borrow();
+        let mut numbers = pages.keys().copied().collect::<Vec<_>>();
+        numbers.sort_unstable();
+        for n in numbers {
+            let start = std::cmp::max(n << LAZY_PAGE_SHIFT, info.base);
+            let end = std::cmp::min((n + 1) << LAZY_PAGE_SHIFT, info.base + info.size);
+            if start >= end {
+                continue;
+            }
+            let page_offset = (start & LAZY_PAGE_MASK) as usize;
+            put_u64(w, start - info.base)?;
+            put_bytes(
+                w,
+                &pages[&n][page_offset..page_offset + (end - start) as usize],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn load(&self, info: &MemInfo, mut payload: &[u8]) -> io::Result<()> {
+        self.inner.borrow_mut().clear();
+        while !payload.is_empty() {
+            let offset = get_u64(&mut payload)?;
+            let data = get_bytes(&mut payload)?;
+            if offset + data.len() as u64 > info.size {
+                return Err(invalid(format!("lazy page @{:#x} out of range!", offset)));
+            }
+            BytesAccess::write(self, &(info.base + offset), &data).map_err(invalid)?;
+        }
+        Ok(())
+    }
+}
+
+impl Region {
+    //the region which holds the data at last
+    pub(crate) fn leaf(&self) -> &Region {
+        match &self.memory {
+            Memory::Block(_, region) => region.leaf(),
+            Memory::RootBlock(region) => region.leaf(),
+            Memory::Remap(remap) => remap.region.leaf(),
+            _ => self,
+        }
+    }
+
+    //self and all the regions it is built on, outermost first
+    pub(crate) fn chain(&self) -> Vec<&Region> {
+        let mut chain = vec![self];
+        loop {
+            let next = match &chain.last().unwrap().memory {
+                Memory::Block(_, region) => region.deref(),
+                Memory::RootBlock(region) => region.deref(),
+                Memory::Remap(remap) => remap.region.deref(),
+                _ => break,
+            };
+            chain.push(next);
+        }
+        chain
+    }
+
+    //live heaps created on this region
+    pub(crate) fn heaps(&self) -> Vec<Rc<Heap>> {
+        self.heaps
+            .borrow()
+            .iter()
+            .filter_map(|h| h.upgrade())
+            .collect()
+    }
+
+    pub(crate) fn has_state(&self) -> bool {
+        self.memory.state_kind().is_some()
+    }
+
+    //kind, size and payload, only for leaf regions with state.
+    //payloads of models are streamed in STATE_CHUNK pieces, the format is the one of put_bytes
+    pub(crate) fn save_state(&self, w: &mut dyn Write) -> io::Result<()> {
+        let kind = self
+            .memory
+            .state_kind()
+            .ok_or_else(|| invalid(format!("{} has no state!", self.get_type())))?;
+        put_u8(w, kind)?;
+        put_u64(w, self.info.size)?;
+        match &self.memory {
+            Memory::Model(model) => {
+                put_u64(w, self.info.size)?;
+                for chunk in model.inner.borrow().chunks(STATE_CHUNK) {
+                    w.write_all(chunk)?
+                }
+                Ok(())
+            }
+            Memory::MmapModel(model) => {
+                put_u64(w, self.info.size)?;
+                let mut buf = vec![0u8; STATE_CHUNK];
+                let mut offset = 0;
+                while offset < self.info.size {
+                    let len = std::cmp::min(STATE_CHUNK as u64, self.info.size - offset) as usize;
+                    BytesAccess::read(model, &(self.info.base + offset), &mut buf[..len])
+                        .map_err(invalid)?;
+                    w.write_all(&buf[..len])?;
+                    offset += len as u64;
+                }
+                Ok(())
+            }
+            Memory::LazyModel(model) => {
+                let mut payload: Vec<u8> = vec![];
+                model.save(&self.info, &mut payload)?;
+                put_bytes(w, &payload)
+            }
+            Memory::IO(io) => {
+                let mut payload: Vec<u8> = vec![];
+                io.snapshot().unwrap().save(&mut payload)?;
+                put_bytes(w, &payload)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn load_state(&self, r: &mut dyn Read) -> io::Result<()> {
+        let kind = get_u8(r)?;
+        let size = get_u64(r)?;
+        if Some(kind) != self.memory.state_kind() || size != self.info.size {
+            return Err(invalid(format!(
+                "state kind {} size {:#x} mismatch with {} {:?}!",
+                kind,
+                size,
+                self.get_type(),
+                self.info
+            )));
+        }
+        match &self.memory {
+            Memory::Model(model) => {
+                if get_u64(r)? != size {
+                    return Err(invalid("model payload size mismatch!"));
+                }
+                for chunk in model.inner.borrow_mut().chunks_mut(STATE_CHUNK) {
+                    r.read_exact(chunk)?
+                }
+            }
+            Memory::LazyModel(model) => model.load(&self.info, &get_bytes(r)?)?,
+            Memory::MmapModel(model) => {
+                if get_u64(r)? != size {
+                    return Err(invalid("mmap payload size mismatch!"));
+                }
+                let mut buf = vec![0u8; STATE_CHUNK];
+                let mut offset = 0;
+                while offset < size {
+                    let len = std::cmp::min(STATE_CHUNK as u64, size - offset) as usize;
+                    r.read_exact(&mut buf[..len])?;
+                    BytesAccess::write(model, &(self.info.base + offset), &buf[..len])
+                        .map_err(invalid)?;
+                    offset += len as u64;
+                }
+            }
+            Memory::IO(io) => io.snapshot().unwrap().load(&mut get_bytes(r)?.as_slice())?,
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+}
+
+impl Heap {
+    //blocks are saved as offsets, so the heap region may be re-allocated at another base
+    pub(crate) fn save_state(&self, w: &mut dyn Write) -> io::Result<()> {
+        let allocator = self.allocator.borrow();
+        let base = allocator.info.base;
+        put_u64(w, allocator.info.size)?;
+        for blocks in [allocator.free_list(), allocator.alloced_list()].iter() {
+            put_u64(w, blocks.len() as u64)?;
+            for b in blocks {
+                put_u64(w, b.base - base)?;
+                put_u64(w, b.size)?;
+            }
+        }
+        let labels = allocator
+            .live_blocks()
+            .into_iter()
+            .filter_map(|b| b.label.map(|label| (b.info.base, label)))
+            .collect::<Vec<_>>();
+        put_u64(w, labels.len() as u64)?;
+        for (addr, label) in labels {
+            put_u64(w, addr - base)?;
+            put_str(w, &label)?;
+        }
+        Ok(())
+    }
+
+    //live blocks must be the ones in the saved state, or blocks only alive in the snapshot would
+    //have no region to free them and the others could not be freed later.
+    //labels are saved since version 4 of the space snapshot
+    pub(crate) fn load_state(&self, r: &mut dyn Read, version: u32) -> io::Result<()> {
+        let mut allocator = self.allocator.borrow_mut();
+        let info = allocator.info;
+        if get_u64(r)? != info.size {
+            return Err(invalid(format!("heap {:?} size mismatch!", info)));
+        }
+        let mut lists: Vec<Vec<MemInfo>> = vec![];
+        for _ in 0..2 {
+            let len = get_u64(r)?;
+            let mut blocks = vec![];
+            for _ in 0..len {
+                let base = get_u64(r)? + info.base;
+                let size = get_u64(r)?;
+                if base + size > info.base + info.size {
+                    return Err(invalid(format!("heap block @{:#x} out of range!", base)));
+                }
+                blocks.push(MemInfo { base, size });
+            }
+            lists.push(blocks)
+        }
+        let alloced = lists.pop().unwrap();
+        let free = lists.pop().unwrap();
+        let labels = if version < 4 {
+            allocator
+                .live_blocks()
+                .into_iter()
+                .filter_map(|b| b.label.map(|label| (b.info.base, label)))
+                .collect::<Vec<_>>()
+        } else {
+            let mut labels = vec![];
+            for _ in 0..get_u64(r)? {
+                labels.push((get_u64(r)? + info.base, get_str(r)?))
+            }
+            labels
+        };
+        let live = allocator.alloced_list();
+        let same = |a: &MemInfo, b: &MemInfo| a.base == b.base && a.size == b.size;
+        if let Some(b) = live.iter().find(|b| !alloced.iter().any(|a| same(a, b))) {
+            return Err(invalid(format!(
+                "live heap block {:?} is not in snapshot!",
+                b
+            )));
+        }
+        if let Some(b) = alloced.iter().find(|b| !live.iter().any(|a| same(a, b))) {
+            return Err(invalid(format!(
+                "heap block {:?} in snapshot is not live!",
+                b
+            )));
+        }
+        allocator.restore_labeled(free, alloced, labels);
+        Ok(())
+    }
+}
@@ -8,9 +8,8 @@ fn region_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .collect::<Vec<MemInfo>>()
     );
     let region = heap.alloc(9, 1).unwrap();
@@ -23,18 +22,16 @@ fn region_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .collect::<Vec<MemInfo>>()
     );
     assert_ne!(
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -43,9 +40,8 @@ fn region_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -54,9 +50,8 @@ fn region_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -66,9 +61,8 @@ fn region_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -78,9 +72,8 @@ fn region_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -89,9 +82,8 @@ fn region_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -100,9 +92,8 @@ fn region_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -137,3 +128,669 @@ fn region_access() {
         );
     }
 }
+
+#[test]
+fn lazy_region_access() {
+    let heap = &GHEAP;
+    let region = heap.lazy_alloc(0x3000, 0x1000).unwrap();
+    let base = region.info.base;
+    assert_eq!(U64Access::read(region.deref(), &(base + 0x2000)), 0);
+    U64Access::write(region.deref(), &(base + 0xff8), 0x5a5aa5a5aaaa5555);
+    U8Access::write(region.deref(), &(base + 0x1000), 0xab);
+    assert_eq!(U32Access::read(region.deref(), &(base + 0xffc)), 0x5a5aa5a5);
+    assert_eq!(U8Access::read(region.deref(), &(base + 0x1000)), 0xab);
+
+    let data = (0..0x1800).map(|i| i as u8).collect::<Vec<u8>>();
+    BytesAccess::write(region.deref(), &(base + 0x800), &data).unwrap();
+    let mut read_back = vec![0xff; 0x2000];
+    BytesAccess::read(region.deref(), &(base + 0x800), &mut read_back).unwrap();
+    assert_eq!(&read_back[..0x1800], data.as_slice());
+    assert!(read_back[0x1800..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn mmap_region_access() {
+    let file_name = std::env::temp_dir()
+        .join(format!("ts_mmap_region_access_{}", std::process::id()))
+        .to_str()
+        .unwrap()
+        .to_string();
+    let content = (0..0x1800).map(|i| i as u8).collect::<Vec<u8>>();
+    std::fs::write(&file_name, &content).unwrap();
+    let heap = &GHEAP;
+    {
+        let region = heap
+            .mmap_alloc(&file_name, 0x4000, 0x1000, MmapMode::Private)
+            .unwrap();
+        let remap = Region::remap(0x80000000, &region);
+        assert_eq!(
+            U32Access::read(remap.deref(), &(remap.info.base + 4)),
+            0x07060504
+        );
+        assert_eq!(
+            U64Access::read(remap.deref(), &(remap.info.base + 0x3000)),
+            0
+        );
+        U32Access::write(remap.deref(), &remap.info.base, 0xdeadbeef);
+        assert_eq!(
+            U32Access::read(region.deref(), &region.info.base),
+            0xdeadbeef
+        );
+    }
+    assert_eq!(std::fs::read(&file_name).unwrap(), content);
+    {
+        let region = heap.mmap_alloc(&file_name, 0, 1, MmapMode::Shared).unwrap();
+        assert_eq!(region.info.size, 0x1800);
+        let heap1 = Heap::new(&region);
+        let block = heap1.alloc(8, 8).unwrap();
+        U64Access::write(block.deref(), &block.info.base, 0x5a5aa5a5aaaa5555);
+    }
+    assert_eq!(
+        &std::fs::read(&file_name).unwrap()[..8],
+        &0x5a5aa5a5aaaa5555u64.to_le_bytes()
+    );
+    std::fs::remove_file(&file_name).unwrap();
+}
+
+#[test]
+fn region_try_access() {
+    let heap = &GHEAP;
+    let region = heap.alloc(16, 8).unwrap();
+    let remap = Region::remap_partial(0x10000000, &region, 8, 8);
+    let base = region.info.base;
+    assert_eq!(
+        region.try_write_u32(&(base + 2), 0),
+        Err(AccessError::Unaligned(base + 2, 4))
+    );
+    assert_eq!(
+        region.try_read_u64(&(base + 16)),
+        Err(AccessError::OutOfRange(base + 16, 8))
+    );
+    assert_eq!(
+        remap.try_read_u16(&0x10000007),
+        Err(AccessError::OutOfRange(0x10000007, 2))
+    );
+    let mut data = [0u8; 4];
+    assert_eq!(
+        remap.try_read_bytes(&0x10000006, &mut data),
+        Err(AccessError::OutOfRange(0x10000006, 4))
+    );
+    remap.try_write_u64(&0x10000000, 0xdeadbeef).unwrap();
+    assert_eq!(region.try_read_u64(&(base + 8)), Ok(0xdeadbeef));
+}
+
+#[test]
+fn region_unaligned_split() {
+    let heap = &GHEAP;
+    let region = heap.alloc(16, 8).unwrap();
+    let base = region.info.base;
+    assert_eq!(
+        region.try_write_u64(&(base + 3), 0),
+        Err(AccessError::Unaligned(base + 3, 8))
+    );
+    region.set_unaligned_policy(UnalignedPolicy::Split);
+    U64Access::write(region.deref(), &(base + 3), 0x0807060504030201);
+    assert_eq!(U8Access::read(region.deref(), &(base + 3)), 0x01);
+    assert_eq!(U8Access::read(region.deref(), &(base + 10)), 0x08);
+    assert_eq!(
+        U64Access::read(region.deref(), &(base + 3)),
+        0x0807060504030201
+    );
+    assert_eq!(U32Access::read(region.deref(), &(base + 5)), 0x06050403);
+    assert_eq!(U16Access::read(region.deref(), &(base + 9)), 0x0807);
+    assert_eq!(
+        region.try_read_u32(&(base + 14)),
+        Err(AccessError::OutOfRange(base + 16, 2))
+    );
+    assert_eq!(
+        region.try_write_u32(&(base + 14), 0xffffffff),
+        Err(AccessError::OutOfRange(base + 16, 2))
+    );
+    assert_eq!(U16Access::read(region.deref(), &(base + 14)), 0);
+}
+
+#[test]
+fn region_watchpoint() {
+    let heap = &GHEAP;
+    let region = heap.alloc(16, 8).unwrap();
+    let remap = Region::remap(0x10000000, &region);
+    let base = region.info.base;
+    let hits = Rc::new(RefCell::new(vec![]));
+    let hits_cb = hits.clone();
+    let id = region.add_watchpoint(base + 4, 4, WatchKind::Write, move |access| {
+        hits_cb
+            .borrow_mut()
+            .push((access.addr, access.data.to_vec()))
+    });
+    U32Access::write(remap.deref(), &0x10000004, 0xdeadbeef);
+    U32Access::write(remap.deref(), &0x10000008, 0);
+    U8Access::read(region.deref(), &(base + 4));
+    BytesAccess::write(region.deref(), &(base + 6), &[1, 2]).unwrap();
+    assert_eq!(
+        *hits.borrow(),
+        vec![
+            (base + 4, vec![0xef, 0xbe, 0xad, 0xde]),
+            (base + 6, vec![1, 2])
+        ]
+    );
+    //a split access is seen once along the chain
+    remap.set_unaligned_policy(UnalignedPolicy::Split);
+    remap.le_write_u32(&0x10000006, 0x04030201).unwrap();
+    assert_eq!(hits.borrow()[2], (base + 6, vec![1, 2, 3, 4]));
+    assert!(region.remove_watchpoint(id));
+    assert!(!region.remove_watchpoint(id));
+    U8Access::write(region.deref(), &(base + 4), 0);
+    assert_eq!(hits.borrow().len(), 3);
+}
+
+#[terminus_spaceport_proc_macros::derive_io(Bytes, Atomic)]
+struct AtomicDev(RefCell<[u8; 8]>);
+
+impl BytesAccess for AtomicDev {
+    fn write(&self, addr: &u64, data: &[u8]) -> Result<usize, String> {
+        let offset = *addr as usize;
+        self.0.borrow_mut()[offset..offset + data.len()].copy_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn read(&self, addr: &u64, data: &mut [u8]) -> Result<usize, String> {
+        let offset = *addr as usize;
+        data.copy_from_slice(&self.0.borrow()[offset..offset + data.len()]);
+        Ok(data.len())
+    }
+}
+
+impl AtomicAccess for AtomicDev {}
+
+#[terminus_spaceport_proc_macros::derive_io(U32)]
+struct PlainDev;
+
+impl U32Access for PlainDev {
+    fn write(&self, _: &u64, _: u32) {}
+
+    fn read(&self, _: &u64) -> u32 {
+        0
+    }
+}
+
+#[test]
+fn region_atomic() {
+    let heap = &GHEAP;
+    for region in [heap.alloc(16, 8).unwrap(), heap.lazy_alloc(16, 8).unwrap()].iter() {
+        let remap = Region::remap(0x10000000, region);
+        let base = region.info.base;
+        remap.try_write_u64(&0x10000000, 5).unwrap();
+        assert_eq!(remap.try_amo_u64(&0x10000000, AmoOp::Add, 3), Ok(5));
+        assert_eq!(remap.try_amo_u64(&0x10000000, AmoOp::Swap, 1), Ok(8));
+        assert_eq!(
+            region.try_amo_u32(&(base + 8), AmoOp::Or, 0xf0f0_0000),
+            Ok(0)
+        );
+        assert_eq!(
+            region.try_amo_u32(&(base + 8), AmoOp::Min, 1),
+            Ok(0xf0f0_0000)
+        );
+        assert_eq!(region.try_read_u32(&(base + 8)), Ok(0xf0f0_0000));
+        assert_eq!(
+            region.try_amo_u32(&(base + 8), AmoOp::MaxU, 1),
+            Ok(0xf0f0_0000)
+        );
+        assert_eq!(
+            region.try_amo_u32(&(base + 8), AmoOp::Max, 1),
+            Ok(0xf0f0_0000)
+        );
+        assert_eq!(region.try_amo_u32(&(base + 8), AmoOp::Xor, 3), Ok(1));
+        assert_eq!(region.try_amo_u32(&(base + 8), AmoOp::And, 6), Ok(2));
+        assert_eq!(region.try_amo_u32(&(base + 8), AmoOp::MinU, 7), Ok(2));
+        assert_eq!(region.try_compare_exchange_u64(&base, 0, 7), Ok(1));
+        assert_eq!(region.try_read_u64(&base), Ok(1));
+        assert_eq!(remap.try_compare_exchange_u64(&0x10000000, 1, 7), Ok(1));
+        assert_eq!(region.try_read_u64(&base), Ok(7));
+        //never split
+        region.set_unaligned_policy(UnalignedPolicy::Split);
+        assert_eq!(
+            region.try_amo_u32(&(base + 2), AmoOp::Add, 1),
+            Err(AccessError::Unaligned(base + 2, 4))
+        );
+        assert_eq!(
+            remap.try_compare_exchange_u64(&0x10000004, 0, 1),
+            Err(AccessError::Unaligned(0x10000004, 8))
+        );
+        assert_eq!(
+            remap.try_amo_u64(&0x10000010, AmoOp::Add, 1),
+            Err(AccessError::OutOfRange(0x10000010, 8))
+        );
+    }
+
+    let dev = Region::remap(
+        0x20000000,
+        &Region::io(0, 8, Box::new(AtomicDev(RefCell::new([0; 8])))),
+    );
+    assert_eq!(dev.try_amo_u32(&0x20000004, AmoOp::Add, 2), Ok(0));
+    assert_eq!(dev.try_compare_exchange_u32(&0x20000004, 2, 9), Ok(2));
+    let mut data = [0u8; 4];
+    dev.try_read_bytes(&0x20000004, &mut data).unwrap();
+    assert_eq!(u32::from_le_bytes(data), 9);
+    let plain = Region::io(0, 8, Box::new(PlainDev));
+    assert!(matches!(
+        plain.try_amo_u32(&4, AmoOp::Swap, 1),
+        Err(AccessError::DeviceError(4, _))
+    ));
+    assert!(AtomicAccess::amo_u32(plain.deref(), &4, AmoOp::Swap, 1).is_err());
+}
+
+#[test]
+fn region_host_slice() {
+    let heap = &GHEAP;
+    let region = heap.alloc(16, 8).unwrap();
+    let remap = Region::remap_partial(0x10000000, &region, 8, 8);
+    let base = region.info.base;
+    assert!(remap.is_host_mappable());
+    U64Access::write(region.deref(), &(base + 8), 0x0807060504030201);
+    {
+        let s = remap.host_slice(&0x10000002, 4).unwrap();
+        assert_eq!(&*s, &[3, 4, 5, 6]);
+        //reading while borrowed is fine
+        assert_eq!(U8Access::read(region.deref(), &(base + 8)), 1);
+        assert!(matches!(
+            region.host_slice_mut(&base, 1),
+            Err(AccessError::DeviceError(_, _))
+        ));
+    }
+    remap.host_slice_mut(&0x10000004, 4).unwrap()[..].fill(0xff);
+    assert_eq!(
+        U64Access::read(region.deref(), &(base + 8)),
+        0xffffffff04030201
+    );
+    assert_eq!(
+        remap.host_slice(&0x10000004, 8).err(),
+        Some(AccessError::OutOfRange(0x10000004, 8))
+    );
+
+    let lazy = heap.lazy_alloc(16, 8).unwrap();
+    assert!(!lazy.is_host_mappable());
+    assert_eq!(
+        lazy.host_slice(&lazy.info.base, 8).err(),
+        Some(AccessError::NotMappable(lazy.info.base))
+    );
+    let io = Region::remap(0x20000000, &Region::io(0, 8, Box::new(PlainDev)));
+    assert!(!io.is_host_mappable());
+    assert_eq!(
+        io.host_slice_mut(&0x20000004, 4).err(),
+        Some(AccessError::NotMappable(0x20000004))
+    );
+
+    let file_name = std::env::temp_dir()
+        .join(format!("ts_host_slice_{}", std::process::id()))
+        .to_str()
+        .unwrap()
+        .to_string();
+    std::fs::write(&file_name, &[1, 2, 3, 4]).unwrap();
+    {
+        let mmap = heap
+            .mmap_alloc(&file_name, 0x1000, 0x1000, MmapMode::Private)
+            .unwrap();
+        assert!(mmap.is_host_mappable());
+        let mut s = mmap.host_slice_mut(&mmap.info.base, 8).unwrap();
+        assert_eq!(&s[..], &[1, 2, 3, 4, 0, 0, 0, 0]);
+        s[4] = 5;
+        assert!(mmap.host_slice(&mmap.info.base, 1).is_err());
+        std::mem::drop(s);
+        assert_eq!(U8Access::read(mmap.deref(), &(mmap.info.base + 4)), 5);
+    }
+    std::fs::remove_file(&file_name).unwrap();
+}
+
+#[test]
+fn region_endian() {
+    let heap = &GHEAP;
+    for region in [heap.alloc(32, 8).unwrap(), heap.lazy_alloc(32, 8).unwrap()].iter() {
+        let base = region.info.base;
+        let be = Region::remap(0x10000000, region);
+        be.set_endian(Endian::Big);
+        assert_eq!(be.endian(), Endian::Big);
+        U32Access::write(be.deref(), &0x10000000, 0x11223344);
+        let mut bytes = [0u8; 4];
+        region.try_read_bytes(&base, &mut bytes).unwrap();
+        assert_eq!(bytes, [0x11, 0x22, 0x33, 0x44]);
+        assert_eq!(U32Access::read(region.deref(), &base), 0x44332211);
+        assert_eq!(
+            region.try_read_u32_endian(&base, Endian::Big),
+            Ok(0x11223344)
+        );
+        assert_eq!(
+            be.try_read_u16_endian(&0x10000002, Endian::Little),
+            Ok(0x4433)
+        );
+        //the outermost view decides
+        let le = Region::remap(0x20000000, &be);
+        assert_eq!(le.try_read_u32(&0x20000000), Ok(0x44332211));
+        be.try_write_u64(&0x10000008, 0x0102030405060708).unwrap();
+        assert_eq!(region.try_read_u64(&(base + 8)), Ok(0x0807060504030201));
+
+        //split accesses are composed in the byte order of the view
+        be.set_unaligned_policy(UnalignedPolicy::Split);
+        be.try_write_u32(&0x10000013, 0xaabbccdd).unwrap();
+        let mut bytes = [0u8; 4];
+        region.try_read_bytes(&(base + 0x13), &mut bytes).unwrap();
+        assert_eq!(bytes, [0xaa, 0xbb, 0xcc, 0xdd]);
+        assert_eq!(be.try_read_u32(&0x10000013), Ok(0xaabbccdd));
+
+        //atomics
+        be.try_write_u32(&0x10000018, 0xff).unwrap();
+        assert_eq!(be.try_amo_u32(&0x10000018, AmoOp::Add, 1), Ok(0xff));
+        assert_eq!(be.try_read_u32(&0x10000018), Ok(0x100));
+        assert_eq!(be.try_amo_u32(&0x10000018, AmoOp::Or, 1), Ok(0x100));
+        assert_eq!(region.try_read_u32(&(base + 0x18)), Ok(0x01010000));
+        assert_eq!(be.try_amo_u32(&0x10000018, AmoOp::MaxU, 0x200), Ok(0x101));
+        assert_eq!(
+            be.try_compare_exchange_u32(&0x10000018, 0x200, 0x12345678),
+            Ok(0x200)
+        );
+        assert_eq!(region.try_read_u32(&(base + 0x18)), Ok(0x78563412));
+        assert_eq!(
+            region.try_amo_u64_endian(&base, AmoOp::Swap, 1, Endian::Big),
+            Ok(0x1122334400000000)
+        );
+    }
+}
+
+#[test]
+fn region_read_only() {
+    let heap = &GHEAP;
+    let ram = heap.alloc(16, 8).unwrap();
+    let base = ram.info.base;
+    let rom = Region::read_only(0x10000000, &ram, RomPolicy::Fault);
+    let fuse = Region::read_only(0x20000000, &ram, RomPolicy::Ignore);
+    assert_eq!(rom.write_policy(), WritePolicy::Fault);
+    assert_eq!(
+        rom.try_write_u32(&0x10000000, 1),
+        Err(AccessError::ReadOnly(0x10000000))
+    );
+    assert_eq!(
+        rom.try_amo_u64(&0x10000008, AmoOp::Add, 1),
+        Err(AccessError::ReadOnly(0x10000008))
+    );
+    assert_eq!(
+        rom.try_write_u8(&0x10000010, 1),
+        Err(AccessError::OutOfRange(0x10000010, 1))
+    );
+    assert_eq!(fuse.try_write_bytes(&0x20000000, &[1; 16]), Ok(16));
+    assert_eq!(fuse.try_write_u64(&0x20000008, 1), Ok(()));
+    assert_eq!(fuse.try_amo_u32(&0x20000000, AmoOp::Swap, 1), Ok(0));
+    assert_eq!(ram.try_read_u64(&base), Ok(0));
+    assert_eq!(
+        fuse.try_write_u16(&0x20000010, 1),
+        Err(AccessError::OutOfRange(0x20000010, 2))
+    );
+
+    //the backdoor
+    rom.backdoor_write_bytes(&0x10000000, &[1, 2, 3, 4])
+        .unwrap();
+    assert_eq!(rom.try_read_u32(&0x10000000), Ok(0x04030201));
+    assert_eq!(fuse.try_read_u32(&0x20000000), Ok(0x04030201));
+    assert_eq!(
+        rom.backdoor_write_bytes(&0x1000000c, &[0; 8]),
+        Err(AccessError::OutOfRange(0x1000000c, 8))
+    );
+
+    //views of a read only region are read only as well, but not the region behind
+    let alias = Region::remap(0x30000000, &rom);
+    assert_eq!(
+        alias.try_write_u8(&0x30000004, 1),
+        Err(AccessError::ReadOnly(0x30000004))
+    );
+    alias.backdoor_write_bytes(&0x30000004, &[5]).unwrap();
+    assert_eq!(U8Access::read(ram.deref(), &(base + 4)), 5);
+    U8Access::write(ram.deref(), &(base + 5), 6);
+    assert_eq!(U8Access::read(rom.deref(), &0x10000005), 6);
+    //host slices of read only regions and their views
+    assert_eq!(
+        rom.host_slice_mut(&0x10000000, 4).err(),
+        Some(AccessError::ReadOnly(0x10000000))
+    );
+    assert_eq!(
+        alias.host_slice_mut(&0x30000000, 4).err(),
+        Some(AccessError::ReadOnly(0x30000000))
+    );
+    assert_eq!(
+        fuse.host_slice_mut(&0x20000000, 4).err(),
+        Some(AccessError::NotMappable(0x20000000))
+    );
+    assert_eq!(rom.host_slice(&0x10000000, 4).unwrap()[0], 1);
+    rom.set_write_policy(WritePolicy::Writable);
+    alias.host_slice_mut(&0x30000000, 1).unwrap()[0] = 0xaa;
+    assert_eq!(U8Access::read(ram.deref(), &base), 0xaa);
+    alias.try_write_u8(&0x30000006, 7).unwrap();
+    assert_eq!(U8Access::read(ram.deref(), &(base + 6)), 7);
+}
+
+#[test]
+fn heap_policy() {
+    let region = GHEAP.alloc(0x100, 0x100).unwrap();
+    let heap = Heap::with_policy(&region, Policy::Buddy);
+    let a = heap.alloc(0x10, 1).unwrap();
+    let b = heap.alloc(0x11, 1).unwrap();
+    assert_eq!(b.info.base - a.info.base, 0x20);
+    assert_eq!(b.info.size, 0x11);
+    b.try_write_u64(&b.info.base, 0x1234).unwrap();
+    assert_eq!(region.try_read_u64(&b.info.base).unwrap(), 0x1234);
+    std::mem::drop(a);
+    std::mem::drop(b);
+    assert_eq!(
+        heap.allocator.borrow().free_list(),
+        vec![MemInfo {
+            base: region.info.base,
+            size: 0x100
+        }]
+    );
+}
+
+#[test]
+fn heap_stats() {
+    let region = GHEAP.alloc(0x100, 1).unwrap();
+    let heap = Heap::new(&region);
+    let _a = heap.alloc_labeled(0x80, 1, "buffer").unwrap();
+    let b = heap.alloc(0x40, 1).unwrap();
+    std::mem::drop(b);
+    assert_eq!(
+        heap.alloc(0x100, 1).err(),
+        Some(AllocError::OutOfMemory(0x100, 1))
+    );
+    let stats = heap.stats().to_string();
+    assert!(stats.starts_with("alloced 0x80 bytes in 1 blocks"), "{}", stats);
+    let blocks = heap.live_blocks();
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0].label.as_deref(), Some("buffer"));
+    assert_eq!(heap.stats().high_water, 0xc0);
+}
+
+#[test]
+fn heap_realloc() {
+    let region = GHEAP.alloc(0x100, 1).unwrap();
+    let heap = Heap::new(&region);
+    let base = region.info.base;
+    let hole = heap.reserve(base + 0x80, 0x10).unwrap();
+    assert_eq!(
+        heap.reserve(base + 0x88, 0x10).err(),
+        Some(AllocError::NotFree(base + 0x88, 0x10))
+    );
+    let mut a = heap.alloc(0x10, 1).unwrap();
+    a.try_write_u64(&base, 0xdead_beef).unwrap();
+    //in place
+    heap.realloc(&mut a, 0x20, 1).unwrap();
+    assert_eq!(a.info, MemInfo { base, size: 0x20 });
+    let b = heap.alloc(0x10, 1).unwrap();
+    //moved with the content
+    heap.realloc(&mut a, 0x40, 1).unwrap();
+    assert_eq!(a.info.base, base + 0x30);
+    assert_eq!(a.try_read_u64(&a.info.base).unwrap(), 0xdead_beef);
+    //shared blocks can not be changed
+    let shared = a.clone();
+    assert_eq!(
+        heap.realloc(&mut a, 0x80, 1),
+        Err(AllocError::Shared(base + 0x30))
+    );
+    std::mem::drop(shared);
+    assert_eq!(
+        heap.realloc(&mut a, 0x80, 1),
+        Err(AllocError::OutOfMemory(0x80, 1))
+    );
+    assert_eq!(a.info.size, 0x40);
+    std::mem::drop(b);
+    std::mem::drop(hole);
+    let mut other = Heap::new(&GHEAP.alloc(0x10, 1).unwrap())
+        .alloc(0x10, 1)
+        .unwrap();
+    assert_eq!(
+        heap.realloc(&mut other, 0x8, 1),
+        Err(AllocError::InvalidRealloc(other.info.base))
+    );
+    assert_eq!(
+        heap.alloc(0x200, 1).err(),
+        Some(AllocError::OutOfMemory(0x200, 1))
+    );
+}
+
+#[test]
+fn heap_free_range() {
+    let region = GHEAP.alloc(0x100, 1).unwrap();
+    let heap = Heap::new(&region);
+    let base = region.info.base;
+    let mut a = heap.alloc(0x40, 1).unwrap();
+    let upper = heap.free_range(&mut a, base + 0x10, 0x10).unwrap().unwrap();
+    assert_eq!(a.info, MemInfo { base, size: 0x10 });
+    assert_eq!(
+        upper.info,
+        MemInfo {
+            base: base + 0x20,
+            size: 0x20
+        }
+    );
+    assert_eq!(heap.alloc(0x10, 1).unwrap().info.base, base + 0x10);
+    let mut upper = upper;
+    assert!(heap.free_range(&mut upper, base + 0x30, 0x20).is_err());
+    assert!(heap.free_range(&mut upper, base + 0x20, 0x20).is_err());
+    assert!(heap
+        .free_range(&mut upper, base + 0x20, 0x10)
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        upper.info,
+        MemInfo {
+            base: base + 0x30,
+            size: 0x10
+        }
+    );
+    std::mem::drop(a);
+    std::mem::drop(upper);
+    assert_eq!(heap.stats().alloced_bytes, 0);
+    assert_eq!(
+        heap.allocator.borrow().free_list(),
+        vec![MemInfo { base, size: 0x100 }]
+    );
+}
+
+#[test]
+fn iommu_view() {
+    use crate::memory::attr::Perm;
+    let memory = GHEAP.alloc(0x2000, 0x1000).unwrap();
+    let pa = memory.info.base;
+    let view = Region::iommu(0, 0x10000, &memory);
+    //two pages mapped in reverse order
+    view.iommu_map(0x4000, pa + 0x1000, 0x1000, Perm::RW)
+        .unwrap();
+    view.iommu_map(0x5000, pa, 0x1000, Perm::R).unwrap();
+    assert!(view.iommu_map(0x4800, pa, 0x1000, Perm::RW).is_err());
+    view.try_write_u32(&0x4000, 0x12345678).unwrap();
+    assert_eq!(memory.try_read_u32(&(pa + 0x1000)).unwrap(), 0x12345678);
+    //crossing the windows
+    memory.try_write_u8(&pa, 0xab).unwrap();
+    view.try_write_u8(&0x4fff, 0xcd).unwrap();
+    assert_eq!(view.try_read_u16(&0x4fff).unwrap(), 0xabcd);
+    assert!(matches!(
+        view.host_slice(&0x4ffe, 4),
+        Err(AccessError::NotMappable(0x4ffe))
+    ));
+    let faults = Rc::new(RefCell::new(vec![]));
+    let f = Rc::clone(&faults);
+    view.set_iommu_fault_handler(move |kind, iova, size| f.borrow_mut().push((kind, iova, size)));
+    assert_eq!(
+        view.try_write_u16(&0x5000, 0),
+        Err(AccessError::Fault(0x5000, AccessKind::Write))
+    );
+    assert_eq!(
+        view.try_read_bytes(&0x5ffe, &mut [0u8; 4]),
+        Err(AccessError::Fault(0x5ffe, AccessKind::Read))
+    );
+    //the rom loader still writes through it
+    view.backdoor_write_bytes(&0x5000, &[0x5a]).unwrap();
+    assert_eq!(memory.try_read_u8(&pa).unwrap(), 0x5a);
+    //page tables in memory: one u64 entry of pa | perm per page of iova 0x8000 - 0xa000
+    view.iommu_unmap(0x4000).unwrap();
+    let table = Rc::clone(&memory);
+    let base = pa + 0x1800;
+    view.set_iommu_walker(move |iova| {
+        if !(0x8000..0xa000).contains(&iova) {
+            return None;
+        }
+        let pte = table
+            .try_read_u64(&(base + (iova - 0x8000) / 0x1000 * 8))
+            .ok()?;
+        Some(IommuEntry {
+            iova: iova & !0xfff,
+            pa: pte & !0xfff,
+            size: 0x1000,
+            perm: Perm::from_bits(pte as u8),
+        })
+    });
+    memory
+        .try_write_u64(&base, pa | Perm::RW.bits() as u64)
+        .unwrap();
+    assert_eq!(view.try_read_u8(&0x8000).unwrap(), 0x5a);
+    assert_eq!(
+        view.try_read_u8(&0x9000),
+        Err(AccessError::Fault(0x9000, AccessKind::Read))
+    );
+    assert_eq!(
+        view.try_read_u8(&0x4000),
+        Err(AccessError::Fault(0x4000, AccessKind::Read))
+    );
+    assert_eq!(
+        view.try_amo_u32(&0x8004, AmoOp::Add, 2).unwrap(),
+        memory.try_read_u32(&(pa + 4)).unwrap() - 2
+    );
+    assert_eq!(
+        *faults.borrow(),
+        vec![
+            (AccessKind::Write, 0x5000, 2),
+            (AccessKind::Read, 0x5ffe, 4),
+            (AccessKind::Read, 0x9000, 1),
+            (AccessKind::Read, 0x4000, 1)
+        ]
+    );
+}
+
+#[test]
+fn region_state_chunks() {
+    let region = GHEAP.alloc(0x18000, 8).unwrap();
+    let base = region.info.base;
+    region.try_write_bytes(&(base + 0xfffe), &[1, 2, 3, 4]).unwrap();
+    region.try_write_u8(&(base + 0x17fff), 5).unwrap();
+    let mut state: Vec<u8> = vec![];
+    region.leaf().save_state(&mut state).unwrap();
+    //kind, size and the length prefixed payload
+    assert_eq!(state.len(), 1 + 8 + 8 + 0x18000);
+
+    region.try_write_bytes(&(base + 0xfffe), &[0; 4]).unwrap();
+    region.try_write_u8(&(base + 0x17fff), 0).unwrap();
+    region.leaf().load_state(&mut state.as_slice()).unwrap();
+    let mut data = [0u8; 4];
+    region.try_read_bytes(&(base + 0xfffe), &mut data).unwrap();
+    assert_eq!(data, [1, 2, 3, 4]);
+    assert_eq!(region.try_read_u8(&(base + 0x17fff)), Ok(5));
+    assert!(region
+        .leaf()
+        .load_state(&mut &state[..state.len() - 1])
+        .is_err());
+}
@@ -0,0 +1,151 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::rc::Rc;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Both,
+}
+
+impl WatchKind {
+    fn matches(&self, kind: AccessKind) -> bool {
+        match self {
+            WatchKind::Read => kind == AccessKind::Read,
+            WatchKind::Write => kind == AccessKind::Write,
+            WatchKind::Both => true,
+        }
+    }
+}
+
+//data is in little-endian for U16Access..U64Access, its length is the access size
+#[derive(Debug)]
+pub struct Access<'a> {
+    pub kind: AccessKind,
+    pub addr: u64,
+    pub data: &'a [u8],
+}
+
+impl<'a> Display for Access<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {:#016x} [{}] {:02x?}",
+            match self.kind {
+                AccessKind::Read => "R",
+                AccessKind::Write => "W",
+            },
+            self.addr,
+            self.data.len(),
+            self.data
+        )
+    }
+}
+
+struct Watchpoint {
+    id: usize,
+    base: u64,
+    size: u64,
+    kind: WatchKind,
+    callback: Rc<dyn Fn(&Access)>,
+}
+
+//callbacks may add or remove watchpoints, e.g. one-shot watchpoints removing themselves
+pub(crate) struct Watcher {
+    active: Cell<bool>,
+    next_id: Cell<usize>,
+    points: RefCell<Vec<Watchpoint>>,
+    trace: RefCell<Option<Box<dyn Write>>>,
+}
+
+impl Watcher {
+    pub(crate) fn new() -> Watcher {
+        Watcher {
+            active: Cell::new(false),
+            next_id: Cell::new(0),
+            points: RefCell::new(vec![]),
+            trace: RefCell::new(None),
+        }
+    }
+
+    fn update_active(&self) {
+        self.active
+            .set(!self.points.borrow().is_empty() || self.trace.borrow().is_some())
+    }
+
+    pub(crate) fn add<F: Fn(&Access) + 'static>(
+        &self,
+        base: u64,
+        size: u64,
+        kind: WatchKind,
+        callback: F,
+    ) -> usize {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.points.borrow_mut().push(Watchpoint {
+            id,
+            base,
+            size,
+            kind,
+            callback: Rc::new(callback),
+        });
+        self.update_active();
+        id
+    }
+
+    pub(crate) fn remove(&self, id: usize) -> bool {
+        let removed = {
+            let mut points = self.points.borrow_mut();
+            let len = points.len();
+            points.retain(|p| p.id != id);
+            points.len() != len
+        };
+        self.update_active();
+        removed
+    }
+
+    pub(crate) fn set_trace(&self, sink: Option<Box<dyn Write>>) {
+        *self.trace.borrow_mut() = sink;
+        self.update_active();
+    }
+
+    pub(crate) fn notify(&self, kind: AccessKind, addr: &u64, data: &[u8]) {
+        if !self.active.get() {
+            return;
+        }
+        let access = Access {
+            kind,
+            addr: *addr,
+            data,
+        };
+        //a broken sink stops the trace instead of the simulation
+        let failed = match self.trace.borrow_mut().as_mut() {
+            Some(sink) => writeln!(sink, "{}", access).err(),
+            None => None,
+        };
+        if let Some(e) = failed {
+            eprintln!("trace is stopped! {}", e);
+            self.set_trace(None);
+        }
+        let end = *addr + data.len() as u64;
+        let callbacks = self
+            .points
+            .borrow()
+            .iter()
+            .filter(|p| p.kind.matches(kind) && *addr < p.base + p.size && p.base < end)
+            .map(|p| Rc::clone(&p.callback))
+            .collect::<Vec<_>>();
+        for callback in callbacks {
+            callback(&access)
+        }
+    }
+}
@@ -1,11 +1,23 @@
 extern crate intrusive_collections;
 
-use crate::memory::region::{BytesAccess, Region, U16Access, U32Access, U64Access, U8Access};
+use crate::memory::attr::{AccessType, Attr};
+use crate::memory::region::snapshot::{
+    get_str, get_u32, get_u64, get_u8, invalid, put_str, put_u32, put_u64, put_u8,
+};
+use crate::memory::region::{
+    check_align, split_read, split_write, Access, AccessError, AccessKind, AmoOp, Endian, Heap,
+    HostSlice, HostSliceMut, Region, UnalignedPolicy, WatchKind, Watcher,
+};
 use intrusive_collections::rbtree::RBTree;
 use intrusive_collections::{intrusive_adapter, Bound, KeyAdapter, RBTreeLink};
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::cmp::min;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::ops::Deref;
 use std::rc::Rc;
 
@@ -13,6 +25,7 @@ struct SpaceElem {
     link: RBTreeLink,
     key: u64,
     value: (String, Rc<Region>),
+    enabled: Cell<bool>,
 }
 
 intrusive_adapter!(Adapter = Box<SpaceElem>:SpaceElem {link:RBTreeLink});
@@ -24,10 +37,52 @@ impl<'a> KeyAdapter<'a> for Adapter {
     }
 }
 
+//a mapping which may overlap the others, the highest priority one covering an address decodes it.
+//regions added without a priority are at priority 0
+struct Overlay {
+    name: String,
+    region: Rc<Region>,
+    attr: Attr,
+    priority: i32,
+    enabled: Cell<bool>,
+}
+
+impl Overlay {
+    fn covers(&self, addr: &u64) -> bool {
+        self.enabled.get()
+            && *addr >= self.region.info.base
+            && *addr - self.region.info.base < self.region.info.size
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Decoded<'a> {
+    Region(&'a SpaceElem),
+    Overlay(&'a Overlay),
+}
+
+impl<'a> Decoded<'a> {
+    fn region(&self) -> &'a Rc<Region> {
+        match self {
+            Decoded::Region(e) => &e.value.1,
+            Decoded::Overlay(o) => &o.region,
+        }
+    }
+
+    fn priority(&self) -> i32 {
+        match self {
+            Decoded::Region(_) => 0,
+            Decoded::Overlay(o) => o.priority,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     Overlap(String, String),
     Renamed(String, String),
+    Snapshot(String),
+    NotFound(String),
 }
 
 impl Display for Error {
@@ -35,9 +90,119 @@ impl Display for Error {
         match self {
             Error::Overlap(s1, s2) => write!(f, "Overlap!{}:{}", s1, s2),
             Error::Renamed(s1, s2) => write!(f, "Renamed!{}:{}", s1, s2),
+            Error::Snapshot(s) => write!(f, "Snapshot!{}", s),
+            Error::NotFound(s) => write!(f, "NotFound!region {}", s),
         }
     }
 }
+
+//bus errors of the guest are reported by address, e.g. holes, accesses running off the end of a
+//region, device errors, writes to roms, iommu faults, misaligned or denied accesses
+pub(crate) fn unmapped_addr(e: AccessError) -> u64 {
+    match e {
+        AccessError::Unaligned(addr, _)
+        | AccessError::OutOfRange(addr, _)
+        | AccessError::Unmapped(addr)
+        | AccessError::DeviceError(addr, _)
+        | AccessError::NotMappable(addr)
+        | AccessError::Denied(addr, _, _)
+        | AccessError::ReadOnly(addr)
+        | AccessError::Fault(addr, _) => addr,
+    }
+}
+
+//the exclusive end of [addr, addr + len), ranges wrapping around the address space are out of range
+pub(crate) fn range_end(addr: &u64, len: usize) -> Result<u64, AccessError> {
+    addr.checked_add(len as u64)
+        .ok_or(AccessError::OutOfRange(*addr, len))
+}
+
+//an access of read_u8..read_bytes and write_u8..write_bytes failed, whatever the AccessError is
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BusFault {
+    pub kind: AccessKind,
+    pub addr: u64,
+    pub size: usize,
+}
+
+impl Display for BusFault {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "BusFault!{:?} @{:#x} size {}",
+            self.kind, self.addr, self.size
+        )
+    }
+}
+
+type BusFaultHandler = Rc<dyn Fn(&BusFault) -> Result<u64, ()>>;
+
+//what the default slave does with faults of read_u8..read_bytes and write_u8..write_bytes,
+//the try_ accesses always report them
+#[derive(Clone)]
+pub enum UnmappedPolicy {
+    //report Err(addr), which the C API panics on
+    Panic,
+    //the value is sized per access: read_u64 returns it as is, narrower reads return its low bytes
+    //and read_bytes fills with its low byte, so use u64::MAX rather than 0xffff_ffff for all ones.
+    //writes are dropped, the first fault at each address is logged
+    Fill(u64),
+    //Ok(value) completes the access as Fill(value) without logging, Err raises a bus error
+    //reported as Err(addr), which the C API completes with 0
+    Callback(BusFaultHandler),
+}
+
+//LR/SC reservation of each hart, kept as the granule aligned address
+struct Reservations {
+    granule: u64,
+    set: RefCell<Vec<(usize, u64)>>,
+}
+
+impl Reservations {
+    fn new(granule: u64) -> Reservations {
+        assert!(
+            granule.is_power_of_two(),
+            "reservation granule {:#x} should be power of 2!",
+            granule
+        );
+        Reservations {
+            granule,
+            set: RefCell::new(vec![]),
+        }
+    }
+
+    fn granule_of(&self, addr: &u64) -> u64 {
+        *addr & !(self.granule - 1)
+    }
+
+    //a hart holds at most one reservation
+    fn reserve(&self, hart: usize, addr: &u64) {
+        let granule = self.granule_of(addr);
+        let mut set = self.set.borrow_mut();
+        set.retain(|(h, _)| *h != hart);
+        set.push((hart, granule))
+    }
+
+    fn take(&self, hart: usize) -> Option<u64> {
+        let mut set = self.set.borrow_mut();
+        let i = set.iter().position(|(h, _)| *h == hart)?;
+        Some(set.swap_remove(i).1)
+    }
+
+    //drop all reservations on granules overlapped with [addr, addr + len)
+    fn invalidate(&self, addr: &u64, len: usize) {
+        let mut set = self.set.borrow_mut();
+        if set.is_empty() || len == 0 {
+            return;
+        }
+        let start = self.granule_of(addr);
+        let end = addr.saturating_add(len as u64);
+        set.retain(|(_, g)| *g < start || *g >= end)
+    }
+}
+
+const DEFAULT_RESERVATION_GRANULE: u64 = 64;
+
 struct RegionCPtr(*const Box<Rc<Region>>);
 
 unsafe impl Send for RegionCPtr {}
@@ -47,159 +212,684 @@ unsafe impl Sync for RegionCPtr {}
 //Space should be an owner of Regions
 pub struct Space {
     regions: RBTree<Adapter>,
+    //name -> base
+    names: HashMap<String, u64>,
     //for ffi free
     ptrs: HashMap<String, Vec<RegionCPtr>>,
+    unaligned: UnalignedPolicy,
+    endian: Option<Endian>,
+    //attribute runs base -> (size, attr), covering all regions at priority 0
+    attrs: BTreeMap<u64, (u64, Attr)>,
+    //in descending priority, there are only a few of them, e.g. boot roms and default slaves
+    overlays: Vec<Overlay>,
+    watcher: Watcher,
+    reservations: Reservations,
+    unmapped: UnmappedPolicy,
+    last_fault: Cell<Option<BusFault>>,
+    //addresses already logged by UnmappedPolicy::Fill
+    logged: RefCell<HashSet<u64>>,
 }
 
 impl Space {
     pub fn new() -> Space {
         Space {
             regions: RBTree::new(Adapter::default()),
+            names: HashMap::new(),
             ptrs: HashMap::new(),
+            unaligned: UnalignedPolicy::Fault,
+            endian: None,
+            attrs: BTreeMap::new(),
+            overlays: vec![],
+            watcher: Watcher::new(),
+            reservations: Reservations::new(DEFAULT_RESERVATION_GRANULE),
+            unmapped: UnmappedPolicy::Panic,
+            last_fault: Cell::new(None),
+            logged: RefCell::new(HashSet::new()),
         }
     }
 
     pub fn add_region(&mut self, name: &str, region: &Rc<Region>) -> Result<Rc<Region>, Error> {
-        let check = || {
-            if let Some(_) = self.regions.iter().find(|a| a.value.0 == name) {
-                return Err(Error::Renamed(
-                    name.to_string(),
-                    format!("region name {} has existed!", name),
-                ));
-            }
-            if let Some(v) = self.regions.iter().find(|a| {
-                region.info.base >= a.value.1.info.base
-                    && region.info.base < a.value.1.info.base + a.value.1.info.size
-                    || region.info.base + region.info.size - 1 >= a.value.1.info.base
-                        && region.info.base + region.info.size - 1
-                            < a.value.1.info.base + a.value.1.info.size
-                    || a.value.1.info.base >= region.info.base
-                        && a.value.1.info.base < region.info.base + region.info.size
-                    || a.value.1.info.base + a.value.1.info.size - 1 >= region.info.base
-                        && a.value.1.info.base + a.value.1.info.size - 1
-                            < region.info.base + region.info.size
-            }) {
-                return Err(Error::Overlap(
-                    v.value.0.to_string(),
-                    format!(
-                        "region [{} : {:?}] is overlapped with [{} : {:?}]!",
-                        name,
-                        region.deref().info,
-                        v.value.0,
-                        v.value.1.deref().info
-                    ),
-                ));
-            }
-            Ok(())
-        };
-        check()?;
-        // self.regions.insert(region.info.base, (name.to_string(), Rc::clone(region)));
+        self.add_region_with_attr(name, region, Attr::default())
+    }
+
+    pub fn add_region_with_attr(
+        &mut self,
+        name: &str,
+        region: &Rc<Region>,
+        attr: Attr,
+    ) -> Result<Rc<Region>, Error> {
+        self.check_name(name)?;
+        if let Some(v) = self.overlapped(region.info.base, region.info.size) {
+            return Err(Error::Overlap(
+                v.value.0.to_string(),
+                format!(
+                    "region [{} : {:?}] is overlapped with [{} : {:?}]!",
+                    name,
+                    region.deref().info,
+                    v.value.0,
+                    v.value.1.deref().info
+                ),
+            ));
+        }
+        self.names.insert(name.to_string(), region.info.base);
         self.regions.insert(Box::new(SpaceElem {
             link: RBTreeLink::new(),
             key: region.info.base,
             value: (name.to_string(), Rc::clone(region)),
+            enabled: Cell::new(true),
         }));
+        self.attrs
+            .insert(region.info.base, (region.info.size, attr));
         Ok(Rc::clone(region))
     }
 
-    pub fn delete_region(&mut self, name: &str) {
-        let mut cursor = self.regions.front_mut();
-        while !cursor.is_null() {
-            if let Some(e) = cursor.get() {
-                if e.value.0 == name {
-                    cursor.remove();
-                    break;
-                }
+    fn check_name(&self, name: &str) -> Result<(), Error> {
+        if self.names.contains_key(name) || self.overlays.iter().any(|o| o.name == name) {
+            Err(Error::Renamed(
+                name.to_string(),
+                format!("region name {} has existed!", name),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    //the region may overlap regions of other priorities, e.g. a boot rom shadowing the dram
+    //at a positive priority, or a default slave behind all the regions at a negative one
+    pub fn add_region_with_priority(
+        &mut self,
+        name: &str,
+        region: &Rc<Region>,
+        attr: Attr,
+        priority: i32,
+    ) -> Result<Rc<Region>, Error> {
+        if priority == 0 {
+            return self.add_region_with_attr(name, region, attr);
+        }
+        self.check_name(name)?;
+        let info = region.info;
+        if let Some(o) = self.overlays.iter().find(|o| {
+            o.priority == priority
+                && info.base < o.region.info.base + o.region.info.size
+                && o.region.info.base < info.base + info.size
+        }) {
+            return Err(Error::Overlap(
+                o.name.to_string(),
+                format!(
+                    "region [{} : {:?}] is overlapped with [{} : {:?}] at priority {}!",
+                    name, info, o.name, o.region.info, priority
+                ),
+            ));
+        }
+        let pos = self
+            .overlays
+            .iter()
+            .position(|o| o.priority < priority)
+            .unwrap_or(self.overlays.len());
+        self.overlays.insert(
+            pos,
+            Overlay {
+                name: name.to_string(),
+                region: Rc::clone(region),
+                attr,
+                priority,
+                enabled: Cell::new(true),
+            },
+        );
+        Ok(Rc::clone(region))
+    }
+
+    //regions at priority 0 never overlap each other, so only the neighbours around base are checked
+    fn overlapped(&self, base: u64, size: u64) -> Option<&SpaceElem> {
+        let pre = self
+            .regions
+            .upper_bound(Bound::Included(&base))
+            .get()
+            .filter(|e| base - e.key < e.value.1.info.size);
+        pre.or_else(|| {
+            self.regions
+                .lower_bound(Bound::Excluded(&base))
+                .get()
+                .filter(|e| e.key - base < size)
+        })
+    }
+
+    pub fn delete_region(&mut self, name: &str) -> Result<(), Error> {
+        if let Some(base) = self.names.remove(name) {
+            let info = self.regions.find_mut(&base).remove().unwrap().value.1.info;
+            let attrs = self
+                .attrs
+                .range(info.base..info.base + info.size)
+                .map(|(base, _)| *base)
+                .collect::<Vec<_>>();
+            for base in attrs {
+                self.attrs.remove(&base);
             }
-            cursor.move_next();
+        } else {
+            let i = self
+                .overlays
+                .iter()
+                .position(|o| o.name == name)
+                .ok_or_else(|| Error::NotFound(name.to_string()))?;
+            self.overlays.remove(i);
         }
         if let Some(ps) = self.ptrs.remove(name) {
             ps.iter()
                 .for_each(|RegionCPtr(ptr)| std::mem::drop(unsafe { (*ptr).read() }))
         }
+        Ok(())
     }
 
     pub fn get_region(&self, name: &str) -> Option<Rc<Region>> {
-        if let Some(v) = self.regions.iter().find_map(|a| {
-            if a.value.0 == name {
-                Some(&a.value.1)
-            } else {
-                None
+        if let Some(base) = self.names.get(name) {
+            return self.regions.find(base).get().map(|e| Rc::clone(&e.value.1));
+        }
+        self.overlays
+            .iter()
+            .find(|o| o.name == name)
+            .map(|o| Rc::clone(&o.region))
+    }
+
+    fn enabled_flag(&self, name: &str) -> Option<&Cell<bool>> {
+        if let Some(base) = self.names.get(name) {
+            return self.regions.find(base).get().map(|e| &e.enabled);
+        }
+        self.overlays
+            .iter()
+            .find(|o| o.name == name)
+            .map(|o| &o.enabled)
+    }
+
+    //remap registers, a disabled region does not decode any address until it is enabled again
+    pub fn set_region_enabled(&self, name: &str, enabled: bool) -> Result<(), Error> {
+        self.enabled_flag(name)
+            .ok_or_else(|| Error::NotFound(name.to_string()))?
+            .set(enabled);
+        Ok(())
+    }
+
+    pub fn region_enabled(&self, name: &str) -> Option<bool> {
+        self.enabled_flag(name).map(|f| f.get())
+    }
+
+    fn region_at(&self, addr: &u64) -> Option<&SpaceElem> {
+        self.regions
+            .upper_bound(Bound::Included(addr))
+            .get()
+            .filter(|e| *addr - e.key < e.value.1.info.size)
+    }
+
+    //the highest priority enabled mapping covering addr
+    fn decode(&self, addr: &u64) -> Option<Decoded<'_>> {
+        let overlay = self.overlays.iter().find(|o| o.covers(addr));
+        match overlay {
+            Some(o) if o.priority > 0 => Some(Decoded::Overlay(o)),
+            _ => self
+                .region_at(addr)
+                .filter(|e| e.enabled.get())
+                .map(Decoded::Region)
+                .or(overlay.map(Decoded::Overlay)),
+        }
+    }
+
+    //the mapping at addr and where it stops decoding [addr, end), at its end or where a mapping
+    //of a higher priority starts
+    fn decode_range(&self, addr: &u64, end: u64) -> Option<(Decoded<'_>, u64)> {
+        let decoded = self.decode(addr)?;
+        let info = decoded.region().info;
+        let mut chunk_end = min(end, info.base + info.size);
+        let priority = decoded.priority();
+        for o in self.overlays.iter().take_while(|o| o.priority > priority) {
+            let base = o.region.info.base;
+            if o.enabled.get() && base > *addr && base < chunk_end {
+                chunk_end = base
+            }
+        }
+        if priority < 0 {
+            let mut cursor = self.regions.lower_bound(Bound::Excluded(addr));
+            while let Some(e) = cursor.get().filter(|e| e.key < chunk_end) {
+                if e.enabled.get() {
+                    chunk_end = e.key;
+                    break;
+                }
+                cursor.move_next();
             }
-        }) {
-            Some(Rc::clone(v))
-        } else {
-            None
         }
+        Some((decoded, chunk_end))
+    }
+
+    //the region decoding all of [addr, addr + size), accesses crossing its end are reported by it
+    fn region_of(&self, addr: &u64, size: usize) -> Result<&Rc<Region>, AccessError> {
+        let end = addr.saturating_add(size as u64);
+        let (decoded, chunk_end) = self
+            .decode_range(addr, end)
+            .ok_or(AccessError::Unmapped(*addr))?;
+        let region = decoded.region();
+        if chunk_end < end && chunk_end < region.info.base + region.info.size {
+            return Err(AccessError::OutOfRange(*addr, size));
+        }
+        Ok(region)
     }
 
     pub fn get_region_by_addr(&self, addr: &u64) -> Result<Rc<Region>, u64> {
-        if let Some(e) = self.regions.upper_bound(Bound::Included(addr)).get() {
-            if *addr < e.value.1.info.base + e.value.1.info.size {
-                Ok(Rc::clone(&e.value.1))
-            } else {
-                Err(*addr)
+        self.decode(addr)
+            .map(|d| Rc::clone(d.region()))
+            .ok_or(*addr)
+    }
+
+    //the range must be inside one region, space watchpoints and reservations are bypassed
+    pub fn host_slice(&self, addr: &u64, len: usize) -> Result<HostSlice<'_>, AccessError> {
+        self.region_of(addr, len)?.host_slice(addr, len)
+    }
+
+    pub fn host_slice_mut(&self, addr: &u64, len: usize) -> Result<HostSliceMut<'_>, AccessError> {
+        self.region_of(addr, len)?.host_slice_mut(addr, len)
+    }
+
+    pub fn unaligned_policy(&self) -> UnalignedPolicy {
+        self.unaligned
+    }
+
+    //UnalignedPolicy::Split also allows accesses crossing adjacent regions
+    pub fn set_unaligned_policy(&mut self, policy: UnalignedPolicy) {
+        self.unaligned = policy
+    }
+
+    pub fn endian(&self) -> Option<Endian> {
+        self.endian
+    }
+
+    //byte order of sized and atomic accesses through this space,
+    //None follows the endianness of the region at the address
+    pub fn set_endian(&mut self, endian: Option<Endian>) {
+        self.endian = endian
+    }
+
+    fn endian_at(&self, addr: &u64) -> Endian {
+        self.decode(addr)
+            .map(|d| d.region().endian())
+            .unwrap_or_default()
+    }
+
+    //the attribute run at addr of a region at priority 0
+    fn attr_run(&self, addr: &u64) -> Option<(u64, Attr)> {
+        match self.attrs.range(..=*addr).next_back() {
+            Some((base, (size, attr))) if *addr < *base + *size => Some((*base + *size, *attr)),
+            _ => None,
+        }
+    }
+
+    pub fn get_attr(&self, addr: &u64) -> Option<Attr> {
+        match self.decode(addr)? {
+            Decoded::Region(_) => self.attr_run(addr).map(|(_, attr)| attr),
+            Decoded::Overlay(o) => Some(o.attr),
+        }
+    }
+
+    //change attributes at runtime, [base, base + size) must be in one region at priority 0,
+    //regions at other priorities keep the attributes they are added with
+    pub fn set_attr(&mut self, base: &u64, size: u64, attr: Attr) -> Result<(), AccessError> {
+        let region = &self
+            .region_at(base)
+            .ok_or(AccessError::Unmapped(*base))?
+            .value
+            .1;
+        let end = match base.checked_add(size) {
+            Some(end) if size != 0 && end <= region.info.base + region.info.size => end,
+            _ => return Err(AccessError::OutOfRange(*base, size as usize)),
+        };
+        self.split_attr(*base);
+        self.split_attr(end);
+        let covered = self
+            .attrs
+            .range(*base..end)
+            .map(|(b, _)| *b)
+            .collect::<Vec<_>>();
+        for b in covered {
+            self.attrs.remove(&b);
+        }
+        self.attrs.insert(*base, (size, attr));
+        Ok(())
+    }
+
+    //make addr the start of a run if it is inside one
+    fn split_attr(&mut self, addr: u64) {
+        if let Some((&base, &(size, attr))) = self.attrs.range(..addr).next_back() {
+            if addr < base + size {
+                self.attrs.insert(base, (addr - base, attr));
+                self.attrs.insert(addr, (base + size - addr, attr));
+            }
+        }
+    }
+
+    //report the first byte of [addr, addr + len) which is unmapped or denied
+    pub fn check_access(
+        &self,
+        addr: &u64,
+        len: usize,
+        kind: AccessType,
+    ) -> Result<(), AccessError> {
+        let end = range_end(addr, len)?;
+        let mut cur = *addr;
+        while cur < end {
+            let (decoded, chunk_end) = self
+                .decode_range(&cur, end)
+                .ok_or(AccessError::Unmapped(cur))?;
+            let (run_end, attr) = match decoded {
+                Decoded::Region(_) => self.attr_run(&cur).ok_or(AccessError::Unmapped(cur))?,
+                Decoded::Overlay(o) => (chunk_end, o.attr),
+            };
+            if !attr.allows(kind) {
+                return Err(AccessError::Denied(cur, kind, attr));
+            }
+            cur = min(run_end, chunk_end);
+        }
+        Ok(())
+    }
+
+    //permission-checked transfers, nothing is accessed if any byte is denied
+    pub fn try_write_bytes_checked(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        self.check_access(addr, data.len(), AccessType::Write)?;
+        self.try_write_bytes(addr, data)
+    }
+
+    pub fn try_read_bytes_checked(
+        &self,
+        addr: &u64,
+        data: &mut [u8],
+    ) -> Result<usize, AccessError> {
+        self.check_access(addr, data.len(), AccessType::Read)?;
+        self.try_read_bytes(addr, data)
+    }
+
+    pub fn try_fetch_bytes(&self, addr: &u64, data: &mut [u8]) -> Result<usize, AccessError> {
+        self.check_access(addr, data.len(), AccessType::Fetch)?;
+        self.try_read_bytes(addr, data)
+    }
+
+    //watch space addresses, use Region::add_watchpoint to watch a region through all its aliases
+    pub fn add_watchpoint<F: Fn(&Access) + 'static>(
+        &self,
+        base: u64,
+        size: u64,
+        kind: WatchKind,
+        callback: F,
+    ) -> usize {
+        self.watcher.add(base, size, kind, callback)
+    }
+
+    pub fn remove_watchpoint(&self, id: usize) -> bool {
+        self.watcher.remove(id)
+    }
+
+    //log every access of this space to sink
+    pub fn set_trace(&self, sink: Option<Box<dyn Write>>) {
+        self.watcher.set_trace(sink)
+    }
+
+    fn split_unaligned(&self, addr: &u64, size: usize) -> bool {
+        self.unaligned == UnalignedPolicy::Split && *addr & (size as u64 - 1) != 0
+    }
+
+    //split accesses in memory byte order, the watchers of the space and of each region touched
+    //see the whole access once
+    fn read_split(&self, addr: &u64, size: usize) -> Result<u64, AccessError> {
+        let mut regions: Vec<&Rc<Region>> = vec![];
+        let data = split_read(addr, size, |a, s| {
+            let region = self.region_of(a, s)?;
+            let data = region.read_chunk(a, s)?;
+            if !regions.iter().any(|r| Rc::ptr_eq(r, region)) {
+                regions.push(region)
+            }
+            Ok(data)
+        })?;
+        self.notify_split(
+            &regions,
+            AccessKind::Read,
+            addr,
+            &data.to_le_bytes()[..size],
+        );
+        Ok(data)
+    }
+
+    fn write_split(&self, addr: &u64, size: usize, data: u64) -> Result<(), AccessError> {
+        let mut regions: Vec<&Rc<Region>> = vec![];
+        split_write(
+            addr,
+            size,
+            data,
+            |a, s| {
+                let region = self.region_of(a, s)?;
+                region.check_chunk(a, s)?;
+                Ok(region)
+            },
+            |region, a, s, d| {
+                region.write_chunk(a, s, d)?;
+                if !regions.iter().any(|r| Rc::ptr_eq(r, region)) {
+                    regions.push(region)
+                }
+                Ok(())
+            },
+        )?;
+        self.reservations.invalidate(addr, size);
+        self.notify_split(
+            &regions,
+            AccessKind::Write,
+            addr,
+            &data.to_le_bytes()[..size],
+        );
+        Ok(())
+    }
+
+    fn notify_split(&self, regions: &[&Rc<Region>], kind: AccessKind, addr: &u64, data: &[u8]) {
+        for region in regions {
+            region.notify(kind, addr, data)
+        }
+        self.watcher.notify(kind, addr, data)
+    }
+
+    pub fn try_write_u8(&self, addr: &u64, data: u8) -> Result<(), AccessError> {
+        self.region_of(addr, 1)?.try_write_u8(addr, data)?;
+        self.reservations.invalidate(addr, 1);
+        self.watcher
+            .notify(AccessKind::Write, addr, &data.to_le_bytes());
+        Ok(())
+    }
+
+    pub fn try_read_u8(&self, addr: &u64) -> Result<u8, AccessError> {
+        let data = self.region_of(addr, 1)?.try_read_u8(addr)?;
+        self.watcher
+            .notify(AccessKind::Read, addr, &data.to_le_bytes());
+        Ok(data)
+    }
+
+    //split [addr, addr + len) into (region, addr, len) chunks, fail at the first unmapped hole
+    fn split_by_regions(
+        &self,
+        addr: &u64,
+        len: usize,
+    ) -> Result<Vec<(&Region, u64, usize)>, AccessError> {
+        let mut chunks = vec![];
+        let end = range_end(addr, len)?;
+        let mut cur = *addr;
+        while cur < end {
+            let (decoded, chunk_end) = self
+                .decode_range(&cur, end)
+                .ok_or(AccessError::Unmapped(cur))?;
+            chunks.push((decoded.region().deref(), cur, (chunk_end - cur) as usize));
+            cur = chunk_end;
+        }
+        Ok(chunks)
+    }
+
+    //report the first unmapped address in [addr, addr + len)
+    pub(crate) fn check_mapped(&self, addr: &u64, len: usize) -> Result<(), u64> {
+        self.split_by_regions(addr, len)
+            .map(|_| ())
+            .map_err(unmapped_addr)
+    }
+
+    //transfers crossing adjacent regions are split, nothing is written if there is a hole
+    pub fn try_write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let end = range_end(addr, data.len())?;
+        let (decoded, chunk_end) = self
+            .decode_range(addr, end)
+            .ok_or(AccessError::Unmapped(*addr))?;
+        let region = decoded.region();
+        let size = if chunk_end == end {
+            region.try_write_bytes(addr, data)?
+        } else {
+            let mut offset: usize = 0;
+            for (region, a, len) in self.split_by_regions(addr, data.len())? {
+                //the chunks already written lose their reservations even if a later one fails
+                if let Err(e) = region.try_write_bytes(&a, &data[offset..offset + len]) {
+                    self.reservations.invalidate(addr, offset);
+                    return Err(e);
+                }
+                offset += len;
             }
+            data.len()
+        };
+        self.reservations.invalidate(addr, data.len());
+        self.watcher.notify(AccessKind::Write, addr, data);
+        Ok(size)
+    }
+
+    //privileged write for loaders, bypasses write policies of regions and watchpoints
+    pub fn backdoor_write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        for (region, a, len) in self.split_by_regions(addr, data.len())? {
+            let offset = (a - *addr) as usize;
+            region.backdoor_write_bytes(&a, &data[offset..offset + len])?;
+        }
+        self.reservations.invalidate(addr, data.len());
+        Ok(data.len())
+    }
+
+    pub fn try_read_bytes(&self, addr: &u64, data: &mut [u8]) -> Result<usize, AccessError> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let end = range_end(addr, data.len())?;
+        let (decoded, chunk_end) = self
+            .decode_range(addr, end)
+            .ok_or(AccessError::Unmapped(*addr))?;
+        let region = decoded.region();
+        let size = if chunk_end == end {
+            region.try_read_bytes(addr, data)?
         } else {
-            Err(*addr)
+            let mut offset: usize = 0;
+            for (region, a, len) in self.split_by_regions(addr, data.len())? {
+                region.try_read_bytes(&a, &mut data[offset..offset + len])?;
+                offset += len;
+            }
+            data.len()
+        };
+        self.watcher.notify(AccessKind::Read, addr, data);
+        Ok(size)
+    }
+
+    //the whole reservation set is dropped
+    pub fn set_reservation_granule(&mut self, granule: u64) {
+        self.reservations = Reservations::new(granule)
+    }
+
+    pub fn unmapped_policy(&self) -> &UnmappedPolicy {
+        &self.unmapped
+    }
+
+    pub fn set_unmapped_policy(&mut self, policy: UnmappedPolicy) {
+        self.unmapped = policy;
+        self.logged.borrow_mut().clear()
+    }
+
+    //the last fault of read_u8..read_bytes and write_u8..write_bytes, whatever the policy is
+    pub fn last_fault(&self) -> Option<BusFault> {
+        self.last_fault.get()
+    }
+
+    pub fn clear_last_fault(&self) {
+        self.last_fault.set(None)
+    }
+
+    //Ok(value) if the policy completes the access
+    fn unmapped(&self, kind: AccessKind, size: usize, e: AccessError) -> Result<u64, u64> {
+        let fault = BusFault {
+            kind,
+            addr: unmapped_addr(e),
+            size,
+        };
+        self.last_fault.set(Some(fault));
+        match &self.unmapped {
+            UnmappedPolicy::Panic => Err(fault.addr),
+            UnmappedPolicy::Fill(value) => {
+                if self.logged.borrow_mut().insert(fault.addr) {
+                    eprintln!("{}, filled with {:#x}", fault, value);
+                }
+                Ok(*value)
+            }
+            UnmappedPolicy::Callback(handler) => handler(&fault).map_err(|_| fault.addr),
         }
     }
 
     pub fn write_u8(&self, addr: &u64, data: u8) -> Result<(), u64> {
-        let region = self.get_region_by_addr(addr)?;
-        Ok(U8Access::write(region.deref(), addr, data))
+        self.try_write_u8(addr, data)
+            .or_else(|e| self.unmapped(AccessKind::Write, 1, e).map(|_| ()))
     }
 
     pub fn read_u8(&self, addr: &u64) -> Result<u8, u64> {
-        let region = self.get_region_by_addr(addr)?;
-        Ok(U8Access::read(region.deref(), addr))
+        self.try_read_u8(addr)
+            .or_else(|e| self.unmapped(AccessKind::Read, 1, e).map(|v| v as u8))
     }
 
     pub fn write_u16(&self, addr: &u64, data: u16) -> Result<(), u64> {
-        let region = self.get_region_by_addr(addr)?;
-        Ok(U16Access::write(region.deref(), addr, data))
+        self.try_write_u16(addr, data)
+            .or_else(|e| self.unmapped(AccessKind::Write, 2, e).map(|_| ()))
     }
 
     pub fn read_u16(&self, addr: &u64) -> Result<u16, u64> {
-        let region = self.get_region_by_addr(addr)?;
-        Ok(U16Access::read(region.deref(), addr))
+        self.try_read_u16(addr)
+            .or_else(|e| self.unmapped(AccessKind::Read, 2, e).map(|v| v as u16))
     }
 
     pub fn write_u32(&self, addr: &u64, data: u32) -> Result<(), u64> {
-        let region = self.get_region_by_addr(addr)?;
-        Ok(U32Access::write(region.deref(), addr, data))
+        self.try_write_u32(addr, data)
+            .or_else(|e| self.unmapped(AccessKind::Write, 4, e).map(|_| ()))
     }
 
     pub fn read_u32(&self, addr: &u64) -> Result<u32, u64> {
-        let region = self.get_region_by_addr(addr)?;
-        Ok(U32Access::read(region.deref(), addr))
+        self.try_read_u32(addr)
+            .or_else(|e| self.unmapped(AccessKind::Read, 4, e).map(|v| v as u32))
     }
 
     pub fn write_u64(&self, addr: &u64, data: u64) -> Result<(), u64> {
-        let region = self.get_region_by_addr(addr)?;
-        Ok(U64Access::write(region.deref(), addr, data))
+        self.try_write_u64(addr, data)
+            .or_else(|e| self.unmapped(AccessKind::Write, 8, e).map(|_| ()))
     }
 
     pub fn read_u64(&self, addr: &u64) -> Result<u64, u64> {
-        let region = self.get_region_by_addr(addr)?;
-        Ok(U64Access::read(region.deref(), addr))
+        self.try_read_u64(addr)
+            .or_else(|e| self.unmapped(AccessKind::Read, 8, e))
     }
 
+    //the fault is reported at the first failed byte, nothing is written if there is a hole
     pub fn write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, u64> {
-        let region = self.get_region_by_addr(addr)?;
-        if let Ok(size) = BytesAccess::write(region.deref(), addr, data) {
-            Ok(size)
-        } else {
-            Err(*addr)
-        }
+        self.try_write_bytes(addr, data).or_else(|e| {
+            self.unmapped(AccessKind::Write, data.len(), e)
+                .map(|_| data.len())
+        })
     }
 
     pub fn read_bytes(&self, addr: &u64, data: &mut [u8]) -> Result<usize, u64> {
-        let region = self.get_region_by_addr(addr)?;
-        if let Ok(size) = BytesAccess::read(region.deref(), addr, data) {
-            Ok(size)
-        } else {
-            Err(*addr)
+        match self.try_read_bytes(addr, data) {
+            Ok(size) => Ok(size),
+            Err(e) => {
+                let value = self.unmapped(AccessKind::Read, data.len(), e)?;
+                data.fill(value as u8);
+                Ok(data.len())
+            }
         }
     }
 
@@ -211,19 +901,510 @@ impl Space {
     }
 }
 
-impl Display for Space {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        writeln!(f, "regions:")?;
-        for e in self.regions.iter() {
-            writeln!(
+//permission-checked sized accesses
+macro_rules! space_checked {
+    ($try_write:ident, $try_read:ident, $try_write_checked:ident, $try_read_checked:ident, $try_fetch:ident, $t:ty, $size:expr) => {
+        impl Space {
+            pub fn $try_write_checked(&self, addr: &u64, data: $t) -> Result<(), AccessError> {
+                self.check_access(addr, $size, AccessType::Write)?;
+                self.$try_write(addr, data)
+            }
+
+            pub fn $try_read_checked(&self, addr: &u64) -> Result<$t, AccessError> {
+                self.check_access(addr, $size, AccessType::Read)?;
+                self.$try_read(addr)
+            }
+
+            pub fn $try_fetch(&self, addr: &u64) -> Result<$t, AccessError> {
+                self.check_access(addr, $size, AccessType::Fetch)?;
+                self.$try_read(addr)
+            }
+        }
+    };
+}
+
+space_checked!(
+    try_write_u8,
+    try_read_u8,
+    try_write_u8_checked,
+    try_read_u8_checked,
+    try_fetch_u8,
+    u8,
+    1
+);
+space_checked!(
+    try_write_u16,
+    try_read_u16,
+    try_write_u16_checked,
+    try_read_u16_checked,
+    try_fetch_u16,
+    u16,
+    2
+);
+space_checked!(
+    try_write_u32,
+    try_read_u32,
+    try_write_u32_checked,
+    try_read_u32_checked,
+    try_fetch_u32,
+    u32,
+    4
+);
+space_checked!(
+    try_write_u64,
+    try_read_u64,
+    try_write_u64_checked,
+    try_read_u64_checked,
+    try_fetch_u64,
+    u64,
+    8
+);
+
+//watchers get data in memory byte order
+macro_rules! space_sized {
+    ($try_write:ident, $try_read:ident, $try_write_endian:ident, $try_read_endian:ident, $write_as:ident, $read_as:ident, $conv:ident, $t:ty, $size:expr) => {
+        impl Space {
+            pub fn $try_write(&self, addr: &u64, data: $t) -> Result<(), AccessError> {
+                self.$write_as(addr, data, self.endian)
+            }
+
+            pub fn $try_read(&self, addr: &u64) -> Result<$t, AccessError> {
+                self.$read_as(addr, self.endian)
+            }
+
+            //override the endianness of the space and the regions per call
+            pub fn $try_write_endian(
+                &self,
+                addr: &u64,
+                data: $t,
+                endian: Endian,
+            ) -> Result<(), AccessError> {
+                self.$write_as(addr, data, Some(endian))
+            }
+
+            pub fn $try_read_endian(&self, addr: &u64, endian: Endian) -> Result<$t, AccessError> {
+                self.$read_as(addr, Some(endian))
+            }
+
+            fn $write_as(
+                &self,
+                addr: &u64,
+                data: $t,
+                endian: Option<Endian>,
+            ) -> Result<(), AccessError> {
+                if self.split_unaligned(addr, $size) {
+                    let endian = endian.unwrap_or_else(|| self.endian_at(addr));
+                    return self.write_split(addr, $size, endian.$conv(data) as u64);
+                }
+                let region = self.region_of(addr, $size)?;
+                let endian = endian.unwrap_or_else(|| region.endian());
+                region.$try_write_endian(addr, data, endian)?;
+                self.reservations.invalidate(addr, $size);
+                self.watcher
+                    .notify(AccessKind::Write, addr, &endian.$conv(data).to_le_bytes());
+                Ok(())
+            }
+
+            fn $read_as(&self, addr: &u64, endian: Option<Endian>) -> Result<$t, AccessError> {
+                if self.split_unaligned(addr, $size) {
+                    let endian = endian.unwrap_or_else(|| self.endian_at(addr));
+                    return self.read_split(addr, $size).map(|v| endian.$conv(v as $t));
+                }
+                let region = self.region_of(addr, $size)?;
+                let endian = endian.unwrap_or_else(|| region.endian());
+                let data = region.$try_read_endian(addr, endian)?;
+                self.watcher
+                    .notify(AccessKind::Read, addr, &endian.$conv(data).to_le_bytes());
+                Ok(data)
+            }
+        }
+    };
+}
+
+space_sized!(
+    try_write_u16,
+    try_read_u16,
+    try_write_u16_endian,
+    try_read_u16_endian,
+    write_u16_as,
+    read_u16_as,
+    conv_u16,
+    u16,
+    2
+);
+space_sized!(
+    try_write_u32,
+    try_read_u32,
+    try_write_u32_endian,
+    try_read_u32_endian,
+    write_u32_as,
+    read_u32_as,
+    conv_u32,
+    u32,
+    4
+);
+space_sized!(
+    try_write_u64,
+    try_read_u64,
+    try_write_u64_endian,
+    try_read_u64_endian,
+    write_u64_as,
+    read_u64_as,
+    conv_u64,
+    u64,
+    8
+);
+
+//atomics are never split, all stores to a reserved granule break the reservation,
+//so does a store conditional of the hart itself, whether it succeeds or not
+macro_rules! space_atomic {
+    ($try_amo:ident, $try_amo_endian:ident, $amo_as:ident, $apply:ident, $try_cas:ident, $try_cas_endian:ident, $cas_as:ident, $try_lr:ident, $try_sc:ident, $try_read:ident, $try_write:ident, $conv:ident, $t:ty, $size:expr) => {
+        impl Space {
+            pub fn $try_amo(&self, addr: &u64, op: AmoOp, data: $t) -> Result<$t, AccessError> {
+                self.$amo_as(addr, op, data, self.endian)
+            }
+
+            //override the endianness of the space and the regions per call
+            pub fn $try_amo_endian(
+                &self,
+                addr: &u64,
+                op: AmoOp,
+                data: $t,
+                endian: Endian,
+            ) -> Result<$t, AccessError> {
+                self.$amo_as(addr, op, data, Some(endian))
+            }
+
+            pub fn $try_cas(&self, addr: &u64, current: $t, new: $t) -> Result<$t, AccessError> {
+                self.$cas_as(addr, current, new, self.endian)
+            }
+
+            pub fn $try_cas_endian(
+                &self,
+                addr: &u64,
+                current: $t,
+                new: $t,
+                endian: Endian,
+            ) -> Result<$t, AccessError> {
+                self.$cas_as(addr, current, new, Some(endian))
+            }
+
+            fn $amo_as(
+                &self,
+                addr: &u64,
+                op: AmoOp,
+                data: $t,
+                endian: Option<Endian>,
+            ) -> Result<$t, AccessError> {
+                let region = self.region_of(addr, $size)?;
+                let endian = endian.unwrap_or_else(|| region.endian());
+                let old = region.$try_amo_endian(addr, op, data, endian)?;
+                self.reservations.invalidate(addr, $size);
+                self.watcher
+                    .notify(AccessKind::Read, addr, &endian.$conv(old).to_le_bytes());
+                self.watcher.notify(
+                    AccessKind::Write,
+                    addr,
+                    &endian.$conv(op.$apply(old, data)).to_le_bytes(),
+                );
+                Ok(old)
+            }
+
+            fn $cas_as(
+                &self,
+                addr: &u64,
+                current: $t,
+                new: $t,
+                endian: Option<Endian>,
+            ) -> Result<$t, AccessError> {
+                let region = self.region_of(addr, $size)?;
+                let endian = endian.unwrap_or_else(|| region.endian());
+                let old = region.$try_cas_endian(addr, current, new, endian)?;
+                self.watcher
+                    .notify(AccessKind::Read, addr, &endian.$conv(old).to_le_bytes());
+                if old == current {
+                    self.reservations.invalidate(addr, $size);
+                    self.watcher
+                        .notify(AccessKind::Write, addr, &endian.$conv(new).to_le_bytes());
+                }
+                Ok(old)
+            }
+
+            pub fn $try_lr(&self, hart: usize, addr: &u64) -> Result<$t, AccessError> {
+                check_align(addr, $size)?;
+                let data = self.$try_read(addr)?;
+                self.reservations.reserve(hart, addr);
+                Ok(data)
+            }
+
+            //Ok(false) if the reservation has been lost, memory is untouched
+            pub fn $try_sc(&self, hart: usize, addr: &u64, data: $t) -> Result<bool, AccessError> {
+                check_align(addr, $size)?;
+                if self.reservations.take(hart) != Some(self.reservations.granule_of(addr)) {
+                    return Ok(false);
+                }
+                self.$try_write(addr, data)?;
+                Ok(true)
+            }
+        }
+    };
+}
+
+space_atomic!(
+    try_amo_u32,
+    try_amo_u32_endian,
+    amo_u32_as,
+    apply_u32,
+    try_compare_exchange_u32,
+    try_compare_exchange_u32_endian,
+    compare_exchange_u32_as,
+    try_load_reserved_u32,
+    try_store_conditional_u32,
+    try_read_u32,
+    try_write_u32,
+    conv_u32,
+    u32,
+    4
+);
+space_atomic!(
+    try_amo_u64,
+    try_amo_u64_endian,
+    amo_u64_as,
+    apply_u64,
+    try_compare_exchange_u64,
+    try_compare_exchange_u64_endian,
+    compare_exchange_u64_as,
+    try_load_reserved_u64,
+    try_store_conditional_u64,
+    try_read_u64,
+    try_write_u64,
+    conv_u64,
+    u64,
+    8
+);
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"TSSPACE\0";
+const SNAPSHOT_VERSION: u32 = 4;
+
+//snapshot layout:
+//  magic, version
+//  region map: [name, base, size, enabled], the regions at priority 0 and then the others,
+//  enabled since version 2
+//  contents of Model/LazyModel/MmapModel and IOSnapshot devices: [region name, kind, size, payload]
+//  heaps built on the regions or the regions behind them: [region name, index, allocator state],
+//  labels of live blocks in the allocator state since version 4
+//  spaces behind bridges since version 3: [region name, snapshot of the space]
+impl Space {
+    pub fn snapshot(&self, file_name: &str) -> Result<(), Error> {
+        let file = File::create(file_name)
+            .map_err(|e| Error::Snapshot(format!("can not create {}! {}", file_name, e)))?;
+        let mut w = BufWriter::new(file);
+        self.snapshot_to(&mut w)?;
+        w.flush().map_err(|e| Error::Snapshot(e.to_string()))
+    }
+
+    pub fn snapshot_to(&self, w: &mut dyn Write) -> Result<(), Error> {
+        self.save(w).map_err(|e| Error::Snapshot(e.to_string()))
+    }
+
+    //the space must have the same region map as the snapshot, e.g. built by the same platform code,
+    //regions and heaps restored before an error are not rolled back
+    pub fn restore(&self, file_name: &str) -> Result<(), Error> {
+        let file = File::open(file_name)
+            .map_err(|e| Error::Snapshot(format!("can not open {}! {}", file_name, e)))?;
+        self.restore_from(&mut BufReader::new(file))
+    }
+
+    pub fn restore_from(&self, r: &mut dyn Read) -> Result<(), Error> {
+        self.load(r).map_err(|e| Error::Snapshot(e.to_string()))
+    }
+
+    //(name, region, priority, enabled) of the regions in address order, then the others by priority
+    fn mappings(&self) -> impl Iterator<Item = (&str, &Rc<Region>, i32, &Cell<bool>)> {
+        self.regions
+            .iter()
+            .map(|e| (e.value.0.as_str(), &e.value.1, 0, &e.enabled))
+            .chain(
+                self.overlays
+                    .iter()
+                    .map(|o| (o.name.as_str(), &o.region, o.priority, &o.enabled)),
+            )
+    }
+
+    //each heap is keyed by the first region reaching it and its index among the heaps of that region
+    fn heaps(&self) -> Vec<(&str, u32, Rc<Heap>)> {
+        let mut heaps: Vec<(&str, u32, Rc<Heap>)> = vec![];
+        for (name, region, _, _) in self.mappings() {
+            let mut idx: u32 = 0;
+            for heap in region.chain().iter().flat_map(|r| r.heaps()) {
+                if !heaps.iter().any(|(_, _, h)| Rc::ptr_eq(h, &heap)) {
+                    heaps.push((name, idx, heap));
+                    idx += 1;
+                }
+            }
+        }
+        heaps
+    }
+
+    //each space mounted by bridges is keyed by the first bridge reaching it
+    fn bridged(&self) -> Vec<(&str, &Rc<Space>)> {
+        let mut spaces: Vec<(&str, &Rc<Space>)> = vec![];
+        for (name, region, _, _) in self.mappings() {
+            if let Some(space) = region.leaf().bridged_space() {
+                if !spaces.iter().any(|(_, s)| Rc::ptr_eq(s, space)) {
+                    spaces.push((name, space))
+                }
+            }
+        }
+        spaces
+    }
+
+    fn save(&self, w: &mut dyn Write) -> io::Result<()> {
+        w.write_all(SNAPSHOT_MAGIC)?;
+        put_u32(w, SNAPSHOT_VERSION)?;
+
+        put_u32(w, self.mappings().count() as u32)?;
+        for (name, region, _, enabled) in self.mappings() {
+            put_str(w, name)?;
+            put_u64(w, region.info.base)?;
+            put_u64(w, region.info.size)?;
+            put_u8(w, enabled.get() as u8)?;
+        }
+
+        //aliases share the same leaf, save it once
+        let mut leaves: Vec<(&str, &Region)> = vec![];
+        for (name, region, _, _) in self.mappings() {
+            let leaf = region.leaf();
+            if leaf.has_state() && !leaves.iter().any(|(_, l)| std::ptr::eq(*l, leaf)) {
+                leaves.push((name, leaf))
+            }
+        }
+        put_u32(w, leaves.len() as u32)?;
+        for (name, leaf) in leaves {
+            put_str(w, name)?;
+            leaf.save_state(w)?;
+        }
+
+        let heaps = self.heaps();
+        put_u32(w, heaps.len() as u32)?;
+        for (name, idx, heap) in heaps {
+            put_str(w, name)?;
+            put_u32(w, idx)?;
+            heap.save_state(w)?;
+        }
+
+        let bridged = self.bridged();
+        put_u32(w, bridged.len() as u32)?;
+        for (name, space) in bridged {
+            put_str(w, name)?;
+            space.save(w)?;
+        }
+        Ok(())
+    }
+
+    fn load(&self, r: &mut dyn Read) -> io::Result<()> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != SNAPSHOT_MAGIC {
+            return Err(invalid("not a space snapshot!"));
+        }
+        let version = get_u32(r)?;
+        if !(1..=SNAPSHOT_VERSION).contains(&version) {
+            return Err(invalid(format!(
+                "unsupported snapshot version {}, expect 1 to {}!",
+                version, SNAPSHOT_VERSION
+            )));
+        }
+
+        let mut map: Vec<(String, u64, u64)> = vec![];
+        let mut enabled: Vec<bool> = vec![];
+        for _ in 0..get_u32(r)? {
+            map.push((get_str(r)?, get_u64(r)?, get_u64(r)?));
+            //all regions were enabled before version 2
+            enabled.push(version < 2 || get_u8(r)? != 0);
+        }
+        let current = self
+            .mappings()
+            .map(|(name, region, _, _)| (name.to_string(), region.info.base, region.info.size))
+            .collect::<Vec<_>>();
+        if map != current {
+            return Err(invalid(format!(
+                "region map mismatch! snapshot: {:x?}, space: {:x?}",
+                map, current
+            )));
+        }
+        for ((_, _, _, flag), enabled) in self.mappings().zip(enabled) {
+            flag.set(enabled)
+        }
+
+        for _ in 0..get_u32(r)? {
+            let name = get_str(r)?;
+            let region = self
+                .get_region(&name)
+                .ok_or_else(|| invalid(format!("region {} not found!", name)))?;
+            region.leaf().load_state(r)?;
+        }
+
+        let heaps = self.heaps();
+        for _ in 0..get_u32(r)? {
+            let name = get_str(r)?;
+            let idx = get_u32(r)?;
+            let (_, _, heap) = heaps
+                .iter()
+                .find(|(n, i, _)| *n == name && *i == idx)
+                .ok_or_else(|| invalid(format!("heap {} of region {} not found!", idx, name)))?;
+            heap.load_state(r, version)?;
+        }
+
+        //spaces behind bridges were not saved before version 3
+        if version < 3 {
+            return Ok(());
+        }
+        let bridged = self.bridged();
+        for _ in 0..get_u32(r)? {
+            let name = get_str(r)?;
+            let (_, space) = bridged
+                .iter()
+                .find(|(n, _)| *n == name)
+                .ok_or_else(|| invalid(format!("no space behind region {}!", name)))?;
+            space.load(r)?;
+        }
+        Ok(())
+    }
+}
+
+impl Space {
+    //spaces behind bridges are printed under the bridges, in their own addresses
+    fn fmt_regions(&self, f: &mut Formatter, depth: usize) -> fmt::Result {
+        for (name, region, priority, enabled) in self.mappings() {
+            write!(
                 f,
-                "   {:<10}({:^13})  : {:#016x} -> {:#016x}",
-                e.value.0,
-                e.value.1.get_type(),
-                e.value.1.info.base,
-                e.value.1.info.base + e.value.1.info.size - 1
+                "{:indent$}{:<10}({:^13})  : {:#016x} -> {:#016x}",
+                "",
+                name,
+                region.get_type(),
+                region.info.base,
+                region.info.base + region.info.size - 1,
+                indent = depth * 3
             )?;
+            if priority != 0 {
+                write!(f, " priority {}", priority)?;
+            }
+            if !enabled.get() {
+                write!(f, " disabled")?;
+            }
+            writeln!(f)?;
+            if let Some(space) = region.bridged_space() {
+                space.fmt_regions(f, depth + 1)?;
+            }
         }
         Ok(())
     }
 }
+
+impl Display for Space {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "regions:")?;
+        self.fmt_regions(f, 1)
+    }
+}
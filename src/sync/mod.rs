@@ -0,0 +1,19 @@
+//Arc based flavor of Region, Heap and Space, which are Send + Sync.
+//the models and the memory level access code are shared with the Rc based flavor in memory::model.
+//guaranteed by this flavor:
+//  Region: BytesAccess, U8Access, U16Access, U32Access, U64Access and SizedAccess, the try_* accesses
+//  in little endian and the unaligned policies
+//  Space: the try_* and the plain sized and bytes accesses, unmapped addresses reported as errors
+//  Heap and GHEAP: AllocError on failure, GHEAP shares the global allocator of the Rc based flavor
+//only provided by the Rc based flavor: AtomicAccess, endian, write policies, host slices, bridges,
+//iommus, watchpoints, snapshots, mmap models, resizing heap blocks and the c api.
+#[cfg(test)]
+mod test;
+
+mod region;
+
+pub use region::{GlobalHeap, Heap, Region, GHEAP};
+
+mod space;
+
+pub use space::Space;
@@ -0,0 +1,372 @@
+use crate::memory::allocator::{
+    AllocError, AllocStats, Allocator, LiveBlock, LockedAllocator, Policy,
+};
+use crate::memory::model;
+use crate::memory::model::{memory_bytes, memory_sized, LazyPages};
+use crate::memory::prelude::*;
+use crate::memory::region;
+use crate::memory::region::{check_align, split_read, split_write};
+use crate::memory::MemInfo;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+type LazyModel = model::LazyModel<RwLock<LazyPages>>;
+
+type Model = model::Model<RwLock<Box<[u8]>>>;
+
+struct Remap {
+    region: Arc<Region>,
+    info: MemInfo,
+}
+
+enum Memory {
+    Model(Model),
+    LazyModel(LazyModel),
+    Block(Arc<Heap>, Arc<Region>),
+    RootBlock(Box<Region>),
+    Remap(Remap),
+    IO(Box<dyn IOAccess + Send + Sync>),
+}
+
+impl Memory {
+    fn get_type(&self) -> String {
+        match self {
+            Memory::Model(_) => "Model".to_string(),
+            Memory::LazyModel(_) => "LazyModel".to_string(),
+            Memory::Block(_, _) => "Block".to_string(),
+            Memory::RootBlock(_) => "Block".to_string(),
+            Memory::Remap(remap) => format!(
+                "Remap({}@{:#016x} -> {:#016x})",
+                remap.region.memory.get_type(),
+                remap.info.base,
+                remap.info.base + remap.info.size
+            ),
+            Memory::IO(_) => "IO".to_string(),
+        }
+    }
+}
+
+macro_rules! memory_access {
+    ($obj:expr, $io:ident => $io_access:expr, $model:ident => $model_access:expr, $f:ident, $($p:expr),+) => {match $obj {
+            Memory::IO(io) => {
+                let $io = io.deref();
+                $io_access
+            }
+            Memory::Model($model) => $model_access,
+            Memory::LazyModel($model) => $model_access,
+            Memory::Block(_, region) => region.$f($($p,)+),
+            Memory::RootBlock(region) => region.$f($($p,)+),
+            Memory::Remap(remap) => remap.region.$f($($p,)+),
+        }
+        }
+}
+
+macro_rules! sized_access {
+    ($try_write:ident, $try_read:ident, $access:ident, $t:ty, $size:expr) => {
+        memory_sized!($try_write, $try_read, $try_write, $try_read, $access, $t, $size);
+
+        impl Region {
+            pub fn $try_write(&self, addr: &u64, data: $t) -> Result<(), AccessError> {
+                if self.split_unaligned(addr, $size) {
+                    return split_write(
+                        addr,
+                        $size,
+                        data as u64,
+                        |a, s| self.translate(a, s),
+                        |_, a, s, d| self.try_write_sized(a, s, d),
+                    );
+                }
+                self.memory
+                    .$try_write(&self.translate(addr, $size)?, data)
+                    .map_err(|e| e.at(addr))
+            }
+
+            pub fn $try_read(&self, addr: &u64) -> Result<$t, AccessError> {
+                if self.split_unaligned(addr, $size) {
+                    return split_read(addr, $size, |a, s| self.try_read_sized(a, s))
+                        .map(|v| v as $t);
+                }
+                self.memory
+                    .$try_read(&self.translate(addr, $size)?)
+                    .map_err(|e| e.at(addr))
+            }
+        }
+
+        impl $access for Region {
+            fn write(&self, addr: &u64, data: $t) {
+                self.$try_write(addr, data)
+                    .unwrap_or_else(|e| panic!("{}", e))
+            }
+
+            fn read(&self, addr: &u64) -> $t {
+                self.$try_read(addr).unwrap_or_else(|e| panic!("{}", e))
+            }
+        }
+    };
+}
+
+sized_access!(try_write_u8, try_read_u8, U8Access, u8, 1);
+sized_access!(try_write_u16, try_read_u16, U16Access, u16, 2);
+sized_access!(try_write_u32, try_read_u32, U32Access, u32, 4);
+sized_access!(try_write_u64, try_read_u64, U64Access, u64, 8);
+
+memory_bytes!();
+
+pub struct Region {
+    memory: Memory,
+    pub info: MemInfo,
+    split: AtomicBool,
+}
+
+impl Region {
+    fn new(memory: Memory, base: u64, size: u64) -> Region {
+        Region {
+            memory,
+            info: MemInfo { base, size },
+            split: AtomicBool::new(false),
+        }
+    }
+
+    pub fn get_type(&self) -> String {
+        self.memory.get_type()
+    }
+
+    pub fn io(base: u64, size: u64, io: Box<dyn IOAccess + Send + Sync>) -> Arc<Region> {
+        Arc::new(Region::new(Memory::IO(io), base, size))
+    }
+
+    fn lazy_model(base: u64, size: u64) -> Region {
+        Region::new(Memory::LazyModel(LazyModel::new()), base, size)
+    }
+
+    fn model(base: u64, size: u64) -> Region {
+        Region::new(
+            Memory::Model(Model::new(MemInfo { base, size })),
+            base,
+            size,
+        )
+    }
+
+    fn block(base: u64, size: u64, heap: &Arc<Heap>, memory: &Arc<Region>) -> Arc<Region> {
+        Arc::new(Region::new(
+            Memory::Block(Arc::clone(heap), Arc::clone(memory)),
+            base,
+            size,
+        ))
+    }
+
+    fn root_block(base: u64, size: u64, memory: Region) -> Arc<Region> {
+        Arc::new(Region::new(Memory::RootBlock(Box::new(memory)), base, size))
+    }
+
+    pub fn remap(base: u64, memory: &Arc<Region>) -> Arc<Region> {
+        Region::remap_partial(base, memory, 0, memory.info.size)
+    }
+
+    pub fn remap_partial(base: u64, memory: &Arc<Region>, offset: u64, size: u64) -> Arc<Region> {
+        assert!(offset + size <= memory.info.size);
+        assert!(offset & 0x7 == 0);
+        Arc::new(Region::new(
+            Memory::Remap(Remap {
+                region: Arc::clone(memory),
+                info: MemInfo {
+                    base: memory.info.base + offset,
+                    size,
+                },
+            }),
+            base,
+            size,
+        ))
+    }
+
+    pub(super) fn translate(&self, va: &u64, size: usize) -> Result<u64, AccessError> {
+        let end = va
+            .checked_sub(self.info.base)
+            .and_then(|offset| offset.checked_add(size as u64));
+        if !matches!(end, Some(end) if end <= self.info.size) {
+            return Err(AccessError::OutOfRange(*va, size));
+        }
+        match &self.memory {
+            Memory::Remap(remap) => Ok(va - self.info.base + remap.info.base),
+            _ => Ok(*va),
+        }
+    }
+
+    pub fn unaligned_policy(&self) -> UnalignedPolicy {
+        if self.split.load(Ordering::Relaxed) {
+            UnalignedPolicy::Split
+        } else {
+            UnalignedPolicy::Fault
+        }
+    }
+
+    pub fn set_unaligned_policy(&self, policy: UnalignedPolicy) {
+        self.split
+            .store(policy == UnalignedPolicy::Split, Ordering::Relaxed)
+    }
+
+    fn split_unaligned(&self, addr: &u64, size: usize) -> bool {
+        self.split.load(Ordering::Relaxed) && *addr & (size as u64 - 1) != 0
+    }
+
+    fn try_read_sized(&self, addr: &u64, size: usize) -> Result<u64, AccessError> {
+        match size {
+            1 => self.try_read_u8(addr).map(|v| v as u64),
+            2 => self.try_read_u16(addr).map(|v| v as u64),
+            4 => self.try_read_u32(addr).map(|v| v as u64),
+            _ => self.try_read_u64(addr),
+        }
+    }
+
+    fn try_write_sized(&self, addr: &u64, size: usize, data: u64) -> Result<(), AccessError> {
+        match size {
+            1 => self.try_write_u8(addr, data as u8),
+            2 => self.try_write_u16(addr, data as u16),
+            4 => self.try_write_u32(addr, data as u32),
+            _ => self.try_write_u64(addr, data),
+        }
+    }
+
+    pub fn try_write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        self.memory
+            .try_write_bytes(&self.translate(addr, data.len())?, data)
+            .map_err(|e| e.at(addr))
+    }
+
+    pub fn try_read_bytes(&self, addr: &u64, data: &mut [u8]) -> Result<usize, AccessError> {
+        self.memory
+            .try_read_bytes(&self.translate(addr, data.len())?, data)
+            .map_err(|e| e.at(addr))
+    }
+}
+
+//keep the String error for device errors
+impl BytesAccess for Region {
+    fn write(&self, addr: &u64, data: &[u8]) -> Result<usize, String> {
+        match self.try_write_bytes(addr, data) {
+            Err(AccessError::DeviceError(_, msg)) => Err(msg),
+            res => Ok(res.unwrap_or_else(|e| panic!("{}", e))),
+        }
+    }
+
+    fn read(&self, addr: &u64, data: &mut [u8]) -> Result<usize, String> {
+        match self.try_read_bytes(addr, data) {
+            Err(AccessError::DeviceError(_, msg)) => Err(msg),
+            res => Ok(res.unwrap_or_else(|e| panic!("{}", e))),
+        }
+    }
+}
+
+impl SizedAccess for Region {}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        let freed = if let Memory::Block(heap, _) = &self.memory {
+            heap.allocator.lock().unwrap().free(self.info.base)
+        } else if let Memory::RootBlock(_) = &self.memory {
+            GHEAP.allocator().free(self.info.base)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = freed {
+            eprintln!("block {:?} is leaked! {}", self.info, e)
+        }
+    }
+}
+
+pub struct Heap {
+    memory: Arc<Region>,
+    allocator: Mutex<Allocator>,
+}
+
+impl Heap {
+    pub fn new(memory: &Arc<Region>) -> Arc<Heap> {
+        Heap::with_policy(memory, Policy::FirstFit)
+    }
+
+    pub fn with_policy(memory: &Arc<Region>, policy: Policy) -> Arc<Heap> {
+        Arc::new(Heap {
+            memory: Arc::clone(memory),
+            allocator: Mutex::new(Allocator::with_policy(
+                memory.info.base,
+                memory.info.size,
+                policy,
+            )),
+        })
+    }
+
+    pub fn alloc(self: &Arc<Self>, size: u64, align: u64) -> Result<Arc<Region>, AllocError> {
+        let info = self.allocator.lock().unwrap().alloc(size, align)?;
+        Ok(Region::block(info.base, info.size, self, &self.memory))
+    }
+
+    pub fn alloc_labeled(
+        self: &Arc<Self>,
+        size: u64,
+        align: u64,
+        label: &str,
+    ) -> Result<Arc<Region>, AllocError> {
+        let info = self
+            .allocator
+            .lock()
+            .unwrap()
+            .alloc_labeled(size, align, label)?;
+        Ok(Region::block(info.base, info.size, self, &self.memory))
+    }
+
+    pub fn get_region(&self) -> &Arc<Region> {
+        &self.memory
+    }
+
+    pub fn stats(&self) -> AllocStats {
+        self.allocator.lock().unwrap().stats()
+    }
+
+    pub fn live_blocks(&self) -> Vec<LiveBlock> {
+        self.allocator.lock().unwrap().live_blocks()
+    }
+}
+
+//root blocks come from the global allocator of the Rc flavor, so blocks of both flavors never
+//overlap and the policy, stats and live blocks are shared
+pub static GHEAP: GlobalHeap = GlobalHeap;
+
+pub struct GlobalHeap;
+
+impl GlobalHeap {
+    fn allocator(&self) -> &'static LockedAllocator {
+        &region::GHEAP.allocator
+    }
+
+    pub fn stats(&self) -> AllocStats {
+        self.allocator().stats()
+    }
+
+    pub fn live_blocks(&self) -> Vec<LiveBlock> {
+        self.allocator().live_blocks()
+    }
+
+    //only before anything is allocated
+    pub fn set_policy(&self, policy: Policy) -> Result<(), String> {
+        region::GHEAP.set_policy(policy)
+    }
+
+    pub fn lazy_alloc(&self, size: u64, align: u64) -> Result<Arc<Region>, AllocError> {
+        let info = self.allocator().alloc(size, align)?;
+        Ok(Region::root_block(
+            info.base,
+            info.size,
+            Region::lazy_model(info.base, info.size),
+        ))
+    }
+
+    pub fn alloc(&self, size: u64, align: u64) -> Result<Arc<Region>, AllocError> {
+        let info = self.allocator().alloc(size, align)?;
+        Ok(Region::root_block(
+            info.base,
+            info.size,
+            Region::model(info.base, info.size),
+        ))
+    }
+}
@@ -0,0 +1,257 @@
+use super::Region;
+use crate::memory::prelude::*;
+use crate::memory::region::{split_read, split_write};
+use crate::space::{range_end, unmapped_addr, Error};
+use std::cmp::min;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::ops::Bound::{Excluded, Unbounded};
+use std::sync::Arc;
+
+//the region map is changed through &mut, build it first then share the space by Arc.
+//intrusive RBTreeLink is not Sync, so regions are kept in a BTreeMap keyed by base
+pub struct Space {
+    regions: BTreeMap<u64, (String, Arc<Region>)>,
+    //name -> base
+    names: HashMap<String, u64>,
+    unaligned: UnalignedPolicy,
+}
+
+impl Default for Space {
+    fn default() -> Space {
+        Space::new()
+    }
+}
+
+impl Space {
+    pub fn new() -> Space {
+        Space {
+            regions: BTreeMap::new(),
+            names: HashMap::new(),
+            unaligned: UnalignedPolicy::Fault,
+        }
+    }
+
+    pub fn add_region(&mut self, name: &str, region: &Arc<Region>) -> Result<Arc<Region>, Error> {
+        if self.names.contains_key(name) {
+            return Err(Error::Renamed(
+                name.to_string(),
+                format!("region name {} has existed!", name),
+            ));
+        }
+        //only the neighbours around base can overlap
+        let base = region.info.base;
+        if let Some((n, r)) = self
+            .regions
+            .range(..=base)
+            .next_back()
+            .filter(|(b, (_, r))| base - *b < r.info.size)
+            .or_else(|| {
+                self.regions
+                    .range((Excluded(base), Unbounded))
+                    .next()
+                    .filter(|(b, _)| *b - base < region.info.size)
+            })
+            .map(|(_, v)| v)
+        {
+            return Err(Error::Overlap(
+                n.to_string(),
+                format!(
+                    "region [{} : {:?}] is overlapped with [{} : {:?}]!",
+                    name, region.info, n, r.info
+                ),
+            ));
+        }
+        self.names.insert(name.to_string(), base);
+        self.regions
+            .insert(base, (name.to_string(), Arc::clone(region)));
+        Ok(Arc::clone(region))
+    }
+
+    pub fn delete_region(&mut self, name: &str) -> Result<(), Error> {
+        let base = self
+            .names
+            .remove(name)
+            .ok_or_else(|| Error::NotFound(name.to_string()))?;
+        self.regions.remove(&base);
+        Ok(())
+    }
+
+    pub fn get_region(&self, name: &str) -> Option<Arc<Region>> {
+        let base = self.names.get(name)?;
+        self.regions.get(base).map(|(_, r)| Arc::clone(r))
+    }
+
+    pub fn get_region_by_addr(&self, addr: &u64) -> Result<Arc<Region>, u64> {
+        self.region_by_addr(addr).map(Arc::clone)
+    }
+
+    //no refcount traffic on the access path
+    fn region_by_addr(&self, addr: &u64) -> Result<&Arc<Region>, u64> {
+        match self.regions.range(..=*addr).next_back() {
+            Some((_, (_, r))) if *addr < r.info.base + r.info.size => Ok(r),
+            _ => Err(*addr),
+        }
+    }
+
+    pub fn unaligned_policy(&self) -> UnalignedPolicy {
+        self.unaligned
+    }
+
+    //UnalignedPolicy::Split also allows accesses crossing adjacent regions
+    pub fn set_unaligned_policy(&mut self, policy: UnalignedPolicy) {
+        self.unaligned = policy
+    }
+
+    fn split_unaligned(&self, addr: &u64, size: usize) -> bool {
+        self.unaligned == UnalignedPolicy::Split && *addr & (size as u64 - 1) != 0
+    }
+
+    fn try_read_sized(&self, addr: &u64, size: usize) -> Result<u64, AccessError> {
+        match size {
+            1 => self.try_read_u8(addr).map(|v| v as u64),
+            2 => self.try_read_u16(addr).map(|v| v as u64),
+            4 => self.try_read_u32(addr).map(|v| v as u64),
+            _ => self.try_read_u64(addr),
+        }
+    }
+
+    fn try_write_sized(&self, addr: &u64, size: usize, data: u64) -> Result<(), AccessError> {
+        match size {
+            1 => self.try_write_u8(addr, data as u8),
+            2 => self.try_write_u16(addr, data as u16),
+            4 => self.try_write_u32(addr, data as u32),
+            _ => self.try_write_u64(addr, data),
+        }
+    }
+
+    //split [addr, addr + len) into (region, addr, len) chunks, fail at the first unmapped hole
+    fn split_by_regions(
+        &self,
+        addr: &u64,
+        len: usize,
+    ) -> Result<Vec<(&Region, u64, usize)>, AccessError> {
+        let mut chunks = vec![];
+        let end = range_end(addr, len)?;
+        let mut cur = *addr;
+        while cur < end {
+            let region = self
+                .region_by_addr(&cur)
+                .map_err(AccessError::Unmapped)?
+                .as_ref();
+            let chunk_end = min(end, region.info.base + region.info.size);
+            chunks.push((region, cur, (chunk_end - cur) as usize));
+            cur = chunk_end;
+        }
+        Ok(chunks)
+    }
+
+    //transfers crossing adjacent regions are split, nothing is written if there is a hole
+    pub fn try_write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, AccessError> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let end = range_end(addr, data.len())?;
+        let region = self.region_by_addr(addr).map_err(AccessError::Unmapped)?;
+        if end <= region.info.base + region.info.size {
+            return region.try_write_bytes(addr, data);
+        }
+        let mut offset: usize = 0;
+        for (region, a, len) in self.split_by_regions(addr, data.len())? {
+            region.try_write_bytes(&a, &data[offset..offset + len])?;
+            offset += len;
+        }
+        Ok(data.len())
+    }
+
+    pub fn try_read_bytes(&self, addr: &u64, data: &mut [u8]) -> Result<usize, AccessError> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let end = range_end(addr, data.len())?;
+        let region = self.region_by_addr(addr).map_err(AccessError::Unmapped)?;
+        if end <= region.info.base + region.info.size {
+            return region.try_read_bytes(addr, data);
+        }
+        let mut offset: usize = 0;
+        for (region, a, len) in self.split_by_regions(addr, data.len())? {
+            region.try_read_bytes(&a, &mut data[offset..offset + len])?;
+            offset += len;
+        }
+        Ok(data.len())
+    }
+
+    pub fn write_bytes(&self, addr: &u64, data: &[u8]) -> Result<usize, u64> {
+        self.try_write_bytes(addr, data).map_err(unmapped_addr)
+    }
+
+    pub fn read_bytes(&self, addr: &u64, data: &mut [u8]) -> Result<usize, u64> {
+        self.try_read_bytes(addr, data).map_err(unmapped_addr)
+    }
+}
+
+macro_rules! space_access {
+    ($try_write:ident, $try_read:ident, $write:ident, $read:ident, $t:ty, $size:expr) => {
+        impl Space {
+            pub fn $try_write(&self, addr: &u64, data: $t) -> Result<(), AccessError> {
+                if self.split_unaligned(addr, $size) {
+                    return split_write(
+                        addr,
+                        $size,
+                        data as u64,
+                        |a, s| {
+                            self.region_by_addr(a)
+                                .map_err(AccessError::Unmapped)?
+                                .translate(a, s)
+                        },
+                        |_, a, s, d| self.try_write_sized(a, s, d),
+                    );
+                }
+                self.region_by_addr(addr)
+                    .map_err(AccessError::Unmapped)?
+                    .$try_write(addr, data)
+            }
+
+            pub fn $try_read(&self, addr: &u64) -> Result<$t, AccessError> {
+                if self.split_unaligned(addr, $size) {
+                    return split_read(addr, $size, |a, s| self.try_read_sized(a, s))
+                        .map(|v| v as $t);
+                }
+                self.region_by_addr(addr)
+                    .map_err(AccessError::Unmapped)?
+                    .$try_read(addr)
+            }
+
+            pub fn $write(&self, addr: &u64, data: $t) -> Result<(), u64> {
+                self.$try_write(addr, data).map_err(unmapped_addr)
+            }
+
+            pub fn $read(&self, addr: &u64) -> Result<$t, u64> {
+                self.$try_read(addr).map_err(unmapped_addr)
+            }
+        }
+    };
+}
+
+space_access!(try_write_u8, try_read_u8, write_u8, read_u8, u8, 1);
+space_access!(try_write_u16, try_read_u16, write_u16, read_u16, u16, 2);
+space_access!(try_write_u32, try_read_u32, write_u32, read_u32, u32, 4);
+space_access!(try_write_u64, try_read_u64, write_u64, read_u64, u64, 8);
+
+impl Display for Space {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "regions:")?;
+        for (name, region) in self.regions.values() {
+            writeln!(
+                f,
+                "   {:<10}({:^13})  : {:#016x} -> {:#016x}",
+                name,
+                region.get_type(),
+                region.info.base,
+                region.info.base + region.info.size - 1
+            )?;
+        }
+        Ok(())
+    }
+}
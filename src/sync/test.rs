@@ -0,0 +1,150 @@
+use super::*;
+use crate::memory::prelude::*;
+use std::ops::Deref;
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+const THREADS: u64 = 8;
+const ROUNDS: u64 = 1000;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn sync_send_sync() {
+    assert_send_sync::<Region>();
+    assert_send_sync::<Heap>();
+    assert_send_sync::<Space>();
+}
+
+#[test]
+fn sync_region_threads() {
+    for region in [
+        GHEAP.alloc(THREADS * 0x100, 8).unwrap(),
+        GHEAP.lazy_alloc(THREADS * 0x2000, 8).unwrap(),
+    ]
+    .iter()
+    {
+        let stride = region.info.size / THREADS;
+        let barrier = Arc::new(Barrier::new(THREADS as usize));
+        let handles = (0..THREADS)
+            .map(|i| {
+                let region = Arc::clone(region);
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    let base = region.info.base + i * stride;
+                    for r in 0..ROUNDS {
+                        let addr = base + (r * 8) % stride;
+                        U64Access::write(region.deref(), &addr, i << 32 | r);
+                        assert_eq!(U64Access::read(region.deref(), &addr), i << 32 | r);
+                        U8Access::write(region.deref(), &(base + stride - 1), i as u8);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        for i in 0..THREADS {
+            let base = region.info.base + i * stride;
+            assert_eq!(
+                U8Access::read(region.deref(), &(base + stride - 1)),
+                i as u8
+            );
+            assert_eq!(
+                U64Access::read(region.deref(), &(base + ((ROUNDS - 1) * 8) % stride)),
+                i << 32 | (ROUNDS - 1)
+            );
+        }
+    }
+}
+
+#[test]
+fn sync_heap_threads() {
+    let heap = Heap::new(&GHEAP.alloc(THREADS * ROUNDS * 0x10, 8).unwrap());
+    let handles = (0..THREADS)
+        .map(|i| {
+            let heap = Arc::clone(&heap);
+            thread::spawn(move || {
+                let mut blocks = vec![];
+                for r in 0..ROUNDS {
+                    let block = heap.alloc(8, 8).unwrap();
+                    U64Access::write(block.deref(), &block.info.base, i << 32 | r);
+                    blocks.push(block);
+                    //free some blocks to mix alloc and free of all threads
+                    if r % 3 == 0 {
+                        blocks.swap_remove(0);
+                    }
+                }
+                blocks
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut blocks = vec![];
+    for (i, h) in handles.into_iter().enumerate() {
+        for b in h.join().unwrap() {
+            assert_eq!(U64Access::read(b.deref(), &b.info.base) >> 32, i as u64);
+            blocks.push(b);
+        }
+    }
+    blocks.sort_by_key(|b| b.info.base);
+    for w in blocks.windows(2) {
+        assert!(w[0].info.base + w[0].info.size <= w[1].info.base);
+    }
+    std::mem::drop(blocks);
+    //all blocks are freed
+    assert_eq!(
+        heap.alloc(THREADS * ROUNDS * 0x10, 8).unwrap().info.size,
+        THREADS * ROUNDS * 0x10
+    );
+}
+
+#[test]
+fn sync_space_threads() {
+    let mut space = Space::new();
+    let ram = GHEAP.alloc(THREADS * 0x100, 8).unwrap();
+    space
+        .add_region("ram", &Region::remap(0x8000_0000, &ram))
+        .unwrap();
+    space
+        .add_region("ram_alias", &Region::remap(0x1_0000_0000, &ram))
+        .unwrap();
+    assert!(matches!(
+        space.add_region("overlap", &Region::remap(0x8000_0100, &ram)),
+        Err(crate::space::Error::Overlap(_, _))
+    ));
+    assert!(matches!(
+        space.delete_region("missing"),
+        Err(crate::space::Error::NotFound(_))
+    ));
+    space.set_unaligned_policy(UnalignedPolicy::Split);
+    let space = Arc::new(space);
+    let handles = (0..THREADS)
+        .map(|i| {
+            let space = Arc::clone(&space);
+            thread::spawn(move || {
+                let base = 0x8000_0000 + i * 0x100;
+                for r in 0..ROUNDS {
+                    let addr = base + (r * 4) % 0xf0;
+                    space.write_u32(&addr, (i << 16 | r) as u32).unwrap();
+                    assert_eq!(
+                        space.read_u32(&(addr + 0x8000_0000)),
+                        Ok((i << 16 | r) as u32)
+                    );
+                    //unaligned split access
+                    space.write_u64(&(base + 0xf1), i).unwrap();
+                    assert_eq!(space.read_u64(&(base + 0xf1)), Ok(i));
+                }
+                assert_eq!(space.read_u8(&0x7fff_ffff), Err(0x7fff_ffff));
+            })
+        })
+        .collect::<Vec<_>>();
+    for h in handles {
+        h.join().unwrap();
+    }
+    let mut buf = vec![0u8; (THREADS * 0x100) as usize];
+    space.read_bytes(&0x1_0000_0000, &mut buf).unwrap();
+    for i in 0..THREADS as usize {
+        assert_eq!(buf[i * 0x100 + 0xf1], i as u8);
+    }
+}
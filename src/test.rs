@@ -1,8 +1,22 @@
+use crate::devices::armory::display::{Fb, SimpleFb};
+use crate::devices::PixelFormat;
+use crate::memory::attr::{AccessType, Attr, MemType, Perm};
+use crate::memory::region::snapshot::{put_bytes, put_str, put_u32, put_u64, put_u8};
+use crate::memory::region::AccessError;
+use crate::memory::region::AccessKind;
+use crate::memory::region::AmoOp;
+use crate::memory::region::Endian;
 use crate::memory::region::Heap;
 use crate::memory::region::Region;
+use crate::memory::region::UnalignedPolicy;
+use crate::memory::region::WatchKind;
+use crate::memory::region::WritePolicy;
 use crate::memory::region::GHEAP;
 use crate::memory::MemInfo;
 use crate::space::*;
+use std::cell::{Cell, RefCell};
+use std::io::Write;
+use std::rc::Rc;
 
 #[test]
 fn space_drop() {
@@ -23,18 +37,16 @@ fn space_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .collect::<Vec<MemInfo>>()
     );
     assert_ne!(
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -44,18 +56,16 @@ fn space_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .collect::<Vec<MemInfo>>()
     );
     assert_ne!(
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -65,18 +75,16 @@ fn space_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .collect::<Vec<MemInfo>>()
     );
     assert_ne!(
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -87,18 +95,16 @@ fn space_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .collect::<Vec<MemInfo>>()
     );
     assert_ne!(
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -109,18 +115,16 @@ fn space_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .collect::<Vec<MemInfo>>()
     );
     assert_ne!(
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -130,18 +134,16 @@ fn space_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .collect::<Vec<MemInfo>>()
     );
     assert_ne!(
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -150,30 +152,27 @@ fn space_drop() {
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
-    space.delete_region("region");
+    space.delete_region("region").unwrap();
     println!(
         "{:?}",
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .collect::<Vec<MemInfo>>()
     );
     assert_eq!(
         heap.allocator
             .lock()
             .unwrap()
-            .alloced_blocks
-            .iter()
-            .map(|l| { l.car().unwrap() })
+            .alloced_list()
+            .into_iter()
             .find(|i| { i == &info }),
         None
     );
@@ -207,3 +206,970 @@ fn space_query() {
         region3.info
     );
 }
+
+#[test]
+fn space_region_index() {
+    let mut space = Space::new();
+    let ram = GHEAP.alloc(0x10, 1).unwrap();
+    for i in 0..0x100 {
+        space
+            .add_region(
+                &format!("mmio{}", i),
+                &Region::remap(0x1000 + i * 0x20, &ram),
+            )
+            .unwrap();
+    }
+    assert!(matches!(
+        space.add_region("mmio0", &Region::remap(0x8000, &ram)),
+        Err(Error::Renamed(_, _))
+    ));
+    //overlapped with the one below and the one above
+    match space.add_region("low", &Region::remap(0x100f, &ram)) {
+        Err(Error::Overlap(name, _)) => assert_eq!(name, "mmio0"),
+        _ => panic!("mmio0 is not overlapped!"),
+    }
+    match space.add_region("high", &Region::remap(0x1031, &ram)) {
+        Err(Error::Overlap(name, _)) => assert_eq!(name, "mmio2"),
+        _ => panic!("mmio2 is not overlapped!"),
+    }
+    space
+        .add_region("gap", &Region::remap(0x1010, &ram))
+        .unwrap();
+    assert_eq!(space.get_region("mmio8").unwrap().info.base, 0x1100);
+    space.delete_region("mmio8").unwrap();
+    assert!(space.get_region("mmio8").is_none());
+    assert!(space.get_region_by_addr(&0x1100).is_err());
+    assert!(matches!(
+        space.delete_region("mmio8"),
+        Err(Error::NotFound(_))
+    ));
+    space
+        .add_region("mmio8", &Region::remap(0x1100, &ram))
+        .unwrap();
+}
+
+#[test]
+fn space_try_access() {
+    let mut space = Space::new();
+    let heap = &GHEAP;
+    space
+        .add_region(
+            "region",
+            &Region::remap(0x80000000, &heap.alloc(16, 8).unwrap()),
+        )
+        .unwrap();
+    assert_eq!(
+        space.try_read_u32(&0x80000010),
+        Err(AccessError::Unmapped(0x80000010))
+    );
+    assert_eq!(space.read_u32(&0x80000010), Err(0x80000010));
+    assert_eq!(
+        space.try_write_u16(&0x80000001, 0),
+        Err(AccessError::Unaligned(0x80000001, 2))
+    );
+    assert_eq!(
+        space.try_write_bytes(&0x8000000c, &[0; 8]),
+        Err(AccessError::Unmapped(0x80000010))
+    );
+    space.try_write_u32(&0x80000004, 0xa5a5).unwrap();
+    assert_eq!(space.try_read_u32(&0x80000004), Ok(0xa5a5));
+}
+
+#[test]
+fn space_unaligned_split() {
+    let mut space = Space::new();
+    let heap = &GHEAP;
+    space
+        .add_region(
+            "bank0",
+            &Region::remap(0x80000000, &heap.alloc(8, 8).unwrap()),
+        )
+        .unwrap();
+    space
+        .add_region(
+            "bank1",
+            &Region::remap(0x80000008, &heap.alloc(8, 8).unwrap()),
+        )
+        .unwrap();
+    assert_eq!(
+        space.try_write_u32(&0x80000002, 0),
+        Err(AccessError::Unaligned(0x80000002, 4))
+    );
+    space.set_unaligned_policy(UnalignedPolicy::Split);
+    space.try_write_u32(&0x80000006, 0xdeadbeef).unwrap();
+    assert_eq!(space.try_read_u16(&0x80000006), Ok(0xbeef));
+    assert_eq!(space.try_read_u16(&0x80000008), Ok(0xdead));
+    assert_eq!(space.try_read_u64(&0x80000005), Ok(0xdeadbeef00));
+    assert_eq!(
+        space.try_read_u64(&0x8000000d),
+        Err(AccessError::Unmapped(0x80000010))
+    );
+    //no partial write
+    assert_eq!(
+        space.try_write_u32(&0x8000000e, 0x12345678),
+        Err(AccessError::Unmapped(0x80000010))
+    );
+    assert_eq!(space.try_read_u16(&0x8000000e), Ok(0));
+}
+
+#[test]
+fn space_lr_sc() {
+    let mut space = Space::new();
+    space
+        .add_region(
+            "ram",
+            &Region::remap(0x80000000, &GHEAP.alloc(0x100, 8).unwrap()),
+        )
+        .unwrap();
+    space.set_reservation_granule(16);
+    //no reservation
+    assert_eq!(
+        space.try_store_conditional_u32(0, &0x80000000, 1),
+        Ok(false)
+    );
+    assert_eq!(space.try_load_reserved_u32(0, &0x80000000), Ok(0));
+    assert_eq!(space.try_store_conditional_u32(0, &0x80000000, 1), Ok(true));
+    assert_eq!(space.try_read_u32(&0x80000000), Ok(1));
+    //reservation is consumed by sc
+    assert_eq!(
+        space.try_store_conditional_u32(0, &0x80000000, 2),
+        Ok(false)
+    );
+    assert_eq!(space.try_read_u32(&0x80000000), Ok(1));
+
+    //sc of another hart to the same granule breaks the reservation
+    space.try_load_reserved_u64(0, &0x80000008).unwrap();
+    space.try_load_reserved_u64(1, &0x80000000).unwrap();
+    assert_eq!(space.try_store_conditional_u64(1, &0x80000000, 3), Ok(true));
+    assert_eq!(
+        space.try_store_conditional_u64(0, &0x80000008, 4),
+        Ok(false)
+    );
+    assert_eq!(space.try_read_u64(&0x80000008), Ok(0));
+
+    //stores and amos break the reservation, reads and stores to other granules don't
+    let stores: Vec<Box<dyn Fn(&Space)>> = vec![
+        Box::new(|s| s.try_write_u8(&0x80000013, 0).unwrap()),
+        Box::new(|s| {
+            s.try_write_bytes(&0x8000000e, &[0; 4]).unwrap();
+        }),
+        Box::new(|s| {
+            s.try_amo_u32(&0x8000001c, AmoOp::Add, 1).unwrap();
+        }),
+        Box::new(|s| {
+            s.try_compare_exchange_u64(&0x80000018, 1 << 32, 1 << 32)
+                .unwrap();
+        }),
+    ];
+    for store in stores.iter() {
+        space.try_load_reserved_u32(0, &0x80000010).unwrap();
+        space.try_read_u32(&0x80000010).unwrap();
+        space.try_write_u64(&0x80000020, 0).unwrap();
+        space.try_compare_exchange_u32(&0x80000014, 1, 1).unwrap();
+        store(&space);
+        assert_eq!(
+            space.try_store_conditional_u32(0, &0x80000010, 5),
+            Ok(false)
+        );
+    }
+    //a new lr replaces the old reservation of the hart
+    space.try_load_reserved_u32(0, &0x80000010).unwrap();
+    space.try_load_reserved_u32(0, &0x80000040).unwrap();
+    assert_eq!(
+        space.try_store_conditional_u32(0, &0x80000010, 5),
+        Ok(false)
+    );
+
+    assert_eq!(
+        space.try_load_reserved_u32(0, &0x80000002),
+        Err(AccessError::Unaligned(0x80000002, 4))
+    );
+    assert_eq!(
+        space.try_amo_u64(&0x90000000, AmoOp::Swap, 0),
+        Err(AccessError::Unmapped(0x90000000))
+    );
+    assert_eq!(space.try_amo_u64(&0x80000018, AmoOp::Swap, 9), Ok(1 << 32));
+    assert_eq!(space.try_read_u64(&0x80000018), Ok(9));
+
+    //a split write failing in a later region still breaks the reservations it wrote through
+    let rom = Region::remap(0x80000100, &GHEAP.alloc(0x100, 8).unwrap());
+    rom.set_write_policy(WritePolicy::Fault);
+    space.add_region("rom", &rom).unwrap();
+    space.try_load_reserved_u32(0, &0x800000f0).unwrap();
+    assert_eq!(
+        space.try_write_bytes(&0x800000f8, &[1; 0x10]),
+        Err(AccessError::ReadOnly(0x80000100))
+    );
+    assert_eq!(
+        space.try_store_conditional_u32(0, &0x800000f0, 5),
+        Ok(false)
+    );
+}
+
+#[test]
+fn space_host_slice() {
+    let mut space = Space::new();
+    space
+        .add_region(
+            "ram",
+            &Region::remap(0x80000000, &GHEAP.alloc(0x100, 8).unwrap()),
+        )
+        .unwrap();
+    space
+        .add_region(
+            "ram1",
+            &Region::remap(0x80000100, &GHEAP.alloc(0x100, 8).unwrap()),
+        )
+        .unwrap();
+    space
+        .host_slice_mut(&0x800000f0, 0x10)
+        .unwrap()
+        .copy_from_slice(&[0xa5; 0x10]);
+    assert_eq!(space.read_u64(&0x800000f8), Ok(0xa5a5a5a5a5a5a5a5));
+    assert_eq!(
+        &space.host_slice(&0x800000fe, 2).unwrap()[..],
+        &[0xa5, 0xa5]
+    );
+    //must be inside one region
+    assert_eq!(
+        space.host_slice(&0x800000f8, 0x10).err(),
+        Some(AccessError::OutOfRange(0x800000f8, 0x10))
+    );
+    assert_eq!(
+        space.host_slice(&0x70000000, 1).err(),
+        Some(AccessError::Unmapped(0x70000000))
+    );
+}
+
+#[test]
+fn region_out_of_range_overflow() {
+    let fb = Rc::new(Fb::new(4, 4, PixelFormat::RGB565));
+    let io = Region::io(0, u64::MAX, Box::new(SimpleFb::new(&fb)));
+    assert_eq!(
+        io.try_read_u32(&(u64::MAX - 1)),
+        Err(AccessError::OutOfRange(u64::MAX - 1, 4))
+    );
+    assert_eq!(
+        AccessError::OutOfRange(0, 0).to_string(),
+        "OutOfRange!0-byte access @0x0"
+    );
+    assert_eq!(
+        AccessError::OutOfRange(u64::MAX, 2).to_string(),
+        "OutOfRange!2-byte access @0xffffffffffffffff"
+    );
+}
+
+#[test]
+fn space_cross_region_bytes() {
+    let mut space = Space::new();
+    let heap = &GHEAP;
+    space
+        .add_region(
+            "bank0",
+            &Region::remap(0x80000000, &heap.alloc(8, 8).unwrap()),
+        )
+        .unwrap();
+    space
+        .add_region(
+            "bank1",
+            &Region::remap(0x80000008, &heap.lazy_alloc(8, 8).unwrap()),
+        )
+        .unwrap();
+    space
+        .add_region(
+            "bank2",
+            &Region::remap(0x80000020, &heap.alloc(8, 8).unwrap()),
+        )
+        .unwrap();
+    let data = (0..12).collect::<Vec<u8>>();
+    assert_eq!(space.write_bytes(&0x80000002, &data), Ok(12));
+    assert_eq!(space.read_u8(&0x80000009), Ok(7));
+    let mut read_back = [0u8; 12];
+    assert_eq!(space.read_bytes(&0x80000002, &mut read_back), Ok(12));
+    assert_eq!(&read_back[..], data.as_slice());
+
+    let mut buffer = [0u8; 0x20];
+    assert_eq!(
+        space.try_read_bytes(&0x80000008, &mut buffer),
+        Err(AccessError::Unmapped(0x80000010))
+    );
+    assert_eq!(
+        space.try_write_bytes(&0x80000004, &buffer),
+        Err(AccessError::Unmapped(0x80000010))
+    );
+    assert_eq!(space.read_u8(&0x80000004), Ok(2));
+    //empty transfers succeed anywhere
+    assert_eq!(space.try_write_bytes(&0x70000000, &[]), Ok(0));
+    assert_eq!(space.try_read_bytes(&0x70000000, &mut []), Ok(0));
+
+    //ranges wrapping around the address space
+    space
+        .add_region(
+            "top",
+            &Region::remap(0xffff_ffff_ffff_e000, &heap.alloc(0x1000, 8).unwrap()),
+        )
+        .unwrap();
+    assert_eq!(
+        space.try_read_bytes(&(u64::MAX - 3), &mut buffer),
+        Err(AccessError::OutOfRange(u64::MAX - 3, 0x20))
+    );
+    assert_eq!(
+        space.check_access(&(u64::MAX - 3), 0x20, AccessType::Read),
+        Err(AccessError::OutOfRange(u64::MAX - 3, 0x20))
+    );
+    assert_eq!(
+        space.set_attr(
+            &0xffff_ffff_ffff_e000,
+            u64::MAX,
+            Attr::new(Perm::R, MemType::Cacheable)
+        ),
+        Err(AccessError::OutOfRange(0xffff_ffff_ffff_e000, usize::MAX))
+    );
+}
+
+struct TraceSink(Rc<RefCell<Vec<u8>>>);
+
+impl Write for TraceSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn space_watchpoint() {
+    let mut space = Space::new();
+    let heap = &GHEAP;
+    space
+        .add_region(
+            "bank0",
+            &Region::remap(0x80000000, &heap.alloc(8, 8).unwrap()),
+        )
+        .unwrap();
+    space
+        .add_region(
+            "bank1",
+            &Region::remap(0x80000008, &heap.alloc(8, 8).unwrap()),
+        )
+        .unwrap();
+    let reads = Rc::new(Cell::new(0));
+    let reads_cb = reads.clone();
+    let id = space.add_watchpoint(0x80000006, 4, WatchKind::Read, move |access| {
+        reads_cb.set(reads_cb.get() + access.data.len())
+    });
+    let log = Rc::new(RefCell::new(vec![]));
+    space.set_trace(Some(Box::new(TraceSink(log.clone()))));
+    space.write_u16(&0x80000006, 0xbeef).unwrap();
+    assert_eq!(space.read_u16(&0x80000006), Ok(0xbeef));
+    let mut data = [0u8; 8];
+    space.read_bytes(&0x80000004, &mut data).unwrap();
+    space.read_u32(&0x80000000).unwrap();
+    assert_eq!(reads.get(), 10);
+    assert_eq!(
+        String::from_utf8(log.borrow().clone()).unwrap(),
+        "W 0x00000080000006 [2] [ef, be]\n\
+         R 0x00000080000006 [2] [ef, be]\n\
+         R 0x00000080000004 [8] [00, 00, ef, be, 00, 00, 00, 00]\n\
+         R 0x00000080000000 [4] [00, 00, 00, 00]\n"
+    );
+    let log_len = log.borrow().len();
+    assert!(space.remove_watchpoint(id));
+    space.set_trace(None);
+    space.read_u16(&0x80000006).unwrap();
+    assert_eq!(reads.get(), 10);
+    assert_eq!(log.borrow().len(), log_len);
+
+    //a one-shot watchpoint removing itself
+    let space = Rc::new(space);
+    let id = Rc::new(Cell::new(0));
+    let (space_cb, id_cb, reads_cb) = (Rc::downgrade(&space), id.clone(), reads.clone());
+    id.set(
+        space.add_watchpoint(0x80000000, 16, WatchKind::Read, move |_| {
+            reads_cb.set(reads_cb.get() + 1);
+            assert!(space_cb.upgrade().unwrap().remove_watchpoint(id_cb.get()));
+        }),
+    );
+    space.read_u8(&0x80000000).unwrap();
+    space.read_u8(&0x80000000).unwrap();
+    assert_eq!(reads.get(), 11);
+
+    //a failing sink stops the trace
+    let writes = Rc::new(Cell::new(0));
+    space.set_trace(Some(Box::new(FailingSink(writes.clone()))));
+    space.read_u8(&0x80000000).unwrap();
+    space.read_u8(&0x80000000).unwrap();
+    assert_eq!(writes.get(), 1);
+}
+
+struct FailingSink(Rc<Cell<usize>>);
+
+impl Write for FailingSink {
+    fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+        self.0.set(self.0.get() + 1);
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "sink is closed",
+        ))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn space_snapshot() {
+    let build = |label: &str| {
+        let mut space = Space::new();
+        let heap = &GHEAP;
+        space
+            .add_region(
+                "ram",
+                &Region::remap(0x80000000, &heap.alloc(0x100, 8).unwrap()),
+            )
+            .unwrap();
+        space
+            .add_region(
+                "ram_alias",
+                &Region::remap(0x90000000, &space.get_region("ram").unwrap()),
+            )
+            .unwrap();
+        space
+            .add_region(
+                "dram",
+                &Region::remap(0xa0000000, &heap.lazy_alloc(0x10000, 8).unwrap()),
+            )
+            .unwrap();
+        let fb = Rc::new(Fb::new(4, 4, PixelFormat::RGB565));
+        space
+            .add_region(
+                "fb",
+                &Region::remap(0xb0000000, &Region::io(0, 32, Box::new(SimpleFb::new(&fb)))),
+            )
+            .unwrap();
+        let ram_heap = Heap::new(&space.get_region("ram").unwrap());
+        let block = ram_heap.alloc_labeled(0x10, 8, label).unwrap();
+        (space, ram_heap, block)
+    };
+    let (space, ram_heap, block) = build("block");
+    space.write_u64(&0x80000008, 0xdeadbeef).unwrap();
+    space.write_u32(&0xa0001ffc, 0x12345678).unwrap();
+    space.write_u8(&0xb0000003, 0x5a).unwrap();
+    let mut image: Vec<u8> = vec![];
+    space.snapshot_to(&mut image).unwrap();
+
+    space.write_u64(&0x90000008, 0).unwrap();
+    space.write_u32(&0xa0003000, 1).unwrap();
+    space.write_u8(&0xb0000003, 0).unwrap();
+    let leaked = ram_heap.alloc(0x10, 8).unwrap();
+    assert!(space.restore_from(&mut image.as_slice()).is_err());
+    std::mem::drop(leaked);
+    space.restore_from(&mut image.as_slice()).unwrap();
+    assert_eq!(space.read_u64(&0x80000008), Ok(0xdeadbeef));
+    assert_eq!(space.read_u32(&0xa0001ffc), Ok(0x12345678));
+    assert_eq!(space.read_u32(&0xa0003000), Ok(0));
+    assert_eq!(space.read_u8(&0xb0000003), Ok(0x5a));
+
+    //fork another platform from the snapshot file
+    let file_name = std::env::temp_dir().join("terminus_spaceport_space_snapshot.bin");
+    let file_name = file_name.to_str().unwrap();
+    space.snapshot(file_name).unwrap();
+    let (forked, forked_heap, _forked_block) = build("forked");
+    forked.restore(file_name).unwrap();
+    std::fs::remove_file(file_name).unwrap();
+    assert_eq!(forked.read_u64(&0x90000008), Ok(0xdeadbeef));
+    assert_eq!(forked.read_u32(&0xa0001ffc), Ok(0x12345678));
+    assert_eq!(forked.read_u8(&0xb0000003), Ok(0x5a));
+    let live = forked_heap.allocator.borrow().live_blocks();
+    assert_eq!(live.len(), 1);
+    assert_eq!(
+        live[0].info.base - 0x80000000,
+        block.info.base - ram_heap.get_region().info.base
+    );
+    assert_eq!(live[0].label.as_deref(), Some("block"));
+
+    //a block freed since the snapshot would be restored without a region to free it
+    std::mem::drop(block);
+    match space.restore_from(&mut image.as_slice()) {
+        Err(Error::Snapshot(msg)) => assert!(msg.ends_with("in snapshot is not live!")),
+        _ => panic!("restore of a freed block should fail!"),
+    }
+
+    let mut other = Space::new();
+    other
+        .add_region(
+            "ram",
+            &Region::remap(0x80000000, &GHEAP.alloc(0x80, 8).unwrap()),
+        )
+        .unwrap();
+    match other.restore_from(&mut image.as_slice()) {
+        Err(Error::Snapshot(msg)) => assert!(msg.starts_with("region map mismatch!")),
+        _ => panic!("restore into a different region map should fail!"),
+    }
+    assert!(other.restore_from(&mut &image[1..]).is_err());
+}
+
+#[test]
+fn space_snapshot_v1() {
+    let mut space = Space::new();
+    space
+        .add_region(
+            "ram",
+            &Region::remap(0x1000, &GHEAP.alloc(0x10, 8).unwrap()),
+        )
+        .unwrap();
+    space.set_region_enabled("ram", false).unwrap();
+    //without enabled flags and spaces behind bridges
+    let mut image: Vec<u8> = b"TSSPACE\0".to_vec();
+    put_u32(&mut image, 1).unwrap();
+    put_u32(&mut image, 1).unwrap();
+    put_str(&mut image, "ram").unwrap();
+    put_u64(&mut image, 0x1000).unwrap();
+    put_u64(&mut image, 0x10).unwrap();
+    put_u32(&mut image, 1).unwrap();
+    put_str(&mut image, "ram").unwrap();
+    put_u8(&mut image, 0).unwrap();
+    put_u64(&mut image, 0x10).unwrap();
+    put_bytes(&mut image, &[0xa5; 0x10]).unwrap();
+    put_u32(&mut image, 0).unwrap();
+    space.restore_from(&mut image.as_slice()).unwrap();
+    assert_eq!(space.region_enabled("ram"), Some(true));
+    assert_eq!(space.read_u32(&0x1000), Ok(0xa5a5_a5a5));
+}
+
+#[test]
+fn space_endian() {
+    let mut space = Space::new();
+    let ram = GHEAP.alloc(0x100, 8).unwrap();
+    let be = space
+        .add_region("ram", &Region::remap(0x80000000, &ram))
+        .unwrap();
+    //follow the region
+    be.set_endian(Endian::Big);
+    assert_eq!(space.endian(), None);
+    space.write_u32(&0x80000000, 0x11223344).unwrap();
+    assert_eq!(ram.try_read_u32(&ram.info.base), Ok(0x44332211));
+    assert_eq!(
+        space.try_read_u32_endian(&0x80000000, Endian::Little),
+        Ok(0x44332211)
+    );
+    //the space overrides the regions
+    space.set_endian(Some(Endian::Little));
+    assert_eq!(space.read_u32(&0x80000000), Ok(0x44332211));
+    space.set_endian(Some(Endian::Big));
+    be.set_endian(Endian::Little);
+    assert_eq!(space.read_u16(&0x80000002), Ok(0x3344));
+
+    let bytes = Rc::new(RefCell::new(vec![]));
+    let b = Rc::clone(&bytes);
+    space.add_watchpoint(0x80000008, 8, WatchKind::Write, move |a| {
+        b.borrow_mut().push(a.data.to_vec())
+    });
+    space.set_unaligned_policy(UnalignedPolicy::Split);
+    space.write_u64(&0x80000008, 0x0102030405060708).unwrap();
+    space.write_u32(&0x8000000b, 0xaabbccdd).unwrap();
+    assert_eq!(space.read_u32(&0x8000000b), Ok(0xaabbccdd));
+    assert_eq!(space.read_u64(&0x80000008), Ok(0x010203aabbccdd08));
+    assert_eq!(
+        *bytes.borrow(),
+        vec![vec![1, 2, 3, 4, 5, 6, 7, 8], vec![0xaa, 0xbb, 0xcc, 0xdd]]
+    );
+
+    //atomics and lr/sc
+    assert_eq!(
+        space.try_amo_u64(&0x80000008, AmoOp::Add, 0xf8),
+        Ok(0x010203aabbccdd08)
+    );
+    assert_eq!(
+        ram.try_read_u64(&(ram.info.base + 8)),
+        Ok(0x00deccbbaa030201)
+    );
+    assert_eq!(space.try_load_reserved_u32(0, &0x80000010), Ok(0));
+    assert_eq!(space.try_store_conditional_u32(0, &0x80000010, 1), Ok(true));
+    assert_eq!(ram.try_read_u32(&(ram.info.base + 0x10)), Ok(1 << 24));
+}
+
+#[test]
+fn space_attr() {
+    let mut space = Space::new();
+    let rom = Attr::new(Perm::RX, MemType::Cacheable);
+    let dev = Attr::new(Perm::RWX, MemType::Device);
+    space
+        .add_region_with_attr(
+            "rom",
+            &Region::remap(0x1000, &GHEAP.alloc(0x100, 8).unwrap()),
+            rom,
+        )
+        .unwrap();
+    space
+        .add_region_with_attr(
+            "dev",
+            &Region::remap(0x1100, &GHEAP.alloc(0x100, 8).unwrap()),
+            dev,
+        )
+        .unwrap();
+    space
+        .add_region(
+            "ram",
+            &Region::remap(0x2000, &GHEAP.alloc(0x100, 8).unwrap()),
+        )
+        .unwrap();
+    assert_eq!(space.get_attr(&0x10ff), Some(rom));
+    assert_eq!(space.get_attr(&0x2000), Some(Attr::default()));
+    assert_eq!(space.get_attr(&0x1200), None);
+
+    //unchecked accesses ignore attributes
+    space.write_u32(&0x1000, 0x13).unwrap();
+    assert_eq!(space.try_fetch_u32(&0x1000), Ok(0x13));
+    assert_eq!(
+        space.try_write_u32_checked(&0x1000, 0),
+        Err(AccessError::Denied(0x1000, AccessType::Write, rom))
+    );
+    assert_eq!(
+        space.try_fetch_u16(&0x1100),
+        Err(AccessError::Denied(0x1100, AccessType::Fetch, dev))
+    );
+    space.try_write_u64_checked(&0x1100, 1).unwrap();
+    assert_eq!(space.try_read_u64_checked(&0x1100), Ok(1));
+    //the first denied or unmapped byte is reported, nothing is accessed
+    assert_eq!(
+        space.try_fetch_bytes(&0x10fc, &mut [0; 8]),
+        Err(AccessError::Denied(0x1100, AccessType::Fetch, dev))
+    );
+    assert_eq!(
+        space.try_write_bytes_checked(&0x11fc, &[1; 8]),
+        Err(AccessError::Unmapped(0x1200))
+    );
+    assert_eq!(space.read_u32(&0x11fc), Ok(0));
+    assert_eq!(
+        format!(
+            "{}",
+            space
+                .check_access(&0x10f0, 1, AccessType::Write)
+                .unwrap_err()
+        ),
+        "Denied!Write @0x10f0 with r-x:Cacheable"
+    );
+
+    //change attributes of a subrange at runtime
+    space
+        .set_attr(&0x2040, 0x40, Attr::new(Perm::R, MemType::Uncacheable))
+        .unwrap();
+    assert_eq!(space.get_attr(&0x203f), Some(Attr::default()));
+    assert_eq!(
+        space.get_attr(&0x2040),
+        Some(Attr::new(Perm::R, MemType::Uncacheable))
+    );
+    assert_eq!(space.get_attr(&0x2080), Some(Attr::default()));
+    assert!(space.try_write_u8_checked(&0x207f, 1).is_err());
+    space.try_write_u8_checked(&0x2080, 1).unwrap();
+    space
+        .set_attr(&0x2000, 0x50, Attr::new(Perm::NONE, MemType::Cacheable))
+        .unwrap();
+    assert_eq!(space.get_attr(&0x2050).unwrap().perm, Perm::R);
+    assert!(space.try_read_u8_checked(&0x204f).is_err());
+    assert_eq!(
+        space.set_attr(&0x20f0, 0x20, Attr::default()),
+        Err(AccessError::OutOfRange(0x20f0, 0x20))
+    );
+    assert_eq!(
+        space.set_attr(&0x3000, 0x20, Attr::default()),
+        Err(AccessError::Unmapped(0x3000))
+    );
+    //attributes go with the region
+    space.delete_region("ram").unwrap();
+    assert_eq!(space.get_attr(&0x2050), None);
+    space
+        .add_region(
+            "ram",
+            &Region::remap(0x2000, &GHEAP.alloc(0x100, 8).unwrap()),
+        )
+        .unwrap();
+    assert_eq!(space.get_attr(&0x2050), Some(Attr::default()));
+}
+
+#[test]
+fn space_bridge() {
+    //a peripheral bus with its own decode, mounted twice on the system bus
+    let mut apb = Space::new();
+    apb.add_region(
+        "uart",
+        &Region::remap(0x100, &GHEAP.alloc(0x100, 8).unwrap()),
+    )
+    .unwrap();
+    apb.add_region(
+        "timer",
+        &Region::remap(0x1000, &GHEAP.alloc(0x100, 8).unwrap()),
+    )
+    .unwrap();
+    let apb = Rc::new(apb);
+    let mut space = Space::new();
+    let bridge = space
+        .add_region("apb", &Region::bridge(0x4000_0000, 0x2000, &apb, 0))
+        .unwrap();
+    space
+        .add_region("apb_hi", &Region::bridge(0x5000_0000, 0x1000, &apb, 0x1000))
+        .unwrap();
+
+    space.write_u32(&0x4000_0104, 0xdead_beef).unwrap();
+    assert_eq!(apb.read_u32(&0x104).unwrap(), 0xdead_beef);
+    space.write_u64(&0x5000_0008, 0x1234).unwrap();
+    assert_eq!(space.read_u64(&0x4000_1008).unwrap(), 0x1234);
+    assert_eq!(
+        space.try_amo_u32(&0x4000_0104, AmoOp::Add, 1),
+        Ok(0xdead_beef)
+    );
+    assert_eq!(apb.read_u32(&0x104).unwrap(), 0xdead_bef0);
+    let mut bytes = [0u8; 4];
+    space.read_bytes(&0x4000_0104, &mut bytes).unwrap();
+    assert_eq!(u32::from_le_bytes(bytes), 0xdead_bef0);
+    //holes of the bus are reported at the address on the system bus
+    assert_eq!(space.read_u8(&0x4000_0800), Err(0x4000_0800));
+    assert_eq!(space.read_u8(&0x4000_2000), Err(0x4000_2000));
+
+    //hooks see the address on the bus
+    let seen = Rc::new(RefCell::new(vec![]));
+    let hook_seen = Rc::clone(&seen);
+    bridge.set_bridge_hook(move |kind, addr, size| {
+        hook_seen.borrow_mut().push((kind, addr, size));
+        if kind == AccessKind::Write && addr >= 0x1000 {
+            Err("timer is locked!".to_string())
+        } else {
+            Ok(())
+        }
+    });
+    space.read_u16(&0x4000_0100).unwrap();
+    assert_eq!(
+        space.try_write_u32(&0x4000_1000, 1),
+        Err(AccessError::DeviceError(
+            0x4000_1000,
+            "timer is locked!".to_string()
+        ))
+    );
+    assert_eq!(
+        space.try_amo_u32(&0x4000_1000, AmoOp::Swap, 1),
+        Err(AccessError::DeviceError(
+            0x4000_1000,
+            "timer is locked!".to_string()
+        ))
+    );
+    //not through the hooked bridge
+    space.write_u32(&0x5000_0000, 1).unwrap();
+    assert_eq!(
+        *seen.borrow(),
+        vec![
+            (AccessKind::Read, 0x100, 2),
+            (AccessKind::Write, 0x1000, 4),
+            (AccessKind::Write, 0x1000, 4)
+        ]
+    );
+    bridge.clear_bridge_hook();
+    space.write_u32(&0x4000_1000, 2).unwrap();
+    assert_eq!(apb.read_u32(&0x1000).unwrap(), 2);
+
+    //atomics through the bridge break reservations on the bus
+    assert_eq!(apb.try_load_reserved_u32(0, &0x104), Ok(0xdead_bef0));
+    space.try_amo_u32(&0x4000_0104, AmoOp::Add, 1).unwrap();
+    assert_eq!(apb.try_store_conditional_u32(0, &0x104, 0), Ok(false));
+    assert_eq!(
+        space.try_compare_exchange_u32(&0x4000_0104, 0xdead_bef1, 0xdead_beef),
+        Ok(0xdead_bef1)
+    );
+
+    //spaces behind bridges are saved with the space, heaps of the bus as well
+    let uart_heap = Heap::new(&apb.get_region("uart").unwrap());
+    let _block = uart_heap.alloc(0x10, 8).unwrap();
+    space.write_u32(&0x4000_0104, 0xdead_beef).unwrap();
+    let mut image: Vec<u8> = vec![];
+    space.snapshot_to(&mut image).unwrap();
+    space.write_u32(&0x5000_0000, 0).unwrap();
+    space.write_u32(&0x4000_0104, 0x1234).unwrap();
+    space.restore_from(&mut image.as_slice()).unwrap();
+    assert_eq!(space.read_u32(&0x4000_0104).unwrap(), 0xdead_beef);
+    assert_eq!(space.read_u32(&0x5000_0000).unwrap(), 2);
+    assert_eq!(uart_heap.allocator.borrow().alloced_list().len(), 1);
+
+    let tree = space.to_string();
+    assert!(tree.contains("apb_hi    (Bridge(@0x1000))"), "{}", tree);
+    assert!(tree.contains("\n      uart      "), "{}", tree);
+    assert_eq!(tree.matches("timer").count(), 2, "{}", tree);
+}
+
+#[test]
+fn space_priority() {
+    let mut space = Space::new();
+    let dram = space
+        .add_region(
+            "dram",
+            &Region::remap(0x1000_0000, &GHEAP.alloc(0x4000, 8).unwrap()),
+        )
+        .unwrap();
+    //the boot rom shadows the dram until remapped, a default slave is behind everything
+    let rom_attr = Attr::new(Perm::RX, MemType::Cacheable);
+    let rom = space
+        .add_region_with_priority(
+            "bootrom",
+            &Region::remap(0x1000_0000, &GHEAP.alloc(0x1000, 8).unwrap()),
+            rom_attr,
+            1,
+        )
+        .unwrap();
+    let default = space
+        .add_region_with_priority(
+            "default",
+            &Region::remap(0x0fff_0000, &GHEAP.lazy_alloc(0x20000, 8).unwrap()),
+            Attr::default(),
+            -1,
+        )
+        .unwrap();
+    assert!(matches!(
+        space.add_region_with_priority(
+            "rom2",
+            &Region::remap(0x1000_0800, &GHEAP.alloc(0x1000, 8).unwrap()),
+            rom_attr,
+            1
+        ),
+        Err(Error::Overlap(_, _))
+    ));
+    assert!(matches!(
+        space.add_region_with_priority("dram", &dram, Attr::default(), 2),
+        Err(Error::Renamed(_, _))
+    ));
+
+    rom.try_write_u32(&0x1000_0000, 0x1234_5678).unwrap();
+    dram.try_write_u32(&0x1000_0000, 0xdead_beef).unwrap();
+    assert_eq!(space.read_u32(&0x1000_0000).unwrap(), 0x1234_5678);
+    assert_eq!(space.get_attr(&0x1000_0000), Some(rom_attr));
+    assert!(matches!(
+        space.try_write_u32_checked(&0x1000_0000, 0),
+        Err(AccessError::Denied(0x1000_0000, AccessType::Write, _))
+    ));
+    //transfers are split where the decode changes
+    space.write_bytes(&0x1000_0ffe, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(rom.try_read_u16(&0x1000_0ffe).unwrap(), 0x0201);
+    assert_eq!(dram.try_read_u16(&0x1000_1000).unwrap(), 0x0403);
+    space.write_bytes(&0x0fff_fffe, &[5, 6, 7, 8]).unwrap();
+    assert_eq!(default.try_read_u16(&0x0fff_fffe).unwrap(), 0x0605);
+    assert_eq!(rom.try_read_u16(&0x1000_0000).unwrap(), 0x0807);
+    assert_eq!(
+        space.try_read_u32(&0x0fff_fffe),
+        Err(AccessError::OutOfRange(0x0fff_fffe, 4))
+    );
+    space.write_u32(&0x1000_5000, 0x55).unwrap();
+    assert_eq!(default.try_read_u32(&0x1000_5000).unwrap(), 0x55);
+
+    //remap registers
+    space.set_region_enabled("bootrom", false).unwrap();
+    assert_eq!(space.region_enabled("bootrom"), Some(false));
+    assert_eq!(space.read_u32(&0x1000_0000).unwrap(), 0xdead_beef);
+    assert_eq!(space.get_attr(&0x1000_0000), Some(Attr::default()));
+    space.set_region_enabled("dram", false).unwrap();
+    assert_eq!(space.read_u32(&0x1000_2000).unwrap(), 0);
+    assert!(space.set_region_enabled("none", true).is_err());
+    let tree = space.to_string();
+    assert!(tree.contains("priority 1 disabled"), "{}", tree);
+    assert!(tree.contains("priority -1\n"), "{}", tree);
+
+    let mut image: Vec<u8> = vec![];
+    space.snapshot_to(&mut image).unwrap();
+    space.set_region_enabled("bootrom", true).unwrap();
+    space.set_region_enabled("dram", true).unwrap();
+    space.restore_from(&mut image.as_slice()).unwrap();
+    assert_eq!(space.region_enabled("bootrom"), Some(false));
+    assert_eq!(space.region_enabled("dram"), Some(false));
+
+    space.delete_region("bootrom").unwrap();
+    assert!(space.get_region("bootrom").is_none());
+    space.set_region_enabled("dram", true).unwrap();
+    assert_eq!(space.read_u32(&0x1000_0000).unwrap(), 0xdead_beef);
+}
+
+#[test]
+fn space_unmapped_policy() {
+    let mut space = Space::new();
+    space
+        .add_region(
+            "ram",
+            &Region::remap(0x1000, &GHEAP.alloc(0x100, 8).unwrap()),
+        )
+        .unwrap();
+    assert_eq!(space.read_u32(&0x2000), Err(0x2000));
+    assert_eq!(
+        space.last_fault(),
+        Some(BusFault {
+            kind: AccessKind::Read,
+            addr: 0x2000,
+            size: 4
+        })
+    );
+    space.clear_last_fault();
+    assert_eq!(space.last_fault(), None);
+
+    space.set_unmapped_policy(UnmappedPolicy::Fill(0xffff_ffff));
+    assert_eq!(space.read_u32(&0x2000), Ok(0xffff_ffff));
+    assert_eq!(space.read_u16(&0x2000), Ok(0xffff));
+    //the value is sized per access
+    assert_eq!(space.read_u64(&0x2000), Ok(0xffff_ffff));
+    space.set_unmapped_policy(UnmappedPolicy::Fill(u64::MAX));
+    assert_eq!(space.read_u64(&0x2000), Ok(u64::MAX));
+    assert_eq!(space.read_u8(&0x2000), Ok(0xff));
+    //the hole is reported at its first byte
+    let mut bytes = [0u8; 0x10];
+    assert_eq!(space.read_bytes(&0x10f8, &mut bytes), Ok(0x10));
+    assert_eq!(bytes, [0xff; 0x10]);
+    assert_eq!(space.last_fault().unwrap().addr, 0x1100);
+    assert_eq!(space.write_u32(&0x2000, 1), Ok(()));
+    assert_eq!(space.last_fault().unwrap().kind, AccessKind::Write);
+    //running off the end of a region
+    assert_eq!(space.read_u32(&0x10fe), Ok(0xffff_ffff));
+    assert_eq!(space.last_fault().unwrap().addr, 0x10fe);
+    //faults of an iommu view
+    let iommu = Region::iommu(0x4000, 0x1000, &space.get_region("ram").unwrap());
+    space.add_region("iommu", &iommu).unwrap();
+    assert_eq!(space.read_u16(&0x4000), Ok(0xffff));
+    assert_eq!(
+        space.last_fault(),
+        Some(BusFault {
+            kind: AccessKind::Read,
+            addr: 0x4000,
+            size: 2
+        })
+    );
+    iommu.iommu_map(0x4000, 0x1000, 0x100, Perm::R).unwrap();
+    assert_eq!(space.write_u8(&0x4000, 1), Ok(()));
+    assert_eq!(space.last_fault().unwrap().kind, AccessKind::Write);
+    assert_eq!(space.read_u8(&0x4000), Ok(0));
+    //try_ accesses are not affected
+    assert_eq!(
+        space.try_read_u8(&0x2000),
+        Err(AccessError::Unmapped(0x2000))
+    );
+
+    //a bus error above 0x3000
+    let faults = Rc::new(Cell::new(0));
+    let f = Rc::clone(&faults);
+    space.set_unmapped_policy(UnmappedPolicy::Callback(Rc::new(move |fault| {
+        f.set(f.get() + 1);
+        if fault.addr < 0x3000 {
+            Ok(0xdead_beef)
+        } else {
+            Err(())
+        }
+    })));
+    assert_eq!(space.read_u32(&0x2000), Ok(0xdead_beef));
+    assert_eq!(space.read_u8(&0x2000), Ok(0xef));
+    assert_eq!(space.write_u8(&0x2000, 0), Ok(()));
+    assert_eq!(space.read_u32(&0x3000), Err(0x3000));
+    assert_eq!(space.write_bytes(&0x3000, &[0; 4]), Err(0x3000));
+    assert_eq!(faults.get(), 5);
+    assert_eq!(space.read_u32(&0x1000), Ok(0));
+    assert_eq!(faults.get(), 5);
+    //misaligned accesses are bus faults as well
+    space.set_unaligned_policy(UnalignedPolicy::Fault);
+    assert_eq!(space.read_u32(&0x1002), Ok(0xdead_beef));
+    assert_eq!(space.last_fault().unwrap().addr, 0x1002);
+    assert_eq!(faults.get(), 6);
+}
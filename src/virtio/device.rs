@@ -1,5 +1,6 @@
 use super::queue::Queue;
 use crate::irq::{IrqVec, IrqVecSender};
+use crate::memory::region::snapshot::{get_u32, get_u64, invalid, put_u32, put_u64};
 use crate::memory::region::Region;
 use crate::virtio::{
     MAX_QUEUE,
@@ -27,6 +28,8 @@ use crate::virtio::{
     MMIO_VERSION,
 };
 use std::cell::RefCell;
+use std::io;
+use std::io::{Read, Write};
 use std::rc::Rc;
 
 pub struct Device {
@@ -85,6 +88,46 @@ impl Device {
         }
     }
 
+    pub fn save(&self, w: &mut dyn Write) -> io::Result<()> {
+        put_u32(w, *self.queue_sel.borrow())?;
+        put_u32(w, *self.status.borrow())?;
+        put_u32(w, *self.device_features_sel.borrow())?;
+        put_u64(w, self.irq_vec.pendings())?;
+        put_u32(w, self.queues.len() as u32)?;
+        for q in self.queues.iter() {
+            q.save(w)?
+        }
+        Ok(())
+    }
+
+    //the irq line is raised again if there are pending irqs
+    pub fn load(&self, r: &mut dyn Read) -> io::Result<()> {
+        *self.queue_sel.borrow_mut() = get_u32(r)?;
+        *self.status.borrow_mut() = get_u32(r)?;
+        *self.device_features_sel.borrow_mut() = get_u32(r)?;
+        let pendings = get_u64(r)?;
+        if get_u32(r)? as usize != self.queues.len() {
+            return Err(invalid("virtio queue number mismatch!"));
+        }
+        for q in self.queues.iter() {
+            q.load(r)?
+        }
+        self.irq_vec.clr_pendings(!0);
+        for i in 0..64 {
+            if (pendings >> i) & 0x1 != 0 {
+                self.irq_vec
+                    .set_pending(i, true)
+                    .map_err(|e| invalid(format!("{:?}", e)))?
+            }
+        }
+        if pendings != 0 {
+            self.irq_sender.send()
+        } else {
+            self.irq_sender.clear()
+        }
+        .map_err(|e| invalid(format!("{:?}", e)))
+    }
+
     pub fn irq_id(&self) -> usize {
         self.irq_sender.id()
     }
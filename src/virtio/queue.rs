@@ -1,9 +1,13 @@
 #![allow(dead_code)]
 
-use crate::memory::region::{BytesAccess, Heap, Region, SizedAccess, U16Access};
+use crate::memory::allocator::AllocError;
+use crate::memory::region::snapshot::{get_u32, get_u64, get_u8, put_u32, put_u64, put_u8};
+use crate::memory::region::{AccessError, BytesAccess, Endian, Heap, Region, SizedAccess};
 use crate::virtio::{DESC_F_NEXT, DESC_F_WRITE};
 use std::cell::RefCell;
 use std::cmp::min;
+use std::io;
+use std::io::{Read, Write};
 use std::marker::PhantomData;
 use std::num::Wrapping;
 use std::ops::Deref;
@@ -28,6 +32,19 @@ impl From<String> for Error {
     }
 }
 
+impl From<AllocError> for Error {
+    fn from(error: AllocError) -> Self {
+        Error::MemError(error.to_string())
+    }
+}
+
+//e.g. faults of an iommu view, reported to the device instead of panicking
+impl From<AccessError> for Error {
+    fn from(error: AccessError) -> Self {
+        Error::MemError(error.to_string())
+    }
+}
+
 pub type Result<T> = result::Result<T, Error>;
 
 #[derive(Copy, Clone)]
@@ -102,6 +119,26 @@ impl Queue {
         *self.used_addr.borrow_mut() = 0;
     }
 
+    //rings are in guest memory, only the registers are saved
+    pub fn save(&self, w: &mut dyn Write) -> io::Result<()> {
+        put_u8(w, *self.ready.borrow() as u8)?;
+        put_u32(w, *self.queue_size.borrow() as u32)?;
+        put_u32(w, self.last_avail_idx.borrow().0 as u32)?;
+        put_u64(w, *self.desc_addr.borrow())?;
+        put_u64(w, *self.avail_addr.borrow())?;
+        put_u64(w, *self.used_addr.borrow())
+    }
+
+    pub fn load(&self, r: &mut dyn Read) -> io::Result<()> {
+        *self.ready.borrow_mut() = get_u8(r)? != 0;
+        *self.queue_size.borrow_mut() = get_u32(r)? as u16;
+        *self.last_avail_idx.borrow_mut() = Wrapping(get_u32(r)? as u16);
+        *self.desc_addr.borrow_mut() = get_u64(r)?;
+        *self.avail_addr.borrow_mut() = get_u64(r)?;
+        *self.used_addr.borrow_mut() = get_u64(r)?;
+        Ok(())
+    }
+
     fn check_idx(&self, idx: u16) -> Result<()> {
         if idx as usize >= self.get_queue_size() {
             Err(Error::InvalidDesc(format!("invalid desc idx! {}", idx)))
@@ -214,48 +251,64 @@ impl Queue {
         *self.queue_size.borrow_mut() = size
     }
 
-    pub fn set_desc(&self, idx: u16, desc: &DescMeta) -> Result<()> {
-        SizedAccess::write(self.memory.deref(), &self.desc_addr(idx)?, desc);
+    //ring accesses fail instead of panicking, so the device sees faults of the memory
+    fn write_ring<T: Sized>(&self, addr: u64, data: &T) -> Result<()> {
+        self.memory.try_write_bytes(&addr, unsafe {
+            std::slice::from_raw_parts((data as *const T) as *const u8, mem::size_of::<T>())
+        })?;
+        Ok(())
+    }
+
+    fn read_ring<T: Sized>(&self, addr: u64, data: &mut T) -> Result<()> {
+        self.memory.try_read_bytes(&addr, unsafe {
+            std::slice::from_raw_parts_mut((data as *mut T) as *mut u8, mem::size_of::<T>())
+        })?;
         Ok(())
     }
 
+    pub fn set_desc(&self, idx: u16, desc: &DescMeta) -> Result<()> {
+        self.write_ring(self.desc_addr(idx)?, desc)
+    }
+
     pub fn get_desc(&self, idx: u16) -> Result<DescMeta> {
         let mut desc = DescMeta::default();
-        SizedAccess::read(self.memory.deref(), &self.desc_addr(idx)?, &mut desc);
+        self.read_ring(self.desc_addr(idx)?, &mut desc)?;
         Ok(desc)
     }
 
     pub fn get_avail_idx(&self) -> Result<Wrapping<u16>> {
-        Ok(Wrapping(U16Access::read(
-            self.memory.deref(),
+        Ok(Wrapping(self.memory.try_read_u16_endian(
             &(self.get_avail_addr() + 2),
-        )))
+            Endian::Little,
+        )?))
     }
 
     pub fn set_avail_idx(&self, idx: u16) -> Result<()> {
-        U16Access::write(self.memory.deref(), &(self.get_avail_addr() + 2), idx);
+        self.memory
+            .try_write_u16_endian(&(self.get_avail_addr() + 2), idx, Endian::Little)?;
         Ok(())
     }
 
     pub fn set_avail_desc(&self, avail_idx: u16, desc_idx: u16) -> Result<()> {
         self.check_idx(desc_idx)?;
-        U16Access::write(
-            self.memory.deref(),
+        self.memory.try_write_u16_endian(
             &self.avail_elem_addr(avail_idx),
             desc_idx,
-        );
+            Endian::Little,
+        )?;
         Ok(())
     }
 
     pub fn get_used_idx(&self) -> Result<Wrapping<u16>> {
-        Ok(Wrapping(U16Access::read(
-            self.memory.deref(),
+        Ok(Wrapping(self.memory.try_read_u16_endian(
             &(self.get_used_addr() + 2),
-        )))
+            Endian::Little,
+        )?))
     }
 
     fn set_used_idx(&self, idx: u16) -> Result<()> {
-        U16Access::write(self.memory.deref(), &(self.get_used_addr() + 2), idx);
+        self.memory
+            .try_write_u16_endian(&(self.get_used_addr() + 2), idx, Endian::Little)?;
         Ok(())
     }
 
@@ -268,18 +321,13 @@ impl Queue {
 
     pub fn get_used_elem(&self, used_idx: u16) -> Result<RingUsedMetaElem> {
         let mut elem = RingUsedMetaElem::default();
-        SizedAccess::read(
-            self.memory.deref(),
-            &self.used_elem_addr(used_idx),
-            &mut elem,
-        );
+        self.read_ring(self.used_elem_addr(used_idx), &mut elem)?;
         Ok(elem)
     }
 
     fn set_used_elem(&self, used_idx: u16, elem: &RingUsedMetaElem) -> Result<()> {
         self.check_idx(elem.id as u16)?;
-        SizedAccess::write(self.memory.deref(), &self.used_elem_addr(used_idx), elem);
-        Ok(())
+        self.write_ring(self.used_elem_addr(used_idx), elem)
     }
 
     pub fn set_used(&self, desc_idx: u16, len: u32) -> Result<()> {
@@ -342,7 +390,7 @@ impl Queue {
 
     pub fn avail_iter(&self) -> Result<AvailIter> {
         let mut header = RingMetaHeader { flags: 0, idx: 0 };
-        SizedAccess::read(self.memory.deref(), &self.get_avail_addr(), &mut header);
+        self.read_ring(self.get_avail_addr(), &mut header)?;
         Ok(AvailIter::new(
             self,
             Wrapping(header.idx),
@@ -398,33 +446,64 @@ impl Queue {
             read_buffer.resize(read_len, 0);
         }
         if write {
-            write_buffer.resize(write_len, 0);
-            let mut offset: usize = 0;
-            for desc in write_descs.iter() {
-                let next_offset = offset + desc.len as usize;
-                BytesAccess::read(
-                    self.memory.deref(),
-                    &desc.addr,
-                    &mut write_buffer[offset..next_offset],
-                )
-                .unwrap();
-                offset = next_offset;
-            }
+            write_buffer.reserve(write_len);
+            self.visit_descs(write_descs, |s| write_buffer.extend_from_slice(s))?;
         }
         Ok((read_len, write_len))
     }
 
     pub fn copy_to(&self, read_descs: &Vec<DescMeta>, data: &Vec<u8>) -> Result<()> {
         let mut offset: usize = 0;
+        let mut descs = vec![];
         for desc in read_descs.iter() {
             let len = min(desc.len as usize, data.len() - offset);
-            let next_offset = offset + len;
-            BytesAccess::write(self.memory.deref(), &desc.addr, &data[offset..next_offset])
-                .unwrap();
-            if next_offset >= data.len() {
+            descs.push(DescMeta {
+                len: len as u32,
+                ..*desc
+            });
+            offset += len;
+            if offset >= data.len() {
                 break;
             }
-            offset = next_offset;
+        }
+        offset = 0;
+        self.visit_descs_mut(&descs, |s| {
+            s.copy_from_slice(&data[offset..offset + s.len()]);
+            offset += s.len();
+        })
+    }
+
+    //guest buffers of descs are passed to f in order without copying, memory which is not host
+    //mappable is bounced through a temporary buffer. f must not access the queue memory
+    pub fn visit_descs<F: FnMut(&[u8])>(&self, descs: &[DescMeta], mut f: F) -> Result<()> {
+        let mut bounce: Vec<u8> = vec![];
+        for desc in descs {
+            match self.memory.host_slice(&desc.addr, desc.len as usize) {
+                Ok(s) => f(&s),
+                Err(AccessError::NotMappable(_)) => {
+                    bounce.resize(desc.len as usize, 0);
+                    BytesAccess::read(self.memory.deref(), &desc.addr, &mut bounce)?;
+                    f(&bounce)
+                }
+                Err(e) => return Err(Error::MemError(e.to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn visit_descs_mut<F: FnMut(&mut [u8])>(&self, descs: &[DescMeta], mut f: F) -> Result<()> {
+        let mut bounce: Vec<u8> = vec![];
+        for desc in descs {
+            match self.memory.host_slice_mut(&desc.addr, desc.len as usize) {
+                Ok(mut s) => f(&mut s),
+                Err(AccessError::NotMappable(_)) => {
+                    bounce.resize(desc.len as usize, 0);
+                    BytesAccess::read(self.memory.deref(), &desc.addr, &mut bounce)?;
+                    f(&mut bounce);
+                    BytesAccess::write(self.memory.deref(), &desc.addr, &bounce)?;
+                }
+                Err(e) => return Err(Error::MemError(e.to_string())),
+            }
         }
         Ok(())
     }
@@ -590,8 +669,15 @@ impl DefaultQueueServer {
 impl QueueServer for DefaultQueueServer {
     fn init_queue(&mut self, queue: &Queue) -> Result<()> {
         let desc_region = self.heap.alloc(queue.desc_table_size() as u64, 8)?;
-        let avail_region = self.heap.alloc(queue.avail_ring_size() as u64, 2)?;
-        let used_region = self.heap.alloc(queue.used_ring_size() as u64, 4)?;
+        //rings start with a header
+        let avail_region = self.heap.alloc(
+            (queue.avail_ring_size() + mem::size_of::<RingMetaHeader>()) as u64,
+            2,
+        )?;
+        let used_region = self.heap.alloc(
+            (queue.used_ring_size() + mem::size_of::<RingMetaHeader>()) as u64,
+            4,
+        )?;
         queue.set_desc_addr(desc_region.info.base);
         queue.set_avail_addr(avail_region.info.base);
         queue.set_used_addr(used_region.info.base);
@@ -703,7 +789,7 @@ impl QueueServer for DefaultQueueServer {
 }
 
 #[cfg(test)]
-use crate::memory::region::GHEAP;
+use crate::memory::region::{U16Access, GHEAP};
 
 #[cfg(test)]
 struct DummyClient();
@@ -908,3 +994,106 @@ fn add_to_queue_test() {
         }
     }
 }
+
+#[test]
+fn extract_copy_to_test() {
+    //host mappable and bounced memory
+    for memory in [
+        GHEAP.alloc(1024, 16).unwrap(),
+        GHEAP.lazy_alloc(1024, 16).unwrap(),
+    ]
+    .iter()
+    {
+        let queue = Queue::new(memory, QueueSetting { max_queue_size: 4 }, DummyClient());
+        let heap = Heap::new(memory);
+        let mut server = DefaultQueueServer::new(&heap);
+        server.init_queue(&queue).unwrap();
+        let out_mem = heap.alloc(7, 1).unwrap();
+        BytesAccess::write(out_mem.deref(), &out_mem.info.base, &[1, 2, 3, 4, 5, 6, 7]).unwrap();
+        let in_mem1 = heap.alloc(6, 1).unwrap();
+        let in_mem2 = heap.alloc(6, 1).unwrap();
+        let head = server
+            .add_to_queue(
+                &queue,
+                vec![out_mem.deref()].as_slice(),
+                vec![in_mem1.deref(), in_mem2.deref()].as_slice(),
+            )
+            .unwrap();
+
+        let mut read_buffer = vec![];
+        let mut write_buffer = vec![0xff; 3];
+        let mut read_descs = vec![];
+        let mut write_descs = vec![];
+        assert_eq!(
+            queue
+                .extract(
+                    head,
+                    &mut read_buffer,
+                    &mut write_buffer,
+                    &mut read_descs,
+                    &mut write_descs,
+                    true,
+                    true
+                )
+                .unwrap(),
+            (12, 7)
+        );
+        assert_eq!(write_buffer, vec![1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(read_buffer.len(), 12);
+
+        queue
+            .copy_to(&read_descs, &(10..18).collect::<Vec<u8>>())
+            .unwrap();
+        let mut data = [0u8; 6];
+        BytesAccess::read(in_mem1.deref(), &in_mem1.info.base, &mut data).unwrap();
+        assert_eq!(data, [10, 11, 12, 13, 14, 15]);
+        BytesAccess::read(in_mem2.deref(), &in_mem2.info.base, &mut data).unwrap();
+        assert_eq!(data, [16, 17, 0, 0, 0, 0]);
+    }
+}
+
+#[test]
+fn iommu_queue_test() {
+    use crate::memory::attr::Perm;
+    let memory = GHEAP.alloc(0x1000, 0x1000).unwrap();
+    //the device sees the memory at 0x10000 through its own view
+    let view = Region::iommu(0x10000, 0x1000, &memory);
+    view.iommu_map(0x10000, memory.info.base, 0x1000, Perm::RW)
+        .unwrap();
+    let queue = Queue::new(&view, QueueSetting { max_queue_size: 4 }, DummyClient());
+    let heap = Heap::new(&view);
+    let mut server = DefaultQueueServer::new(&heap);
+    server.init_queue(&queue).unwrap();
+    assert!(queue.get_desc_addr() >= 0x10000);
+    let out_mem = heap.alloc(4, 1).unwrap();
+    BytesAccess::write(out_mem.deref(), &out_mem.info.base, &[1, 2, 3, 4]).unwrap();
+    let head = server
+        .add_to_queue(&queue, vec![out_mem.deref()].as_slice(), &[])
+        .unwrap();
+    let mut data = vec![];
+    let mut descs = vec![];
+    queue
+        .extract(
+            head,
+            &mut vec![],
+            &mut data,
+            &mut vec![],
+            &mut descs,
+            false,
+            true,
+        )
+        .unwrap();
+    assert_eq!(data, vec![1, 2, 3, 4]);
+    let mut raw = [0u8; 4];
+    BytesAccess::read(
+        memory.deref(),
+        &(out_mem.info.base - 0x10000 + memory.info.base),
+        &mut raw,
+    )
+    .unwrap();
+    assert_eq!(raw, [1, 2, 3, 4]);
+    //faults are returned to the device
+    view.iommu_unmap(0x10000).unwrap();
+    assert!(matches!(queue.get_avail_idx(), Err(Error::MemError(_))));
+    assert!(matches!(queue.set_used(head, 0), Err(Error::MemError(_))));
+}
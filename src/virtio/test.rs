@@ -250,7 +250,7 @@ fn simple_device_test() {
     assert_eq!(device.read().unwrap(), vec![]);
     device.write(&(0xdeadbeaf as u32).to_le_bytes()).unwrap();
     device.write(&(0xdeadbeaf as u32).to_le_bytes()).unwrap();
-    // for info in heap.allocator.lock().unwrap().alloced_blocks.iter() {
-    //     println!("{:#x?}", info.car());
+    // for info in heap.allocator.lock().unwrap().alloced_list() {
+    //     println!("{:#x?}", info);
     // }
 }
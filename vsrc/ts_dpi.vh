@@ -1,5 +1,31 @@
 `ifndef __TS_DPI_VH__
 `define __TS_DPI_VH__
+`define TS_IMAGE_BIN 0
+`define TS_IMAGE_READMEMH 1
+`define TS_IMAGE_IHEX 2
+`define TS_IMAGE_SREC 3
+`define TS_READONLY_FAULT 0
+`define TS_READONLY_IGNORE 1
+`define TS_ALLOC_FIRST_FIT 0
+`define TS_ALLOC_BEST_FIT 1
+`define TS_ALLOC_NEXT_FIT 2
+`define TS_ALLOC_BUDDY 3
+`define TS_ALLOC_SLAB 4
+`define TS_ALLOC_BUMP 5
+`define TS_ALLOC_OK 0
+`define TS_ALLOC_OUT_OF_MEMORY 1
+`define TS_ALLOC_NOT_FREE 2
+`define TS_ALLOC_INVALID_FREE 3
+`define TS_ALLOC_DOUBLE_FREE 4
+`define TS_ALLOC_INVALID_REALLOC 5
+`define TS_ALLOC_PARTIAL_FREE 6
+`define TS_ALLOC_SHARED 7
+`define TS_ALLOC_BACKING 8
+`define TS_UNMAPPED_PANIC 0
+`define TS_UNMAPPED_FILL 1
+`define TS_UNMAPPED_CALLBACK 2
+`define TS_FAULT_READ 0
+`define TS_FAULT_WRITE 1
 import "DPI-C" function chandle tsv_new_allocator(
     input longint unsigned base,
     input longint unsigned size
@@ -9,6 +35,18 @@ import "DPI-C" function chandle tsv_new_locked_allocator
     input longint unsigned base,
     input longint unsigned size
 );
+import "DPI-C" function chandle tsv_new_allocator_with_policy
+(
+    input longint unsigned base,
+    input longint unsigned size,
+    input int unsigned policy
+);
+import "DPI-C" function chandle tsv_new_locked_allocator_with_policy
+(
+    input longint unsigned base,
+    input longint unsigned size,
+    input int unsigned policy
+);
 import "DPI-C" function longint unsigned tsv_alloc_addr
 (
     input chandle  allocator,
@@ -20,18 +58,91 @@ import "DPI-C" function void tsv_free_addr
     input chandle allocator,
     input longint unsigned addr
 );
+import "DPI-C" function longint unsigned tsv_alloc_addr_labeled
+(
+    input chandle  allocator,
+    input longint unsigned size,
+    input longint unsigned align,
+    input string label
+);
+import "DPI-C" function void tsv_reserve_addr
+(
+    input chandle allocator,
+    input longint unsigned base,
+    input longint unsigned size
+);
+import "DPI-C" function longint unsigned tsv_realloc_addr
+(
+    input chandle allocator,
+    input longint unsigned addr,
+    input longint unsigned size,
+    input longint unsigned align
+);
+import "DPI-C" function void tsv_free_addr_range
+(
+    input chandle allocator,
+    input longint unsigned base,
+    input longint unsigned size
+);
+import "DPI-C" function int unsigned tsv_try_alloc_addr
+(
+    input chandle allocator,
+    input longint unsigned size,
+    input longint unsigned align,
+    output longint unsigned addr
+);
+import "DPI-C" function int unsigned tsv_try_free_addr
+(
+    input chandle allocator,
+    input longint unsigned addr
+);
+import "DPI-C" function int unsigned tsv_try_reserve_addr
+(
+    input chandle allocator,
+    input longint unsigned base,
+    input longint unsigned size
+);
+import "DPI-C" function int unsigned tsv_try_realloc_addr
+(
+    input chandle allocator,
+    input longint unsigned addr,
+    input longint unsigned size,
+    input longint unsigned align,
+    output longint unsigned new_addr
+);
+import "DPI-C" function int unsigned tsv_try_free_addr_range
+(
+    input chandle allocator,
+    input longint unsigned base,
+    input longint unsigned size
+);
+import "DPI-C" function void tsv_allocator_print_stats(input chandle allocator);
+import "DPI-C" function void tsv_allocator_dump(input chandle allocator);
 
 
 import "DPI-C" function chandle tsv_space();
 import "DPI-C" function chandle tsv_get_region(input chandle space , input string name);
+import "DPI-C" function void tsv_set_region_enabled(input chandle space , input string name, input int unsigned enabled);
 import "DPI-C" function chandle tsv_alloc_region(input chandle heap, input longint unsigned size, input longint unsigned align);
 import "DPI-C" function chandle tsv_root_region(input longint unsigned size, input longint unsigned align);
 import "DPI-C" function chandle tsv_lazy_root_region(input longint unsigned size, input longint unsigned align);
+import "DPI-C" function int unsigned tsv_try_alloc_region(input chandle heap, input longint unsigned size, input longint unsigned align, output chandle region);
+import "DPI-C" function int unsigned tsv_try_root_region(input longint unsigned size, input longint unsigned align, output chandle region);
+import "DPI-C" function int unsigned tsv_try_lazy_root_region(input longint unsigned size, input longint unsigned align, output chandle region);
 import "DPI-C" function chandle tsv_map_region(input chandle region, input longint unsigned base);
 import "DPI-C" function chandle tsv_map_region_partial(input chandle region, input longint unsigned base, input longint unsigned offset, input longint unsigned size);
 import "DPI-C" function chandle tsv_heap(input chandle region);
+import "DPI-C" function chandle tsv_heap_with_policy(input chandle region, input int unsigned policy);
 import "DPI-C" function void tsv_free_region(input chandle region);
 import "DPI-C" function void tsv_free_heap(input chandle heap);
+import "DPI-C" function chandle tsv_reserve_region(input chandle heap, input longint unsigned base, input longint unsigned size);
+import "DPI-C" function void tsv_realloc_region(input chandle heap, input chandle region, input longint unsigned size, input longint unsigned align);
+import "DPI-C" function chandle tsv_free_region_range(input chandle heap, input chandle region, input longint unsigned base, input longint unsigned size);
+import "DPI-C" function int unsigned tsv_try_reserve_region(input chandle heap, input longint unsigned base, input longint unsigned size, output chandle region);
+import "DPI-C" function int unsigned tsv_try_realloc_region(input chandle heap, input chandle region, input longint unsigned size, input longint unsigned align);
+import "DPI-C" function int unsigned tsv_try_free_region_range(input chandle heap, input chandle region, input longint unsigned base, input longint unsigned size, output chandle upper);
+import "DPI-C" function void tsv_heap_print_stats(input chandle heap);
+import "DPI-C" function void tsv_heap_dump(input chandle heap);
 import "DPI-C" function longint unsigned tsv_region_base(input chandle region);
 import "DPI-C" function longint unsigned tsv_region_size(input chandle region);
 
@@ -52,4 +163,33 @@ import "DPI-C" function void tsv_space_read_u8(input chandle  space, input longi
 import "DPI-C" function void tsv_space_read_u16(input chandle  space, input longint unsigned addr, output shortint unsigned data);
 import "DPI-C" function void tsv_space_read_u32(input chandle  space, input longint unsigned addr, output int unsigned data);
 import "DPI-C" function void tsv_space_read_u64(input chandle  space, input longint unsigned addr, output longint unsigned data);
+//`TS_UNMAPPED_CALLBACK calls tsv_unmapped_fault, which the testbench defines and exports where it
+//accesses the space:
+//export "DPI-C" function tsv_unmapped_fault;
+//function int tsv_unmapped_fault(input int unsigned kind, input longint unsigned addr, input int unsigned size, output longint unsigned value);
+//non-zero completes the access with value, 0 raises a bus error
+import "DPI-C" function void tsv_space_set_unmapped_policy(input chandle  space, input int unsigned policy, input longint unsigned value);
+import "DPI-C" function int tsv_space_last_fault(input chandle  space, output int unsigned kind, output longint unsigned addr, output int unsigned size);
+import "DPI-C" function void tsv_space_clear_last_fault(input chandle  space);
+
+//null if the file can not be loaded
+import "DPI-C" function chandle tsv_load_elf(input chandle space, input string file_name);
+import "DPI-C" function longint unsigned tsv_elf_entry(input chandle elf);
+import "DPI-C" function int tsv_elf_symbol(input chandle elf, input string name, output longint unsigned addr);
+import "DPI-C" function void tsv_free_elf(input chandle elf);
+
+import "DPI-C" function void tsv_region_load_image(input chandle region, input string file_name, input int unsigned format, input int unsigned word_size, input longint unsigned offset);
+import "DPI-C" function void tsv_space_load_image(input chandle space, input string file_name, input int unsigned format, input int unsigned word_size, input longint unsigned offset);
+import "DPI-C" function void tsv_region_dump_image(input chandle region, input string file_name, input int unsigned format, input int unsigned word_size, input longint unsigned addr, input longint unsigned size, input longint unsigned offset);
+import "DPI-C" function void tsv_space_dump_image(input chandle space, input string file_name, input int unsigned format, input int unsigned word_size, input longint unsigned addr, input longint unsigned size, input longint unsigned offset);
+
+import "DPI-C" function chandle tsv_map_region_read_only(input chandle region, input longint unsigned base, input int unsigned policy);
+import "DPI-C" function void tsv_region_backdoor_write_u8(input chandle  region, input longint unsigned addr, input byte unsigned data);
+import "DPI-C" function void tsv_region_backdoor_write_u16(input chandle  region, input longint unsigned addr, input shortint unsigned data);
+import "DPI-C" function void tsv_region_backdoor_write_u32(input chandle  region, input longint unsigned addr, input int unsigned data);
+import "DPI-C" function void tsv_region_backdoor_write_u64(input chandle  region, input longint unsigned addr, input longint unsigned data);
+import "DPI-C" function void tsv_space_backdoor_write_u8(input chandle  space, input longint unsigned addr, input byte unsigned data);
+import "DPI-C" function void tsv_space_backdoor_write_u16(input chandle  space, input longint unsigned addr, input shortint unsigned data);
+import "DPI-C" function void tsv_space_backdoor_write_u32(input chandle  space, input longint unsigned addr, input int unsigned data);
+import "DPI-C" function void tsv_space_backdoor_write_u64(input chandle  space, input longint unsigned addr, input longint unsigned data);
 `endif
\ No newline at end of file